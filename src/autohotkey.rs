@@ -0,0 +1,57 @@
+use crate::{
+    event::{Button, MacroEventType, with_gaps},
+    key::{autohotkey_key_name, comment_text},
+    macro_manager::SavedMacro,
+};
+
+/// 把宏导出为 AutoHotkey v2 脚本
+///
+/// 鼠标坐标按整个屏幕计算, 事件间隔和延时事件转换为 `Sleep`.
+/// AutoHotkey 不支持的键以注释的形式保留在脚本中.
+pub fn export(saved_macro: &SavedMacro) -> String {
+    let mut script = String::from("#Requires AutoHotkey v2.0\n");
+    script.push_str(&format!(
+        "; Exported from mousepilot: {}\n",
+        comment_text(&saved_macro.name)
+    ));
+    if let Some(screen) = saved_macro.screen {
+        script.push_str(&format!("; Recorded on a {}x{} screen\n", screen.width, screen.height));
+    }
+    script.push_str("CoordMode \"Mouse\", \"Screen\"\nSetKeyDelay -1\n\n");
+
+    for (gap, event) in with_gaps(&saved_macro.events) {
+        if gap > 0 {
+            script.push_str(&format!("Sleep {gap}\n"));
+        }
+        let line = match &event.event_type {
+            MacroEventType::MouseMove { x, y } => format!("MouseMove {x}, {y}, 0\n"),
+            MacroEventType::MouseClick { button, pressed } => {
+                let button = match button {
+                    Button::Left => "Left",
+                    Button::Middle => "Middle",
+                    Button::Right => "Right",
+                };
+                let state = if *pressed { "Down" } else { "Up" };
+                format!("Click \"{button} {state}\"\n")
+            },
+            MacroEventType::KeyPress { key } | MacroEventType::KeyRelease { key } => {
+                let state = match event.event_type {
+                    MacroEventType::KeyPress { .. } => "down",
+                    _ => "up",
+                };
+                match autohotkey_key_name(key) {
+                    Some(name) => format!("Send \"{{{} {state}}}\"\n", escape(name)),
+                    None => format!("; unsupported key: {} {state}\n", comment_text(key)),
+                }
+            },
+            MacroEventType::Delay { duration_ms } => format!("Sleep {duration_ms}\n"),
+        };
+        script.push_str(&line);
+    }
+    script
+}
+
+/// AutoHotkey v2 字符串中反引号和双引号需要转义
+fn escape(text: &str) -> String {
+    text.replace('`', "``").replace('"', "`\"")
+}
@@ -0,0 +1,135 @@
+use std::{fs, io::BufReader, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    event::MacroStats,
+    macro_manager::{MacroError, SavedMacro, unix_now},
+    storage,
+};
+
+/// 宏包文件的格式标识
+pub const BUNDLE_FORMAT: &str = "mousepilot-bundle";
+
+/// 当前宏包版本
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// 宏包的默认扩展名
+pub const BUNDLE_EXTENSION: &str = "mpbundle";
+
+type Result<T, E = MacroError> = std::result::Result<T, E>;
+
+/// 宏包清单中的一项
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleEntry {
+    /// 宏名称, 不带库名前缀
+    pub name: String,
+    /// 导出时所在的分组, 不带库名前缀
+    pub group: String,
+    /// 宏数据的 CRC32 校验值, 形如 `crc32:1a2b3c4d`
+    pub checksum: String,
+    pub stats: MacroStats,
+    /// 宏数据, 导入时和普通宏文件一样经过迁移
+    #[serde(rename = "macro")]
+    pub data: Value,
+}
+
+/// 可分享的宏包: 单个 JSON 文件, 包含清单、校验值和元数据
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bundle {
+    pub format: String,
+    pub version: u32,
+    /// 导出时间(Unix 秒)
+    pub created_at: u64,
+    /// 导出时的程序版本
+    pub app_version: String,
+    pub entries: Vec<BundleEntry>,
+}
+
+impl Default for Bundle {
+    fn default() -> Self {
+        Self {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            created_at: unix_now(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl Bundle {
+    /// 添加一个宏, `saved_macro.name` 应为不带库名前缀的名称
+    pub fn push(&mut self, group: &str, saved_macro: &SavedMacro) -> Result<()> {
+        let data = serde_json::to_value(saved_macro)?;
+        self.entries.push(BundleEntry {
+            name: saved_macro.name.clone(),
+            group: group.to_string(),
+            checksum: checksum(&data)?,
+            stats: saved_macro.stats.clone(),
+            data,
+        });
+        Ok(())
+    }
+
+    pub fn read_from(path: &Path) -> Result<Self> {
+        let bundle: Self = serde_json::from_reader(BufReader::new(fs::File::open(path)?))?;
+        bundle.verify()?;
+        Ok(bundle)
+    }
+
+    pub fn write_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        storage::write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// 检查格式、版本和每个宏的校验值, 并返回迁移后的宏
+    pub fn verify(&self) -> Result<Vec<SavedMacro>> {
+        if self.format != BUNDLE_FORMAT {
+            return Err(MacroError::Format(format!("not a macro bundle: {}", self.format)));
+        }
+        if self.version > BUNDLE_VERSION {
+            return Err(MacroError::Format(format!(
+                "bundle version {} is newer than supported version {BUNDLE_VERSION}",
+                self.version
+            )));
+        }
+
+        let mut macros = Vec::with_capacity(self.entries.len());
+        for entry in self.entries.iter() {
+            if checksum(&entry.data)? != entry.checksum {
+                return Err(MacroError::Format(format!("checksum mismatch: {}", entry.name)));
+            }
+            let saved_macro = SavedMacro::from_value(entry.data.clone())?;
+            if saved_macro.name != entry.name {
+                return Err(MacroError::Format(format!(
+                    "manifest name {} does not match macro {}",
+                    entry.name, saved_macro.name
+                )));
+            }
+            macros.push(saved_macro);
+        }
+        Ok(macros)
+    }
+}
+
+/// 宏数据的校验值, 按紧凑 JSON 计算
+pub fn checksum(data: &Value) -> Result<String> {
+    let json = serde_json::to_vec(data)?;
+    Ok(format!("crc32:{:08x}", crc32fast::hash(&json)))
+}
+
+/// 导入结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// 导入后的宏名称
+    pub imported: Vec<String>,
+    /// 因同名而跳过的宏
+    pub skipped: Vec<String>,
+    /// 快捷键被占用或是保留的组合, 导入后去掉了快捷键的宏
+    pub dropped_hotkeys: Vec<String>,
+}
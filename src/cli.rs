@@ -0,0 +1,426 @@
+use std::{
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::{
+    bundle::Bundle,
+    event::{MacroEvent, MacroEventType},
+    filter::MacroFilter,
+    hotkey::Hotkey,
+    macro_manager::{ConflictPolicy, MacroManager, SavedMacro},
+    player::MacroPlayer,
+    recorder::MacroRecorder,
+    script::ScriptFormat,
+    settings::Settings,
+    state::AppState,
+    storage::StorageFormat,
+    xdotool,
+};
+
+/// 命令行帮助
+pub const USAGE: &str = "\
+usage: mousepilot [--macros-dir <path>] [--library <spec>] [--storage-format <format>] [--ipc-socket <path>] [command]
+
+Without a command the window is opened.
+
+commands:
+  list [query] [--group <group>] [--long]
+  play <name...> [--repeat <n>] [--interval <ms>] [--speed <x>]
+  record --out <name> [--duration <time>] [--group <group>] [--on-conflict skip|overwrite|rename]
+  validate [name...]
+  export <file> [name...]
+  import <file> [--group <group>] [--on-conflict skip|overwrite|rename]
+  export-script <xdotool|ahk|pyautogui> <name> <file>
+  import-xdotool <file> [name]
+  convert <json|text|compact> [name...]
+  remote <method> [params-json]   call the running window over its control socket
+  help
+";
+
+/// 所有子命令
+pub const COMMANDS: [&str; 11] = [
+    "list",
+    "play",
+    "record",
+    "validate",
+    "export",
+    "import",
+    "export-script",
+    "import-xdotool",
+    "convert",
+    "remote",
+    "help",
+];
+
+/// 第一个子命令的位置; 其他不属于选项的参数(如桌面启动器附加的参数)跳过
+pub fn command_index(args: &[String]) -> Option<usize> {
+    let mut i = 0;
+    while i < args.len() {
+        if COMMANDS.contains(&args[i].as_str()) {
+            return Some(i);
+        }
+        // `--flag value` 跳过值, `--flag=value` 和其他参数只占一个
+        i += if args[i].starts_with("--") && !args[i].contains('=') { 2 } else { 1 };
+    }
+    None
+}
+
+/// 子命令的参数: 位置参数和 `--flag value` / `--flag=value` 形式的选项
+struct CommandArgs<'a> {
+    positional: Vec<&'a str>,
+    options: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> CommandArgs<'a> {
+    /// `flags` 是需要值的选项, `switches` 是不带值的开关
+    fn parse(args: &'a [String], flags: &[&str], switches: &[&str]) -> Result<Self> {
+        let mut parsed = Self {
+            positional: Vec::new(),
+            options: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                parsed.positional.push(arg);
+                continue;
+            }
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            if switches.contains(&flag) && inline.is_none() {
+                parsed.options.push((flag, ""));
+            } else if flags.contains(&flag) {
+                let value = match inline {
+                    Some(value) => value,
+                    None => args.next().ok_or_else(|| anyhow!("{flag} requires a value"))?,
+                };
+                parsed.options.push((flag, value));
+            } else {
+                bail!("unknown argument: {arg}");
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// 选项的最后一个值
+    fn value(&self, flag: &str) -> Option<&'a str> {
+        self.options.iter().rev().find(|(f, _)| *f == flag).map(|(_, v)| *v)
+    }
+
+    fn has(&self, flag: &str) -> bool {
+        self.options.iter().any(|(f, _)| *f == flag)
+    }
+
+    fn number<T: std::str::FromStr>(&self, flag: &str) -> Result<Option<T>> {
+        self.value(flag)
+            .map(|v| v.parse().map_err(|_| anyhow!("invalid value for {flag}: {v}")))
+            .transpose()
+    }
+}
+
+/// 去掉末尾用来结束录制的 Enter, 否则回放后 Enter 会一直处于按下状态
+pub fn trim_stop_key(events: &mut Vec<MacroEvent>) {
+    while let Some(event) = events.last()
+        && let MacroEventType::KeyPress { key } | MacroEventType::KeyRelease { key } =
+            &event.event_type
+        && matches!(key.as_str(), "Enter" | "NumpadEnter")
+    {
+        events.pop();
+    }
+}
+
+/// 解析时长: `500ms`、`1.5s`、`2m`, 不带单位时按秒计算; 返回毫秒
+pub fn parse_duration(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let (value, scale) = if let Some(ms) = text.strip_suffix("ms") {
+        (ms, 1.0)
+    } else if let Some(s) = text.strip_suffix('s') {
+        (s, 1000.0)
+    } else if let Some(m) = text.strip_suffix('m') {
+        (m, 60_000.0)
+    } else {
+        (text, 1000.0)
+    };
+    let value: f64 = value.parse().ok()?;
+    if !value.is_finite() || value < 0.0 {
+        return None;
+    }
+    Some((value * scale).round() as u64)
+}
+
+fn parse_policy(name: &str) -> Result<ConflictPolicy> {
+    match name {
+        "skip" => Ok(ConflictPolicy::Error),
+        "overwrite" => Ok(ConflictPolicy::Overwrite),
+        "rename" => Ok(ConflictPolicy::AutoSuffix),
+        other => bail!("unknown conflict policy: {other}"),
+    }
+}
+
+/// 不启动界面, 直接执行子命令, 结果写到 `out`
+pub fn run(settings: &Settings, command: &[String], out: &mut impl Write) -> Result<()> {
+    let Some((name, args)) = command.split_first() else {
+        bail!("missing command");
+    };
+    if matches!(name.as_str(), "help" | "--help" | "-h") {
+        write!(out, "{USAGE}")?;
+        return Ok(());
+    }
+    // 发给正在运行的窗口, 不需要打开宏目录
+    #[cfg(unix)]
+    if name == "remote" {
+        return remote(settings, args, out);
+    }
+
+    let manager = MacroManager::open_with_libraries(
+        settings.macros_dir().to_string_lossy(),
+        &settings.libraries,
+    )?;
+    manager.set_default_format(settings.storage_format);
+    match name.as_str() {
+        "list" => list(&manager, args, out)?,
+        "play" => play(&manager, args, out)?,
+        "record" => record(&manager, args, out)?,
+        "validate" => validate(&manager, args, out)?,
+        "export" => {
+            let Some((file, names)) = args.split_first() else {
+                bail!("usage: export <file> [name...]");
+            };
+            let names = if names.is_empty() { manager.get_macro_names() } else { names.to_vec() };
+            manager.export_bundle(&names)?.write_to(Path::new(file))?;
+            writeln!(out, "Exported {} macros to {file}", names.len())?;
+        },
+        "import" => {
+            let args = CommandArgs::parse(args, &["--group", "--on-conflict"], &[])?;
+            let [file] = args.positional[..] else {
+                bail!(
+                    "usage: import <file> [--group <group>] [--on-conflict skip|overwrite|rename]"
+                );
+            };
+            let group = args.value("--group").unwrap_or_default();
+            let policy = parse_policy(args.value("--on-conflict").unwrap_or("skip"))?;
+            let bundle = Bundle::read_from(Path::new(file))?;
+            let shortcuts = AppState::load_shortcuts(&settings.shortcuts);
+            let taken: Vec<Hotkey> = shortcuts.into_iter().map(|s| s.hotkey).collect();
+            let report = manager.import_bundle(&bundle, group, policy, &taken)?;
+            writeln!(out, "Imported {} macros", report.imported.len())?;
+            for name in report.skipped.iter() {
+                writeln!(out, "Skipped existing macro: {name}")?;
+            }
+            for name in report.dropped_hotkeys.iter() {
+                writeln!(out, "Removed conflicting hotkey from macro: {name}")?;
+            }
+        },
+        "export-script" => {
+            let [format, name, file] = args else {
+                bail!("usage: export-script <xdotool|ahk|pyautogui> <name> <file>");
+            };
+            let Some(format) = ScriptFormat::from_name(format) else {
+                bail!("unknown script format: {format}");
+            };
+            let saved_macro = manager.get_macro(name)?;
+            std::fs::write(file, format.export(&saved_macro))?;
+            writeln!(out, "Exported {name} to {file}")?;
+        },
+        "import-xdotool" => {
+            let (file, name) = match args {
+                [file] => (file, None),
+                [file, name] => (file, Some(name.clone())),
+                _ => bail!("usage: import-xdotool <file> [name]"),
+            };
+            let path = Path::new(file);
+            let name = name.unwrap_or_else(|| {
+                path.file_stem().unwrap_or_default().to_string_lossy().to_string()
+            });
+            let saved_macro = xdotool::import(&name, &std::fs::read_to_string(path)?)?;
+            let name = manager.save(saved_macro, ConflictPolicy::AutoSuffix)?;
+            writeln!(out, "Imported {file} as {name}")?;
+        },
+        "convert" => {
+            let Some((format, names)) = args.split_first() else {
+                bail!("usage: convert <json|text|compact> [name...]");
+            };
+            let Some(format) = StorageFormat::from_name(format) else {
+                bail!("unknown storage format: {format}");
+            };
+            let names = if names.is_empty() { manager.get_macro_names() } else { names.to_vec() };
+            let mut converted = 0;
+            for name in names.iter() {
+                // 只读库中的宏跳过
+                if manager.is_read_only(name) {
+                    continue;
+                }
+                manager.convert_macro(name, format)?;
+                converted += 1;
+            }
+            writeln!(out, "Converted {converted} macros to {}", format.name())?;
+        },
+        other => bail!("unknown command: {other}\n\n{USAGE}"),
+    }
+    Ok(())
+}
+
+/// 调用控制接口的方法, 结果按 JSON 输出
+#[cfg(unix)]
+fn remote(settings: &Settings, args: &[String], out: &mut impl Write) -> Result<()> {
+    let (method, params) = match args {
+        [method] => (method, serde_json::Value::Null),
+        [method, params] => (method, serde_json::from_str(params)?),
+        _ => bail!("usage: remote <method> [params-json]"),
+    };
+    let mut client = crate::ipc::IpcClient::connect(&settings.ipc_socket())?;
+    let result = client.call(method, params)?;
+    writeln!(out, "{}", serde_json::to_string_pretty(&result)?)?;
+    Ok(())
+}
+
+/// 列出宏名称, 每行一个; `--long` 时附带分组、事件数、时长和修改时间, 用制表符分隔
+fn list(manager: &MacroManager, args: &[String], out: &mut impl Write) -> Result<()> {
+    let args = CommandArgs::parse(args, &["--group"], &["--long"])?;
+    let filter = MacroFilter {
+        query: args.positional.join(" "),
+        ..Default::default()
+    };
+    let mut macros = filter.apply(manager.get_all_macros());
+    if let Some(group) = args.value("--group") {
+        let names = manager.get_macro_names_in_group(group);
+        macros.retain(|m| names.contains(&m.name));
+    }
+
+    for saved_macro in macros {
+        if args.has("--long") {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}ms\t{}",
+                saved_macro.name,
+                manager.get_macro_group(&saved_macro.name),
+                saved_macro.stats.event_count,
+                saved_macro.stats.duration_ms,
+                saved_macro.updated_at
+            )?;
+        } else {
+            writeln!(out, "{}", saved_macro.name)?;
+        }
+    }
+    Ok(())
+}
+
+/// 按顺序播放宏, 播放完才返回
+fn play(manager: &MacroManager, args: &[String], out: &mut impl Write) -> Result<()> {
+    let args = CommandArgs::parse(args, &["--repeat", "--interval", "--speed"], &[])?;
+    if args.positional.is_empty() {
+        bail!("usage: play <name...> [--repeat <n>] [--interval <ms>] [--speed <x>]");
+    }
+    let repeat = args.number("--repeat")?.unwrap_or(1u32);
+    let interval = args.number("--interval")?.unwrap_or(0u64);
+    let speed = args.number("--speed")?.unwrap_or(1.0f64);
+    if !speed.is_finite() || speed <= 0.0 {
+        bail!("speed must be a positive number: {speed}");
+    }
+
+    // 名称按命令行顺序播放, 可以重复
+    let macros = args
+        .positional
+        .iter()
+        .map(|name| manager.get_macro(name))
+        .collect::<Result<Vec<Arc<SavedMacro>>, _>>()?;
+    let total: u64 = macros.iter().map(|m| m.stats.duration_ms).sum();
+    writeln!(
+        out,
+        "Playing {} macros ({}ms at {speed}x), repeat {repeat}",
+        macros.len(),
+        (total as f64 / speed).round() as u64
+    )?;
+    out.flush()?;
+    MacroPlayer::new(macros, interval).with_speed(speed).play(repeat)?;
+    writeln!(out, "Done")?;
+    Ok(())
+}
+
+/// 录制到指定时长, 没有指定时长时按回车结束
+fn record(manager: &MacroManager, args: &[String], out: &mut impl Write) -> Result<()> {
+    let args =
+        CommandArgs::parse(args, &["--out", "--duration", "--group", "--on-conflict"], &[])?;
+    let (Some(name), []) = (args.value("--out"), &args.positional[..]) else {
+        bail!(
+            "usage: record --out <name> [--duration <time>] [--group <group>] \
+             [--on-conflict skip|overwrite|rename]"
+        );
+    };
+    let duration = args
+        .value("--duration")
+        .map(|d| parse_duration(d).ok_or_else(|| anyhow!("invalid duration: {d}")))
+        .transpose()?;
+    let policy = parse_policy(args.value("--on-conflict").unwrap_or("skip"))?;
+    // 提前检查, 免得录完才发现名称不能用
+    if policy == ConflictPolicy::Error && manager.macro_exists(name) {
+        bail!("macro already exists: {name}");
+    }
+
+    // 命令行没有界面快捷键, 所有按键都录制
+    let recorder = MacroRecorder::new(Default::default());
+    recorder.start_recording()?;
+    match duration {
+        Some(ms) => {
+            writeln!(out, "Recording for {ms}ms...")?;
+            out.flush()?;
+            thread::sleep(Duration::from_millis(ms));
+        },
+        None => {
+            writeln!(out, "Recording, press Enter to stop...")?;
+            out.flush()?;
+            io::stdin().read_line(&mut String::new())?;
+        },
+    }
+    recorder.stop_recording();
+
+    let mut events = recorder.get_events();
+    if duration.is_none() {
+        trim_stop_key(&mut events);
+    }
+    let saved_macro = SavedMacro::new(name, events).with_screen(recorder.get_screen_info());
+    let count = saved_macro.events.len();
+    let name = manager.save(saved_macro, policy)?;
+    if let Some(group) = args.value("--group") {
+        let group = manager.create_group(group)?;
+        manager.move_macro(&name, &group)?;
+    }
+    writeln!(out, "Recorded {count} events as {name}")?;
+    Ok(())
+}
+
+/// 检查宏文件能否完整读取, 有任何失败时返回错误
+fn validate(manager: &MacroManager, names: &[String], out: &mut impl Write) -> Result<()> {
+    let mut failed = 0;
+    // 不指定名称时也报告加载不了的文件
+    if names.is_empty() {
+        for failure in manager.get_load_report().failures.iter() {
+            writeln!(out, "FAIL {}: {}", failure.path.display(), failure.error)?;
+            failed += 1;
+        }
+    }
+
+    let names = if names.is_empty() { manager.get_macro_names() } else { names.to_vec() };
+    let mut ok = 0;
+    for name in names.iter() {
+        match manager.get_macro(name) {
+            Ok(_) => ok += 1,
+            Err(e) => {
+                writeln!(out, "FAIL {name}: {e}")?;
+                failed += 1;
+            },
+        }
+    }
+    writeln!(out, "{ok} macros OK, {failed} failed")?;
+    if failed > 0 {
+        bail!("{failed} macros failed validation");
+    }
+    Ok(())
+}
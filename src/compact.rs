@@ -0,0 +1,186 @@
+use std::io::{Read, Write};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    event::{Button, MacroEvent, MacroEventType},
+    macro_manager::{MacroError, SavedMacro},
+};
+
+/// 紧凑格式的文件标识
+const COMPACT_FORMAT: &str = "mousepilot-compact";
+
+/// 当前紧凑格式版本
+const COMPACT_VERSION: u32 = 1;
+
+type Result<T, E = MacroError> = std::result::Result<T, E>;
+
+/// 差分编码的事件, 第一个字段是与上一个事件的时间戳差值
+///
+/// 鼠标移动记录与上一次移动的坐标差, 密集的移动事件因此只剩很小的数字,
+/// 再经过 gzip 压缩后通常只有 JSON 的几十分之一.
+#[derive(Debug, Serialize, Deserialize)]
+enum CompactEvent {
+    /// 鼠标移动: 时间差, x 差, y 差
+    M(i64, i64, i64),
+    /// 鼠标按下: 时间差, 按键(1 左键, 2 中键, 3 右键)
+    D(i64, u8),
+    /// 鼠标抬起
+    U(i64, u8),
+    /// 键盘按下
+    K(i64, String),
+    /// 键盘抬起
+    R(i64, String),
+    /// 延时事件: 时间差, 时长
+    W(i64, u64),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CompactFile {
+    format: String,
+    version: u32,
+    /// 除事件外的宏数据, 读取时和普通宏文件一样经过迁移
+    #[serde(rename = "macro")]
+    meta: Value,
+    events: Vec<CompactEvent>,
+}
+
+/// 编码为 gzip 压缩的紧凑格式
+pub fn encode(saved_macro: &SavedMacro) -> Result<Vec<u8>> {
+    let mut meta = serde_json::to_value(saved_macro)?;
+    if let Some(obj) = meta.as_object_mut() {
+        obj.insert("events".to_string(), Value::Array(Vec::new()));
+    }
+
+    let mut last_timestamp = 0i128;
+    let mut last_position = (0i64, 0i64);
+    let mut events = Vec::with_capacity(saved_macro.events.len());
+    for event in saved_macro.events.iter() {
+        // 时间差超出 i64 时不能截断, 否则解码后时间戳会错乱
+        let timestamp = i128::try_from(event.timestamp).map_err(|_| timestamp_error(event))?;
+        let dt = i64::try_from(timestamp - last_timestamp).map_err(|_| timestamp_error(event))?;
+        last_timestamp = timestamp;
+        events.push(match &event.event_type {
+            MacroEventType::MouseMove { x, y } => {
+                let (x, y) = (*x as i64, *y as i64);
+                let (dx, dy) = (x - last_position.0, y - last_position.1);
+                last_position = (x, y);
+                CompactEvent::M(dt, dx, dy)
+            },
+            MacroEventType::MouseClick {
+                button,
+                pressed: true,
+            } => CompactEvent::D(dt, button_code(button)),
+            MacroEventType::MouseClick {
+                button,
+                pressed: false,
+            } => CompactEvent::U(dt, button_code(button)),
+            MacroEventType::KeyPress { key } => CompactEvent::K(dt, key.clone()),
+            MacroEventType::KeyRelease { key } => CompactEvent::R(dt, key.clone()),
+            MacroEventType::Delay { duration_ms } => CompactEvent::W(dt, *duration_ms),
+        });
+    }
+
+    let file = CompactFile {
+        format: COMPACT_FORMAT.to_string(),
+        version: COMPACT_VERSION,
+        meta,
+        events,
+    };
+    // 先整体序列化, 逐字节写入压缩流会非常慢
+    let json = serde_json::to_vec(&file)?;
+    let mut encoder = GzEncoder::new(Vec::with_capacity(json.len() / 8), Compression::default());
+    encoder.write_all(&json)?;
+    Ok(encoder.finish()?)
+}
+
+/// 解码紧凑格式
+pub fn decode(bytes: &[u8]) -> Result<SavedMacro> {
+    let file: CompactFile = read_file(bytes)?;
+    let mut saved_macro = SavedMacro::from_value(file.meta)?;
+    let mut timestamp = 0i128;
+    let mut position = (0i64, 0i64);
+    let mut events = Vec::with_capacity(file.events.len());
+    for event in file.events {
+        let (dt, event_type) = match event {
+            CompactEvent::M(dt, dx, dy) => {
+                // 文件内容不可信, 累加坐标时不能溢出
+                let moved = position.0.checked_add(dx).zip(position.1.checked_add(dy));
+                position = moved
+                    .ok_or_else(|| MacroError::Format(format!("coordinate overflow: {dx}, {dy}")))?;
+                let x = i32::try_from(position.0).map_err(|_| coordinate_error(position.0))?;
+                let y = i32::try_from(position.1).map_err(|_| coordinate_error(position.1))?;
+                (dt, MacroEventType::MouseMove { x, y })
+            },
+            CompactEvent::D(dt, code) => (
+                dt,
+                MacroEventType::MouseClick {
+                    button: button_from_code(code)?,
+                    pressed: true,
+                },
+            ),
+            CompactEvent::U(dt, code) => (
+                dt,
+                MacroEventType::MouseClick {
+                    button: button_from_code(code)?,
+                    pressed: false,
+                },
+            ),
+            CompactEvent::K(dt, key) => (dt, MacroEventType::KeyPress { key }),
+            CompactEvent::R(dt, key) => (dt, MacroEventType::KeyRelease { key }),
+            CompactEvent::W(dt, duration_ms) => (dt, MacroEventType::Delay { duration_ms }),
+        };
+        timestamp += dt as i128;
+        let timestamp = u128::try_from(timestamp)
+            .map_err(|_| MacroError::Format(format!("negative timestamp: {timestamp}")))?;
+        events.push(MacroEvent {
+            event_type,
+            timestamp,
+        });
+    }
+    saved_macro.events = events;
+    Ok(saved_macro)
+}
+
+fn read_file(bytes: &[u8]) -> Result<CompactFile> {
+    let mut json = Vec::new();
+    GzDecoder::new(bytes).read_to_end(&mut json)?;
+    let file: CompactFile = serde_json::from_slice(&json)?;
+    if file.format != COMPACT_FORMAT {
+        return Err(MacroError::Format(format!("not a compact macro file: {}", file.format)));
+    }
+    if file.version > COMPACT_VERSION {
+        return Err(MacroError::Format(format!(
+            "compact format version {} is newer than supported version {COMPACT_VERSION}",
+            file.version
+        )));
+    }
+    Ok(file)
+}
+
+fn button_code(button: &Button) -> u8 {
+    match button {
+        Button::Left => 1,
+        Button::Middle => 2,
+        Button::Right => 3,
+    }
+}
+
+fn button_from_code(code: u8) -> Result<Button> {
+    match code {
+        1 => Ok(Button::Left),
+        2 => Ok(Button::Middle),
+        3 => Ok(Button::Right),
+        _ => Err(MacroError::Format(format!("unknown mouse button: {code}"))),
+    }
+}
+
+fn coordinate_error(value: i64) -> MacroError {
+    MacroError::Format(format!("coordinate out of range: {value}"))
+}
+
+fn timestamp_error(event: &MacroEvent) -> MacroError {
+    MacroError::Format(format!("timestamp out of range: {}", event.timestamp))
+}
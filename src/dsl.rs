@@ -0,0 +1,549 @@
+use std::fmt::Write;
+
+use crate::{
+    event::{Button, MacroEvent, MacroEventType, MacroStats, with_gaps},
+    hotkey::Hotkey,
+    macro_manager::{SavedMacro, ScreenInfo},
+    migration,
+};
+
+/// 文本宏的解析错误, 行号和列号都从 1 开始, 列号按字符计
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+type Result<T, E = ParseError> = std::result::Result<T, E>;
+
+/// 一行中的一个词或字符串, 字符串已去掉引号并处理转义
+#[derive(Debug)]
+struct Token {
+    text: String,
+    column: usize,
+    quoted: bool,
+    /// 字符串中每个字符在源码中的列号, 用于 `type` 报错
+    char_columns: Vec<usize>,
+}
+
+/// 解析中的一行
+struct Line {
+    number: usize,
+    tokens: Vec<Token>,
+    /// 行尾的列号, 缺少参数时指向这里
+    end_column: usize,
+}
+
+impl Line {
+    fn error(&self, column: usize, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.number,
+            column,
+            message: message.into(),
+        }
+    }
+
+    /// 第 `i` 个参数, 缺少时报错
+    fn arg(&self, i: usize, what: &str) -> Result<&Token> {
+        self.tokens
+            .get(i)
+            .ok_or_else(|| self.error(self.end_column, format!("expected {what}")))
+    }
+
+    /// 参数个数必须为 `count`
+    fn expect_len(&self, count: usize) -> Result<()> {
+        match self.tokens.get(count) {
+            Some(extra) => Err(self.error(extra.column, format!("unexpected `{}`", extra.text))),
+            None => Ok(()),
+        }
+    }
+
+    fn word(&self, i: usize, what: &str) -> Result<&Token> {
+        let token = self.arg(i, what)?;
+        if token.quoted {
+            return Err(self.error(token.column, format!("expected {what}, found a string")));
+        }
+        Ok(token)
+    }
+
+    fn string(&self, i: usize, what: &str) -> Result<&Token> {
+        let token = self.arg(i, what)?;
+        if !token.quoted {
+            return Err(self.error(token.column, format!("expected {what} in double quotes")));
+        }
+        Ok(token)
+    }
+
+    fn number<T: std::str::FromStr>(&self, i: usize, what: &str) -> Result<T> {
+        let token = self.word(i, what)?;
+        token
+            .text
+            .parse()
+            .map_err(|_| self.error(token.column, format!("invalid {what}: {}", token.text)))
+    }
+}
+
+/// 把一行拆成词, 支持双引号字符串和 `#` 注释
+fn tokenize(number: usize, source: &str) -> Result<Line> {
+    let chars: Vec<char> = source.chars().collect();
+    let error = |column: usize, message: &str| ParseError {
+        line: number,
+        column,
+        message: message.to_string(),
+    };
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '#' {
+            break;
+        }
+
+        let column = i + 1;
+        if c == '"' {
+            let mut text = String::new();
+            let mut char_columns = Vec::new();
+            i += 1;
+            loop {
+                let Some(&c) = chars.get(i) else {
+                    return Err(error(column, "unterminated string"));
+                };
+                let char_column = i + 1;
+                i += 1;
+                match c {
+                    '"' => break,
+                    '\\' => {
+                        let escaped = match chars.get(i) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some('"') => '"',
+                            Some('\\') => '\\',
+                            Some(_) => return Err(error(i + 1, "unknown escape sequence")),
+                            None => return Err(error(column, "unterminated string")),
+                        };
+                        i += 1;
+                        text.push(escaped);
+                        char_columns.push(char_column);
+                    },
+                    c => {
+                        text.push(c);
+                        char_columns.push(char_column);
+                    },
+                }
+            }
+            tokens.push(Token {
+                text,
+                column,
+                quoted: true,
+                char_columns,
+            });
+        } else {
+            let start = i;
+            while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '"' && chars[i] != '#'
+            {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token {
+                text,
+                column,
+                quoted: false,
+                char_columns: Vec::new(),
+            });
+        }
+    }
+
+    let end_column = source.trim_end().chars().count() + 1;
+    Ok(Line {
+        number,
+        tokens,
+        end_column,
+    })
+}
+
+/// 解析文本宏的事件部分, 忽略元数据
+pub fn parse_events(text: &str) -> Result<Vec<MacroEvent>> {
+    parse_macro(text, "").map(|saved_macro| saved_macro.events)
+}
+
+/// 解析完整的文本宏, 文件中没有 `name` 时使用 `default_name`
+///
+/// 每行一条语句, `#` 之后是注释:
+///
+/// - 元数据: `name "..."`、`description "..."`、`author "..."`、`tag "..."`、
+///   `hotkey "Ctrl+F9"`、`created <秒>`、`updated <秒>`、`screen <宽> <高> <缩放>`
+/// - `wait 250ms` / `wait 1.5s`: 等待一段时间再执行下一条
+/// - `delay 250ms`: 延时事件
+/// - `move 100 200`: 移动鼠标
+/// - `click left`、`mouse down left`、`mouse up left`: 鼠标按键, 支持 left/middle/right
+/// - `key Enter`、`key down LControl`、`key up LControl`: 键盘按键
+/// - `type "hello"`: 依次输入字符串中的每个字符
+pub fn parse_macro(text: &str, default_name: &str) -> Result<SavedMacro> {
+    let mut saved_macro = SavedMacro::new(default_name, Vec::new());
+    saved_macro.created_at = 0;
+    saved_macro.updated_at = 0;
+    saved_macro.author = String::new();
+
+    let mut events = Vec::new();
+    let mut clock = 0u128;
+    for (i, source) in text.lines().enumerate() {
+        let line = tokenize(i + 1, source)?;
+        let Some(command) = line.tokens.first() else {
+            continue;
+        };
+        if command.quoted {
+            return Err(line.error(command.column, "expected a command"));
+        }
+
+        let mut push = |event_type| {
+            events.push(MacroEvent {
+                event_type,
+                timestamp: clock,
+            })
+        };
+        match command.text.as_str() {
+            "name" | "description" | "author" | "tag" => {
+                let value = line.string(1, &command.text)?.text.clone();
+                line.expect_len(2)?;
+                match command.text.as_str() {
+                    "name" => saved_macro.name = value,
+                    "description" => saved_macro.description = value,
+                    "author" => saved_macro.author = value,
+                    _ => saved_macro.tags.push(value),
+                }
+            },
+            "hotkey" => {
+                let value = line.string(1, "hotkey")?;
+                let hotkey = Hotkey::parse(&value.text).map_err(|e| line.error(value.column, e))?;
+                if hotkey.is_reserved() {
+                    return Err(line.error(value.column, format!("hotkey is reserved: {hotkey}")));
+                }
+                line.expect_len(2)?;
+                saved_macro.hotkey = Some(hotkey);
+            },
+            "created" | "updated" => {
+                let timestamp = line.number(1, "timestamp")?;
+                line.expect_len(2)?;
+                match command.text.as_str() {
+                    "created" => saved_macro.created_at = timestamp,
+                    _ => saved_macro.updated_at = timestamp,
+                }
+            },
+            "screen" => {
+                saved_macro.screen = Some(ScreenInfo {
+                    width: line.number(1, "screen width")?,
+                    height: line.number(2, "screen height")?,
+                    scale: line.number(3, "screen scale")?,
+                });
+                line.expect_len(4)?;
+            },
+            "wait" => {
+                clock += parse_duration(&line, 1)? as u128;
+                line.expect_len(2)?;
+            },
+            "delay" => {
+                push(MacroEventType::Delay {
+                    duration_ms: parse_duration(&line, 1)?,
+                });
+                line.expect_len(2)?;
+            },
+            "move" => {
+                push(MacroEventType::MouseMove {
+                    x: line.number(1, "x coordinate")?,
+                    y: line.number(2, "y coordinate")?,
+                });
+                line.expect_len(3)?;
+            },
+            "click" => {
+                let button = parse_button(&line, 1)?;
+                line.expect_len(2)?;
+                push(MacroEventType::MouseClick {
+                    button: button.clone(),
+                    pressed: true,
+                });
+                push(MacroEventType::MouseClick {
+                    button,
+                    pressed: false,
+                });
+            },
+            "mouse" => {
+                let pressed = parse_direction(&line, 1)?;
+                let button = parse_button(&line, 2)?;
+                line.expect_len(3)?;
+                push(MacroEventType::MouseClick { button, pressed });
+            },
+            "key" => {
+                // 键名可以加引号, 所以名为 down 的键写作 `key "down"`
+                let first = line.arg(1, "key name")?;
+                if !first.quoted && matches!(first.text.as_str(), "down" | "up") {
+                    let pressed = parse_direction(&line, 1)?;
+                    let key = line.arg(2, "key name")?.text.clone();
+                    line.expect_len(3)?;
+                    match pressed {
+                        true => push(MacroEventType::KeyPress { key }),
+                        false => push(MacroEventType::KeyRelease { key }),
+                    }
+                } else {
+                    line.expect_len(2)?;
+                    push(MacroEventType::KeyPress {
+                        key: first.text.clone(),
+                    });
+                    push(MacroEventType::KeyRelease {
+                        key: first.text.clone(),
+                    });
+                }
+            },
+            "type" => {
+                let token = line.string(1, "text")?;
+                line.expect_len(2)?;
+                for (c, column) in token.text.chars().zip(token.char_columns.iter()) {
+                    let Some((key, shift)) = char_key(c) else {
+                        return Err(line.error(*column, format!("cannot type {c:?}")));
+                    };
+                    if shift {
+                        push(MacroEventType::KeyPress {
+                            key: "LShift".to_string(),
+                        });
+                    }
+                    push(MacroEventType::KeyPress { key: key.clone() });
+                    push(MacroEventType::KeyRelease { key });
+                    if shift {
+                        push(MacroEventType::KeyRelease {
+                            key: "LShift".to_string(),
+                        });
+                    }
+                }
+            },
+            other => return Err(line.error(command.column, format!("unknown command `{other}`"))),
+        }
+    }
+
+    saved_macro.stats = MacroStats::from_events(&events);
+    saved_macro.events = events;
+    saved_macro.format_version = migration::CURRENT_FORMAT_VERSION;
+    Ok(saved_macro)
+}
+
+/// `250ms`、`1.5s` 形式的时长, 返回毫秒
+fn parse_duration(line: &Line, i: usize) -> Result<u64> {
+    let token = line.word(i, "duration")?;
+    let invalid = || line.error(token.column, format!("invalid duration: {}", token.text));
+    let (value, scale) = if let Some(ms) = token.text.strip_suffix("ms") {
+        (ms, 1.0)
+    } else if let Some(s) = token.text.strip_suffix('s') {
+        (s, 1000.0)
+    } else {
+        return Err(
+            line.error(token.column, format!("duration needs a unit (ms or s): {}", token.text))
+        );
+    };
+    let value: f64 = value.parse().map_err(|_| invalid())?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(invalid());
+    }
+    Ok((value * scale).round() as u64)
+}
+
+fn parse_button(line: &Line, i: usize) -> Result<Button> {
+    let token = line.word(i, "mouse button")?;
+    match token.text.as_str() {
+        "left" => Ok(Button::Left),
+        "middle" => Ok(Button::Middle),
+        "right" => Ok(Button::Right),
+        other => Err(line.error(token.column, format!("unknown mouse button `{other}`"))),
+    }
+}
+
+/// `down` 为按下, `up` 为抬起
+fn parse_direction(line: &Line, i: usize) -> Result<bool> {
+    let token = line.word(i, "`down` or `up`")?;
+    match token.text.as_str() {
+        "down" => Ok(true),
+        "up" => Ok(false),
+        other => Err(line.error(token.column, format!("expected `down` or `up`, found `{other}`"))),
+    }
+}
+
+/// 输入字符对应的键名, 以及是否需要按住 Shift
+fn char_key(c: char) -> Option<(String, bool)> {
+    let key = match c {
+        'a'..='z' => return Some((c.to_ascii_uppercase().to_string(), false)),
+        'A'..='Z' => return Some((c.to_string(), true)),
+        '0'..='9' => return Some((format!("Key{c}"), false)),
+        ' ' => ("Space", false),
+        '\n' => ("Enter", false),
+        '\t' => ("Tab", false),
+        '`' => ("Grave", false),
+        '~' => ("Grave", true),
+        '-' => ("Minus", false),
+        '_' => ("Minus", true),
+        '=' => ("Equal", false),
+        '+' => ("Equal", true),
+        '[' => ("LeftBracket", false),
+        '{' => ("LeftBracket", true),
+        ']' => ("RightBracket", false),
+        '}' => ("RightBracket", true),
+        '\\' => ("BackSlash", false),
+        '|' => ("BackSlash", true),
+        ';' => ("Semicolon", false),
+        ':' => ("Semicolon", true),
+        '\'' => ("Apostrophe", false),
+        '"' => ("Apostrophe", true),
+        ',' => ("Comma", false),
+        '<' => ("Comma", true),
+        '.' => ("Dot", false),
+        '>' => ("Dot", true),
+        '/' => ("Slash", false),
+        '?' => ("Slash", true),
+        '!' => ("Key1", true),
+        '@' => ("Key2", true),
+        '#' => ("Key3", true),
+        '$' => ("Key4", true),
+        '%' => ("Key5", true),
+        '^' => ("Key6", true),
+        '&' => ("Key7", true),
+        '*' => ("Key8", true),
+        '(' => ("Key9", true),
+        ')' => ("Key0", true),
+        _ => return None,
+    };
+    Some((key.0.to_string(), key.1))
+}
+
+/// 把宏输出为文本格式, 重新解析后得到相同的事件和元数据
+pub fn print_macro(saved_macro: &SavedMacro) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "name {}", quote(&saved_macro.name));
+    if !saved_macro.description.is_empty() {
+        let _ = writeln!(text, "description {}", quote(&saved_macro.description));
+    }
+    if !saved_macro.author.is_empty() {
+        let _ = writeln!(text, "author {}", quote(&saved_macro.author));
+    }
+    for tag in saved_macro.tags.iter() {
+        let _ = writeln!(text, "tag {}", quote(tag));
+    }
+    if let Some(hotkey) = &saved_macro.hotkey {
+        let _ = writeln!(text, "hotkey {}", quote(&hotkey.to_string()));
+    }
+    let _ = writeln!(text, "created {}", saved_macro.created_at);
+    let _ = writeln!(text, "updated {}", saved_macro.updated_at);
+    if let Some(screen) = saved_macro.screen {
+        let _ = writeln!(text, "screen {} {} {:?}", screen.width, screen.height, screen.scale);
+    }
+    text.push('\n');
+    text.push_str(&print_events(&saved_macro.events));
+    text
+}
+
+/// 把事件输出为文本格式
+///
+/// 同一时刻的按下和抬起合并为 `click` 或 `key`, 时间戳差值输出为 `wait`.
+pub fn print_events(events: &[MacroEvent]) -> String {
+    let mut text = String::new();
+    let mut events = with_gaps(events).peekable();
+    while let Some((gap, event)) = events.next() {
+        if gap > 0 {
+            let _ = writeln!(text, "wait {}", format_duration(gap));
+        }
+
+        // 同一时刻紧接着抬起同一个键或按钮时合并为一行
+        let merged = match (&event.event_type, events.peek()) {
+            (
+                MacroEventType::MouseClick {
+                    button,
+                    pressed: true,
+                },
+                Some(&(0, next)),
+            ) => matches!(
+                &next.event_type,
+                MacroEventType::MouseClick { button: b, pressed: false } if button_name(b) == button_name(button)
+            ),
+            (MacroEventType::KeyPress { key }, Some(&(0, next))) => {
+                matches!(&next.event_type, MacroEventType::KeyRelease { key: k } if k == key)
+            },
+            _ => false,
+        };
+        if merged {
+            events.next();
+        }
+
+        let _ = match &event.event_type {
+            MacroEventType::MouseMove { x, y } => writeln!(text, "move {x} {y}"),
+            MacroEventType::MouseClick { button, .. } if merged => {
+                writeln!(text, "click {}", button_name(button))
+            },
+            MacroEventType::MouseClick { button, pressed } => {
+                let direction = if *pressed { "down" } else { "up" };
+                writeln!(text, "mouse {direction} {}", button_name(button))
+            },
+            MacroEventType::KeyPress { key } if merged => {
+                // 名为 down 或 up 的键要加引号, 避免被当作方向
+                let key =
+                    if matches!(key.as_str(), "down" | "up") { quote(key) } else { key_token(key) };
+                writeln!(text, "key {key}")
+            },
+            MacroEventType::KeyPress { key } => writeln!(text, "key down {}", key_token(key)),
+            MacroEventType::KeyRelease { key } => writeln!(text, "key up {}", key_token(key)),
+            MacroEventType::Delay { duration_ms } => {
+                writeln!(text, "delay {}", format_duration(*duration_ms))
+            },
+        };
+    }
+    text
+}
+
+/// 整秒输出为 `2s`, 其余输出为毫秒
+fn format_duration(duration_ms: u64) -> String {
+    if duration_ms >= 1000 && duration_ms.is_multiple_of(1000) {
+        format!("{}s", duration_ms / 1000)
+    } else {
+        format!("{duration_ms}ms")
+    }
+}
+
+fn button_name(button: &Button) -> &'static str {
+    match button {
+        Button::Left => "left",
+        Button::Middle => "middle",
+        Button::Right => "right",
+    }
+}
+
+/// 键名通常原样输出, 包含空白、引号或 `#` 时加引号
+fn key_token(key: &str) -> String {
+    if key.is_empty() || key.chars().any(|c| c.is_whitespace() || c == '"' || c == '#') {
+        quote(key)
+    } else {
+        key.to_string()
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
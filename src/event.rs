@@ -31,7 +31,7 @@ pub enum MacroEventType {
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Button {
     Left,
     Middle,
@@ -48,3 +48,70 @@ impl From<usize> for Button {
         }
     }
 }
+
+/// 按播放顺序列出事件及其前面需要等待的时间(ms)
+///
+/// 和播放时一样取相邻事件的时间戳差值, 延时事件自身的等待不计入.
+pub fn with_gaps(events: &[MacroEvent]) -> impl Iterator<Item = (u64, &MacroEvent)> {
+    let mut last_timestamp = 0u128;
+    events.iter().map(move |event| {
+        let gap = event.timestamp.saturating_sub(last_timestamp) as u64;
+        last_timestamp = event.timestamp;
+        (gap, event)
+    })
+}
+
+/// 事件统计, 随宏文件一起保存, 列表显示和筛选时不必遍历事件
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroStats {
+    /// 总时长(ms), 包含延时事件
+    pub duration_ms: u64,
+    pub event_count: usize,
+    pub mouse_moves: usize,
+    pub mouse_clicks: usize,
+    pub key_events: usize,
+    pub delays: usize,
+}
+
+impl MacroStats {
+    pub fn from_events(events: &[MacroEvent]) -> Self {
+        let mut stats = Self {
+            event_count: events.len(),
+            ..Default::default()
+        };
+        let mut total_delay = 0u64;
+
+        for event in events {
+            match event.event_type {
+                MacroEventType::MouseMove { .. } => stats.mouse_moves += 1,
+                // 按下和抬起算一次点击
+                MacroEventType::MouseClick { pressed, .. } => {
+                    if pressed {
+                        stats.mouse_clicks += 1;
+                    }
+                },
+                MacroEventType::KeyPress { .. } | MacroEventType::KeyRelease { .. } => {
+                    stats.key_events += 1;
+                },
+                MacroEventType::Delay { duration_ms } => {
+                    stats.delays += 1;
+                    total_delay = total_delay.saturating_add(duration_ms);
+                },
+            }
+        }
+
+        let last_timestamp = events.last().map(|e| e.timestamp).unwrap_or(0);
+        stats.duration_ms =
+            u64::try_from(last_timestamp).unwrap_or(u64::MAX).saturating_add(total_delay);
+        stats
+    }
+
+    pub fn has_keyboard(&self) -> bool {
+        self.key_events > 0
+    }
+
+    pub fn has_clicks(&self) -> bool {
+        self.mouse_clicks > 0
+    }
+}
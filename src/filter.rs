@@ -0,0 +1,159 @@
+use std::{cmp::Reverse, sync::Arc};
+
+use crate::macro_manager::SavedMacro;
+
+/// 宏列表的排序方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// 有搜索词时按匹配程度, 否则按名称
+    #[default]
+    Relevance,
+    Name,
+    NewestCreated,
+    RecentlyUpdated,
+    Longest,
+    Shortest,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 6] = [
+        SortOrder::Relevance,
+        SortOrder::Name,
+        SortOrder::NewestCreated,
+        SortOrder::RecentlyUpdated,
+        SortOrder::Longest,
+        SortOrder::Shortest,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortOrder::Relevance => "匹配度",
+            SortOrder::Name => "名称",
+            SortOrder::NewestCreated => "最新创建",
+            SortOrder::RecentlyUpdated => "最近修改",
+            SortOrder::Longest => "时长最长",
+            SortOrder::Shortest => "时长最短",
+        }
+    }
+}
+
+/// 宏列表的搜索和筛选条件
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MacroFilter {
+    /// 模糊匹配名称、标签和描述
+    pub query: String,
+    pub min_duration_ms: Option<u64>,
+    pub max_duration_ms: Option<u64>,
+    pub has_keyboard: bool,
+    pub has_clicks: bool,
+    /// 只保留在此时间(Unix 秒)之后修改过的宏
+    pub updated_after: Option<u64>,
+    pub sort: SortOrder,
+}
+
+impl MacroFilter {
+    /// 是否设置了任何筛选条件(排序方式不算)
+    pub fn is_active(&self) -> bool {
+        !self.query.trim().is_empty()
+            || self.min_duration_ms.is_some()
+            || self.max_duration_ms.is_some()
+            || self.has_keyboard
+            || self.has_clicks
+            || self.updated_after.is_some()
+    }
+
+    /// 匹配时返回得分, 越大越相关; 不匹配返回 None
+    pub fn score(&self, saved_macro: &SavedMacro) -> Option<u32> {
+        let stats = &saved_macro.stats;
+        if self.min_duration_ms.is_some_and(|min| stats.duration_ms < min)
+            || self.max_duration_ms.is_some_and(|max| stats.duration_ms > max)
+            || (self.has_keyboard && !stats.has_keyboard())
+            || (self.has_clicks && !stats.has_clicks())
+            || self.updated_after.is_some_and(|t| saved_macro.updated_at < t)
+        {
+            return None;
+        }
+
+        let query = self.query.trim();
+        if query.is_empty() {
+            return Some(0);
+        }
+
+        // 名称权重最高, 其次是标签, 最后是描述
+        let name = fuzzy_score(query, &saved_macro.name).map(|s| s * 4);
+        let tags = saved_macro
+            .tags
+            .iter()
+            .filter_map(|t| fuzzy_score(query, t))
+            .max()
+            .map(|s| s * 2);
+        let description = fuzzy_score(query, &saved_macro.description);
+        [name, tags, description].into_iter().flatten().max()
+    }
+
+    /// 筛选并排序
+    pub fn apply(&self, macros: impl IntoIterator<Item = Arc<SavedMacro>>) -> Vec<Arc<SavedMacro>> {
+        let mut matches: Vec<(u32, Arc<SavedMacro>)> =
+            macros.into_iter().filter_map(|m| Some((self.score(&m)?, m))).collect();
+        self.sort(&mut matches);
+        matches.into_iter().map(|(_, m)| m).collect()
+    }
+
+    fn sort(&self, matches: &mut [(u32, Arc<SavedMacro>)]) {
+        // 先按名称排好, 其余排序都是稳定排序, 相同时保持名称顺序
+        matches.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name));
+        match self.sort {
+            SortOrder::Relevance => matches.sort_by_key(|(score, _)| Reverse(*score)),
+            SortOrder::Name => {},
+            SortOrder::NewestCreated => matches.sort_by_key(|(_, m)| Reverse(m.created_at)),
+            SortOrder::RecentlyUpdated => matches.sort_by_key(|(_, m)| Reverse(m.updated_at)),
+            SortOrder::Longest => matches.sort_by_key(|(_, m)| Reverse(m.stats.duration_ms)),
+            SortOrder::Shortest => matches.sort_by_key(|(_, m)| m.stats.duration_ms),
+        }
+    }
+}
+
+/// 模糊匹配: `pattern` 的每个字符按顺序出现在 `text` 中即为匹配, 忽略大小写
+///
+/// 连续匹配和从单词开头匹配得分更高, 完整包含子串时额外加分.
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<u32> {
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+
+    let mut score = 0;
+    let mut pattern_idx = 0;
+    let mut prev_match: Option<usize> = None;
+    for (i, c) in text.iter().enumerate() {
+        if pattern_idx == pattern.len() {
+            break;
+        }
+        if *c != pattern[pattern_idx] {
+            continue;
+        }
+
+        score += 1;
+        if prev_match.is_some_and(|p| p + 1 == i) {
+            score += 4;
+        }
+        if i == 0 || !text[i - 1].is_alphanumeric() {
+            score += 2;
+        }
+        prev_match = Some(i);
+        pattern_idx += 1;
+    }
+
+    if pattern_idx < pattern.len() {
+        return None;
+    }
+    if text.windows(pattern.len()).any(|w| w == pattern.as_slice()) {
+        score += 8;
+    }
+    Some(score)
+}
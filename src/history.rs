@@ -0,0 +1,200 @@
+use std::{
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    event::MacroStats,
+    macro_manager::{MacroError, SavedMacro},
+    storage,
+};
+
+/// 历史版本目录, 位于宏目录下, 以 `.` 开头所以不会被当作分组加载
+pub const HISTORY_DIR: &str = ".history";
+
+/// 每个宏默认保留的历史版本数
+pub const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+type Result<T, E = MacroError> = std::result::Result<T, E>;
+
+/// 一个历史版本的概要
+#[derive(Debug, Clone)]
+pub struct RevisionInfo {
+    /// 版本 ID, 即文件名主干, 按时间递增
+    pub id: String,
+    /// 该版本的修改时间
+    pub updated_at: u64,
+    pub stats: MacroStats,
+}
+
+/// 历史版本与当前版本的差异
+#[derive(Debug, Clone, PartialEq)]
+pub struct RevisionDiff {
+    pub revision: MacroStats,
+    pub current: MacroStats,
+}
+
+impl RevisionDiff {
+    /// 当前版本比历史版本多出的事件数
+    pub fn event_delta(&self) -> i64 {
+        self.current.event_count as i64 - self.revision.event_count as i64
+    }
+
+    /// 当前版本比历史版本多出的时长
+    pub fn duration_delta_ms(&self) -> i64 {
+        self.current.duration_ms as i64 - self.revision.duration_ms as i64
+    }
+}
+
+/// 宏的历史版本存储
+///
+/// 每次覆盖保存前, 旧内容写入 `.history/<slug>/<毫秒时间戳>.json`. 不同名称可能
+/// 生成相同的 slug, 所以读取时还要比对文件里的名称.
+#[derive(Debug, Clone)]
+pub struct History {
+    dir: PathBuf,
+    limit: Arc<AtomicUsize>,
+}
+
+impl History {
+    pub fn new(storage_path: &Path) -> Self {
+        Self {
+            dir: storage_path.join(HISTORY_DIR),
+            limit: Arc::new(AtomicUsize::new(DEFAULT_HISTORY_LIMIT)),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.load(Ordering::Relaxed)
+    }
+
+    /// 设置每个宏保留的版本数, 0 表示不保留历史
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::Relaxed);
+    }
+
+    fn macro_dir(&self, name: &str) -> PathBuf {
+        self.dir.join(storage::slugify(name))
+    }
+
+    /// 记录一个版本, 并删除超出数量限制的旧版本
+    pub fn record(&self, saved_macro: &SavedMacro) -> Result<()> {
+        if self.limit() == 0 {
+            return Ok(());
+        }
+
+        let dir = self.macro_dir(&saved_macro.name);
+        fs::create_dir_all(&dir)?;
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+        let path = unused_path(&dir, millis);
+        storage::write_atomic(&path, serde_json::to_string(saved_macro)?.as_bytes())?;
+
+        self.prune(&saved_macro.name)
+    }
+
+    /// 宏的所有历史版本, 从旧到新排列
+    pub fn list(&self, name: &str) -> Result<Vec<RevisionInfo>> {
+        Ok(self
+            .revisions(name)?
+            .into_iter()
+            .map(|(id, _, saved_macro)| RevisionInfo {
+                id,
+                updated_at: saved_macro.updated_at,
+                stats: saved_macro.stats,
+            })
+            .collect())
+    }
+
+    /// 读取某个历史版本
+    pub fn load(&self, name: &str, id: &str) -> Result<SavedMacro> {
+        self.revisions(name)?
+            .into_iter()
+            .find(|(rev_id, _, _)| rev_id == id)
+            .map(|(_, _, saved_macro)| saved_macro)
+            .ok_or_else(|| MacroError::NotFound(format!("{name}@{id}")))
+    }
+
+    /// 宏改名后把历史版本一起迁移到新名称下
+    pub fn rename(&self, old_name: &str, new_name: &str) -> Result<()> {
+        for (_, path, mut saved_macro) in self.revisions(old_name)? {
+            saved_macro.name = new_name.to_string();
+            let dir = self.macro_dir(new_name);
+            fs::create_dir_all(&dir)?;
+            let mut target = dir.join(path.file_name().unwrap_or_default());
+            // 新目录可能已有同 ID 的版本(比如同 slug 的其他宏), 另分配 ID, 不覆盖
+            if target != path && target.exists() {
+                let id = path.file_stem().unwrap_or_default().to_string_lossy();
+                target = unused_path(&dir, revision_order(&id).0);
+            }
+            storage::write_atomic(&target, serde_json::to_string(&saved_macro)?.as_bytes())?;
+            if target == path {
+                let _ = fs::remove_file(storage::backup_path(&target));
+            } else {
+                fs::remove_file(&path)?;
+            }
+        }
+        self.prune(new_name)
+    }
+
+    fn prune(&self, name: &str) -> Result<()> {
+        let revisions = self.revisions(name)?;
+        let excess = revisions.len().saturating_sub(self.limit());
+        for (_, path, _) in revisions.into_iter().take(excess) {
+            fs::remove_file(&path)?;
+            let _ = fs::remove_file(storage::backup_path(&path));
+        }
+        Ok(())
+    }
+
+    /// 读取名称匹配的所有版本, 按 ID 排序; 读不了的文件直接跳过
+    fn revisions(&self, name: &str) -> Result<Vec<(String, PathBuf, SavedMacro)>> {
+        let dir = self.macro_dir(name);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut revisions = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(id) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let Ok(file) = fs::File::open(&path) else {
+                continue;
+            };
+            if let Ok(saved_macro) = SavedMacro::from_reader(BufReader::new(file))
+                && saved_macro.name == name
+            {
+                revisions.push((id, path, saved_macro));
+            }
+        }
+        revisions.sort_by_key(|(id, _, _)| revision_order(id));
+        Ok(revisions)
+    }
+}
+
+/// `dir` 中以 `millis` 开头且未被占用的版本文件路径
+fn unused_path(dir: &Path, millis: u128) -> PathBuf {
+    let mut path = dir.join(format!("{millis:013}.json"));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{millis:013}-{n}.json"));
+        n += 1;
+    }
+    path
+}
+
+/// 版本 ID 的排序键: `<毫秒>` 或 `<毫秒>-<序号>`
+fn revision_order(id: &str) -> (u128, u32) {
+    let (millis, n) = id.split_once('-').unwrap_or((id, "1"));
+    (millis.parse().unwrap_or_default(), n.parse().unwrap_or_default())
+}
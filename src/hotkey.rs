@@ -1,88 +1,469 @@
 use std::{
+    collections::HashMap,
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
     },
     thread,
+    time::{Duration, Instant},
 };
 
 use device_query::{DeviceQuery, DeviceState, Keycode};
 use eframe::egui;
 use log::debug;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::state::AppState;
+use crate::{event::Button, state::AppState};
 
-// 快捷键结构体
-#[derive(Debug, Clone)]
-pub struct Shortcut {
-    pub name: String,
+/// 按键序列中相邻两次按键的最长间隔
+pub const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1000);
+/// 双击两次按键的最长间隔
+pub const DOUBLE_TAP_TIMEOUT: Duration = Duration::from_millis(400);
+/// 按键序列最多的步数
+pub const MAX_SEQUENCE_LEN: usize = 4;
+
+/// 修饰键状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Modifiers {
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+    meta: bool,
+}
+
+impl Modifiers {
+    /// 当前按下的修饰键, 不区分左右
+    fn held(keys: &[Keycode]) -> Self {
+        let held = |codes: &[Keycode]| codes.iter().any(|code| keys.contains(code));
+        Self {
+            ctrl: held(&[Keycode::LControl, Keycode::RControl]),
+            shift: held(&[Keycode::LShift, Keycode::RShift]),
+            alt: held(&[Keycode::LAlt, Keycode::RAlt, Keycode::LOption, Keycode::ROption]),
+            meta: held(&[Keycode::LMeta, Keycode::RMeta, Keycode::Command, Keycode::RCommand]),
+        }
+    }
+
+    /// 记录一个修饰键, 不是修饰键时返回 `Ok(false)`
+    fn insert(&mut self, name: &str) -> Result<bool, String> {
+        let flag = match name.to_lowercase().as_str() {
+            "ctrl" | "control" => &mut self.ctrl,
+            "shift" => &mut self.shift,
+            "alt" | "option" => &mut self.alt,
+            "meta" | "super" | "win" | "cmd" | "command" => &mut self.meta,
+            _ => return Ok(false),
+        };
+        if *flag {
+            return Err(format!("duplicate modifier: {name}"));
+        }
+        *flag = true;
+        Ok(true)
+    }
+
+    fn any(&self) -> bool {
+        self.ctrl || self.shift || self.alt || self.meta
+    }
+
+    /// 按 `Ctrl+Shift+Alt+Meta+` 的顺序输出
+    fn write(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = [
+            (self.ctrl, "Ctrl+"),
+            (self.shift, "Shift+"),
+            (self.alt, "Alt+"),
+            (self.meta, "Meta+"),
+        ];
+        names.iter().filter(|(on, _)| *on).try_for_each(|(_, name)| f.write_str(name))
+    }
+}
+
+/// 按键组合, 以 `Ctrl+Shift+F9` 形式的文本保存
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
     pub key: egui::Key,
     pub ctrl: bool,
     pub shift: bool,
     pub alt: bool,
-    pub description: String,
-    pub is_ui: bool,
+    /// Win / Super / Command 键
+    pub meta: bool,
 }
 
-impl Shortcut {
-    pub fn new(
-        name: &str, key: egui::Key, ctrl: bool, shift: bool, alt: bool, description: &str,
-        is_ui: bool,
-    ) -> Self {
+impl KeyCombo {
+    pub fn new(key: egui::Key) -> Self {
         Self {
-            name: name.to_string(),
             key,
-            ctrl,
-            shift,
-            alt,
-            description: description.to_string(),
-            is_ui,
+            ctrl: false,
+            shift: false,
+            alt: false,
+            meta: false,
         }
     }
 
-    /// 检查快捷键是否匹配UI快捷键
-    pub fn matches(&self, key: egui::Key, modifiers: &egui::Modifiers) -> bool {
-        if !self.is_ui {
-            return false;
+    /// `key` 和当前按下的修饰键组成的组合
+    pub fn pressed(key: egui::Key, keys: &[Keycode]) -> Self {
+        Self::new(key).with_modifiers(Modifiers::held(keys))
+    }
+
+    fn with_modifiers(mut self, modifiers: Modifiers) -> Self {
+        self.ctrl = modifiers.ctrl;
+        self.shift = modifiers.shift;
+        self.alt = modifiers.alt;
+        self.meta = modifiers.meta;
+        self
+    }
+
+    fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            ctrl: self.ctrl,
+            shift: self.shift,
+            alt: self.alt,
+            meta: self.meta,
         }
-        if key != self.key {
-            return false;
+    }
+
+    /// 解析 `Ctrl+Alt+F9`, 修饰键不区分大小写, 最后一项是按键
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let Some((key, names)) = parts.split_last() else {
+            return Err("empty hotkey".to_string());
+        };
+        let key = egui::Key::from_name(key)
+            .or_else(|| egui::Key::from_name(&key.to_uppercase()))
+            .ok_or_else(|| format!("unknown key: {key}"))?;
+        let mut modifiers = Modifiers::default();
+        for name in names {
+            if !modifiers.insert(name)? {
+                return Err(format!("unknown modifier: {name}"));
+            }
         }
+        Ok(Self::new(key).with_modifiers(modifiers))
+    }
 
-        if self.ctrl && !modifiers.ctrl {
-            return false;
+    /// 保留的组合不能作为快捷键: Esc 用来取消录入; 不带 Ctrl/Alt/Meta 时只能用功能键,
+    /// 否则正常打字也会触发
+    pub fn is_reserved(&self) -> bool {
+        if self.key == egui::Key::Escape {
+            return true;
         }
-        if self.shift && !modifiers.shift {
-            return false;
+        let is_function_key =
+            self.key.name().strip_prefix('F').is_some_and(|n| n.parse::<u8>().is_ok());
+        !(self.ctrl || self.alt || self.meta || is_function_key)
+    }
+
+    /// 检查按下的键是否匹配, `key` 是其中刚检测到的那个键.
+    /// 修饰键必须完全一致, 多按了修饰键也不匹配
+    pub fn matches_keycode(&self, key: &egui::Key, keys: &[Keycode]) -> bool {
+        *key == self.key && self.modifiers() == Modifiers::held(keys)
+    }
+}
+
+impl std::fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.modifiers().write(f)?;
+        f.write_str(self.key.name())
+    }
+}
+
+/// 同时按下的鼠标按键, 可以带修饰键, 如 `Ctrl+MouseMiddle+MouseRight`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MouseChord {
+    /// 按 左/中/右 排序, 不重复
+    pub buttons: Vec<Button>,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+impl MouseChord {
+    pub fn new(mut buttons: Vec<Button>) -> Self {
+        buttons.sort();
+        buttons.dedup();
+        Self {
+            buttons,
+            ctrl: false,
+            shift: false,
+            alt: false,
+            meta: false,
         }
-        if self.alt && !modifiers.alt {
-            return false;
+    }
+
+    fn with_modifiers(mut self, modifiers: Modifiers) -> Self {
+        self.ctrl = modifiers.ctrl;
+        self.shift = modifiers.shift;
+        self.alt = modifiers.alt;
+        self.meta = modifiers.meta;
+        self
+    }
+
+    fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            ctrl: self.ctrl,
+            shift: self.shift,
+            alt: self.alt,
+            meta: self.meta,
         }
+    }
 
-        true
+    /// 解析 `Ctrl+MouseMiddle+MouseRight`, 至少要有一个鼠标按键
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut modifiers = Modifiers::default();
+        let mut buttons = Vec::new();
+        for part in text.split('+').map(str::trim) {
+            if let Some(button) = button_from_name(part) {
+                if buttons.contains(&button) {
+                    return Err(format!("duplicate button: {part}"));
+                }
+                buttons.push(button);
+            } else if !modifiers.insert(part)? {
+                return Err(format!("unknown mouse button or modifier: {part}"));
+            }
+        }
+        if buttons.is_empty() {
+            return Err("no mouse button".to_string());
+        }
+        Ok(Self::new(buttons).with_modifiers(modifiers))
     }
 
-    /// 检查快捷键是否匹配全局快捷键
-    pub fn matches_keycode(&self, key: &egui::Key, keys: &[Keycode]) -> bool {
-        if self.is_ui {
-            return false;
+    /// 不带修饰键的单个按键会和正常点击冲突
+    pub fn is_reserved(&self) -> bool {
+        self.buttons.len() < 2 && !self.modifiers().any()
+    }
+
+    /// 按下的鼠标按键和修饰键是否完全一致
+    pub fn matches(&self, buttons: &[Button], keys: &[Keycode]) -> bool {
+        buttons.len() == self.buttons.len()
+            && self.buttons.iter().all(|button| buttons.contains(button))
+            && self.modifiers() == Modifiers::held(keys)
+    }
+}
+
+impl std::fmt::Display for MouseChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.modifiers().write(f)?;
+        let names: Vec<&str> = self.buttons.iter().map(button_name).collect();
+        f.write_str(&names.join("+"))
+    }
+}
+
+fn button_name(button: &Button) -> &'static str {
+    match button {
+        Button::Left => "MouseLeft",
+        Button::Middle => "MouseMiddle",
+        Button::Right => "MouseRight",
+    }
+}
+
+fn button_from_name(name: &str) -> Option<Button> {
+    [Button::Left, Button::Middle, Button::Right]
+        .into_iter()
+        .find(|button| button_name(button).eq_ignore_ascii_case(name))
+}
+
+/// `device_query` 的鼠标状态中按下的按键, 下标 1/2/3 对应 左/右/中
+pub fn pressed_buttons(button_pressed: &[bool]) -> Vec<Button> {
+    let pressed = |i: usize| button_pressed.get(i).copied().unwrap_or(false);
+    (1..=3).filter(|i| pressed(*i)).map(Button::from).collect()
+}
+
+/// 快捷键的触发方式, 以文本保存:
+/// `Ctrl+F9` 组合键, `Ctrl+K, Ctrl+C` 依次按下, `Double+F9` 快速按两次,
+/// `MouseMiddle+MouseRight` 同时按下鼠标按键
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Hotkey {
+    Key(KeyCombo),
+    /// 相邻两步的间隔不超过 [`SEQUENCE_TIMEOUT`]
+    Sequence(Vec<KeyCombo>),
+    /// 两次按下的间隔不超过 [`DOUBLE_TAP_TIMEOUT`]
+    DoubleTap(KeyCombo),
+    Mouse(MouseChord),
+}
+
+impl Hotkey {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if text.contains(',') {
+            let steps = text.split(',').map(KeyCombo::parse).collect::<Result<Vec<_>, _>>()?;
+            if steps.len() > MAX_SEQUENCE_LEN {
+                return Err(format!("sequence is longer than {MAX_SEQUENCE_LEN} steps"));
+            }
+            return Ok(Self::Sequence(steps));
         }
-        if *key != self.key {
-            return false;
+        if let Some((prefix, combo)) = text.split_once('+')
+            && prefix.trim().eq_ignore_ascii_case("double")
+        {
+            return KeyCombo::parse(combo).map(Self::DoubleTap);
         }
-        // 检查修饰键
-        if self.ctrl && !(keys.contains(&Keycode::LControl) || keys.contains(&Keycode::RControl)) {
-            return false;
+        if text.split('+').any(|part| button_from_name(part.trim()).is_some()) {
+            return MouseChord::parse(text).map(Self::Mouse);
         }
-        if self.shift && !(keys.contains(&Keycode::LShift) || keys.contains(&Keycode::RShift)) {
-            return false;
+        KeyCombo::parse(text).map(Self::Key)
+    }
+
+    /// 依次需要按下的组合, 鼠标组合为空
+    pub fn steps(&self) -> Vec<KeyCombo> {
+        match self {
+            Hotkey::Key(combo) => vec![*combo],
+            Hotkey::Sequence(steps) => steps.clone(),
+            Hotkey::DoubleTap(combo) => vec![*combo, *combo],
+            Hotkey::Mouse(_) => Vec::new(),
         }
-        if self.alt && !(keys.contains(&Keycode::LAlt) || keys.contains(&Keycode::RAlt)) {
-            return false;
+    }
+
+    /// 相邻两步的最长间隔
+    pub fn timeout(&self) -> Duration {
+        match self {
+            Hotkey::DoubleTap(_) => DOUBLE_TAP_TIMEOUT,
+            _ => SEQUENCE_TIMEOUT,
         }
+    }
 
-        true
+    /// 序列中的每一步都不能是保留的组合, 否则正常打字时会被拦截
+    pub fn is_reserved(&self) -> bool {
+        match self {
+            Hotkey::Mouse(chord) => chord.is_reserved(),
+            _ => self.steps().iter().any(KeyCombo::is_reserved),
+        }
+    }
+
+    /// 两个快捷键不能同时使用: 相同, 或者一个的按键序列是另一个的前缀
+    pub fn conflicts_with(&self, other: &Hotkey) -> bool {
+        let (a, b) = (self.steps(), other.steps());
+        if a.is_empty() || b.is_empty() {
+            return self == other;
+        }
+        let len = a.len().min(b.len());
+        a[..len] == b[..len]
+    }
+}
+
+impl From<KeyCombo> for Hotkey {
+    fn from(combo: KeyCombo) -> Self {
+        Hotkey::Key(combo)
+    }
+}
+
+impl std::fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Hotkey::Key(combo) => write!(f, "{combo}"),
+            Hotkey::Sequence(steps) => {
+                let steps: Vec<String> = steps.iter().map(KeyCombo::to_string).collect();
+                f.write_str(&steps.join(", "))
+            },
+            Hotkey::DoubleTap(combo) => write!(f, "Double+{combo}"),
+            Hotkey::Mouse(chord) => write!(f, "{chord}"),
+        }
+    }
+}
+
+macro_rules! serde_as_text {
+    ($ty:ty) => {
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let text = String::deserialize(deserializer)?;
+                Self::parse(&text).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+serde_as_text!(KeyCombo);
+serde_as_text!(Hotkey);
+
+/// 修改快捷键绑定失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindingError {
+    UnknownShortcut(String),
+    Reserved(Hotkey),
+    /// 窗口内快捷键只支持单个组合键
+    Unsupported(Hotkey),
+    /// 已被其他快捷键或宏使用, 内容为占用者
+    Conflict(String),
+}
+
+impl std::fmt::Display for BindingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BindingError::UnknownShortcut(name) => write!(f, "Unknown shortcut: {name}"),
+            BindingError::Reserved(hotkey) => write!(f, "{hotkey} is reserved"),
+            BindingError::Unsupported(hotkey) => {
+                write!(f, "{hotkey} can't be used inside the window")
+            },
+            BindingError::Conflict(owner) => write!(f, "Hotkey is already used by {owner}"),
+        }
+    }
+}
+
+impl std::error::Error for BindingError {}
+
+/// 运行时可以修改的快捷键列表; 修改时整体替换, 读取方拿到的是快照
+pub type SharedShortcuts = Arc<RwLock<Arc<Vec<Shortcut>>>>;
+
+/// 检查把 `name` 改为 `hotkey` 是否可行, 只和列表中的其他快捷键比较
+pub fn check_binding(
+    shortcuts: &[Shortcut], name: &str, hotkey: &Hotkey,
+) -> Result<(), BindingError> {
+    let Some(shortcut) = shortcuts.iter().find(|s| s.name == name) else {
+        return Err(BindingError::UnknownShortcut(name.to_string()));
+    };
+    if shortcut.is_ui && !matches!(hotkey, Hotkey::Key(_)) {
+        return Err(BindingError::Unsupported(hotkey.clone()));
+    }
+    if hotkey.is_reserved() {
+        return Err(BindingError::Reserved(hotkey.clone()));
+    }
+    match shortcuts.iter().find(|s| s.name != name && s.hotkey.conflicts_with(hotkey)) {
+        Some(other) => Err(BindingError::Conflict(format!("shortcut {}", other.name))),
+        None => Ok(()),
+    }
+}
+
+// 快捷键结构体
+#[derive(Debug, Clone)]
+pub struct Shortcut {
+    pub name: String,
+    pub hotkey: Hotkey,
+    pub description: String,
+    pub is_ui: bool,
+}
+
+impl Shortcut {
+    pub fn new(
+        name: &str, key: egui::Key, ctrl: bool, shift: bool, alt: bool, description: &str,
+        is_ui: bool,
+    ) -> Self {
+        let combo = KeyCombo {
+            key,
+            ctrl,
+            shift,
+            alt,
+            meta: false,
+        };
+        Self {
+            name: name.to_string(),
+            hotkey: Hotkey::Key(combo),
+            description: description.to_string(),
+            is_ui,
+        }
+    }
+
+    /// 检查快捷键是否匹配UI快捷键, 只支持单个组合键
+    pub fn matches(&self, key: egui::Key, modifiers: &egui::Modifiers) -> bool {
+        let Hotkey::Key(combo) = &self.hotkey else {
+            return false;
+        };
+        self.is_ui
+            && key == combo.key
+            && combo.ctrl == modifiers.ctrl
+            && combo.shift == modifiers.shift
+            && combo.alt == modifiers.alt
+            && combo.meta == modifiers.mac_cmd
     }
 
     /// 将device_query::Keycode转换为egui::Key
@@ -90,28 +471,115 @@ impl Shortcut {
         egui::Key::from_name(&keycode.to_string())
     }
 
-    /// 将egui::Key转换为device_query::Keycode
-    pub fn to_keycode(&self) -> Option<Keycode> {
-        use std::str::FromStr;
-        Keycode::from_str(self.key.name()).ok()
+    pub fn display_text(&self) -> String {
+        match &self.hotkey {
+            Hotkey::Key(combo) => combo.to_string().replace('+', " + "),
+            hotkey => hotkey.to_string(),
+        }
     }
+}
 
-    pub fn display_text(&self) -> String {
-        let mut parts = Vec::new();
+/// 快捷键触发的动作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotkeyAction {
+    /// 内置快捷键, 内容为快捷键名称
+    Shortcut(String),
+    /// 播放宏, 内容为宏名称
+    Macro(String),
+}
+
+/// 全局快捷键的按下沿检测: 只在组合的主键或鼠标按键刚按下时触发一次,
+/// 按住期间松开或按下其他键都不会重复触发. 按键序列和双击根据最近按下的组合判断
+#[derive(Debug, Default)]
+pub struct HotkeyDetector {
+    last_keys: Vec<Keycode>,
+    last_buttons: Vec<Button>,
+    /// 最近按下的组合和时间, 最多 [`MAX_SEQUENCE_LEN`] 个
+    history: Vec<(KeyCombo, Instant)>,
+    /// 同一快捷键两次触发的最短间隔, 为 0 时不限制
+    debounce: Duration,
+    last_fired: HashMap<Hotkey, Instant>,
+}
 
-        if self.ctrl {
-            parts.push("Ctrl".to_string());
+impl HotkeyDetector {
+    pub fn new(debounce: Duration) -> Self {
+        Self {
+            debounce,
+            ..Default::default()
         }
-        if self.shift {
-            parts.push("Shift".to_string());
+    }
+
+    /// 传入当前按下的键和鼠标按键, 返回刚被触发的动作.
+    /// 只有新按下了键或鼠标按键时才调用 `bindings` 获取当前的绑定
+    pub fn update<T: Clone>(
+        &mut self, keys: &[Keycode], buttons: &[Button], now: Instant,
+        bindings: impl FnOnce() -> Vec<(Hotkey, T)>,
+    ) -> Vec<T> {
+        let pressed: Vec<KeyCombo> = keys
+            .iter()
+            .filter(|key| !self.last_keys.contains(key))
+            .filter_map(Shortcut::to_key)
+            .map(|key| KeyCombo::pressed(key, keys))
+            .collect();
+        let clicked = buttons.iter().any(|button| !self.last_buttons.contains(button));
+        self.last_keys = keys.to_vec();
+        self.last_buttons = buttons.to_vec();
+        if pressed.is_empty() && !clicked {
+            return Vec::new();
         }
-        if self.alt {
-            parts.push("Alt".to_string());
+
+        let bindings = bindings();
+        let mut fired = Vec::new();
+        for combo in pressed {
+            if self.history.len() == MAX_SEQUENCE_LEN {
+                self.history.remove(0);
+            }
+            self.history.push((combo, now));
+            let mut completed = false;
+            for (hotkey, action) in &bindings {
+                if self.completes(hotkey) && self.fire(hotkey, now) {
+                    fired.push(action.clone());
+                    completed |= !matches!(hotkey, Hotkey::Key(_));
+                }
+            }
+            // 完成的序列不能再作为下一个序列的开头, 连按三次只算一次双击
+            if completed {
+                self.history.clear();
+            }
+        }
+        if clicked {
+            for (hotkey, action) in &bindings {
+                if let Hotkey::Mouse(chord) = hotkey
+                    && chord.matches(buttons, keys)
+                    && self.fire(hotkey, now)
+                {
+                    fired.push(action.clone());
+                }
+            }
         }
+        fired
+    }
 
-        parts.push(self.key.name().to_string());
+    /// 最近按下的组合是否正好完成了 `hotkey`
+    fn completes(&self, hotkey: &Hotkey) -> bool {
+        let steps = hotkey.steps();
+        let Some(start) = self.history.len().checked_sub(steps.len()) else {
+            return false;
+        };
+        let recent = &self.history[start..];
+        !steps.is_empty()
+            && recent.iter().zip(&steps).all(|((combo, _), step)| combo == step)
+            && recent.windows(2).all(|w| w[1].1.duration_since(w[0].1) <= hotkey.timeout())
+    }
 
-        parts.join(" + ")
+    /// 记录触发时间, 距上次触发不到防抖间隔时返回 false
+    fn fire(&mut self, hotkey: &Hotkey, now: Instant) -> bool {
+        let last = self.last_fired.get(hotkey);
+        if last.is_some_and(|last| now.duration_since(*last) < self.debounce) {
+            return false;
+        }
+        self.last_fired.insert(hotkey.clone(), now);
+        true
     }
 }
 
@@ -146,27 +614,29 @@ impl GlobalHotkeyListener {
 
     fn run_listener_loop(running: Arc<AtomicBool>, state: Arc<AppState>) {
         let device_state = DeviceState::new();
-        let mut last_keys = Vec::new();
+        let debounce = Duration::from_millis(state.settings.read().hotkey_debounce_ms);
+        let mut detector = HotkeyDetector::new(debounce);
         let mut last_mouse_pos = (0, 0);
         let mut sum_time = 0;
 
         while running.load(Ordering::SeqCst) {
-            thread::sleep(std::time::Duration::from_millis(30));
+            thread::sleep(Duration::from_millis(30));
 
             let keys = device_state.get_keys();
-            if keys != last_keys {
-                for key in keys.iter() {
-                    if let Some(key) = Shortcut::to_key(key) {
-                        for shortcut in state.shortcuts.iter() {
-                            if shortcut.matches_keycode(&key, &keys) {
-                                debug!("检测到全局快捷键: {}", shortcut.name);
-                                ShortcutProcessor::execute_shortcut(&shortcut.name, &state);
-                                break;
-                            }
-                        }
-                    }
+            let mouse_state = device_state.get_mouse();
+            let buttons = pressed_buttons(&mouse_state.button_pressed);
+            let now = Instant::now();
+            for action in detector.update(&keys, &buttons, now, || Self::bindings(&state)) {
+                match action {
+                    HotkeyAction::Shortcut(name) => {
+                        debug!("检测到全局快捷键: {name}");
+                        ShortcutProcessor::execute_shortcut(&name, &state);
+                    },
+                    HotkeyAction::Macro(name) => {
+                        debug!("检测到宏快捷键: {name}");
+                        ShortcutProcessor::play_macro(&name, &state);
+                    },
                 }
-                last_keys = keys;
             }
 
             sum_time = (sum_time + 1) % 6;
@@ -174,7 +644,6 @@ impl GlobalHotkeyListener {
                 continue;
             }
             // 更新鼠标位置
-            let mouse_state = device_state.get_mouse();
             if mouse_state.coords != last_mouse_pos {
                 state.set_mouse_position(mouse_state.coords);
                 last_mouse_pos = mouse_state.coords;
@@ -183,6 +652,22 @@ impl GlobalHotkeyListener {
         }
     }
 
+    /// 当前生效的全局快捷键和宏快捷键, 录入新快捷键时为空
+    fn bindings(state: &AppState) -> Vec<(Hotkey, HotkeyAction)> {
+        if state.hotkeys_suspended() {
+            return Vec::new();
+        }
+        let shortcuts = state.shortcuts();
+        let global = shortcuts
+            .iter()
+            .filter(|shortcut| !shortcut.is_ui)
+            .map(|s| (s.hotkey.clone(), HotkeyAction::Shortcut(s.name.clone())));
+        // 手工编辑的宏文件里可能有保留的组合, 不能让正常打字触发宏
+        let macros = state.macro_manager.macro_hotkeys().into_iter();
+        let macros = macros.filter(|(_, hotkey)| !hotkey.is_reserved());
+        global.chain(macros.map(|(name, hotkey)| (hotkey, HotkeyAction::Macro(name)))).collect()
+    }
+
     pub fn stop(&self) {
         self.running.store(false, Ordering::SeqCst);
 
@@ -231,4 +716,14 @@ impl ShortcutProcessor {
             },
         }
     }
+
+    /// 宏快捷键: 播放一次, 录制时忽略
+    pub fn play_macro(name: &str, state: &AppState) {
+        if state.recorder.is_recording() {
+            return;
+        }
+        if let Err(e) = state.play_macros(&[name.to_string()], 1, 0, 1.0) {
+            debug!("Failed to play macro {name}: {e}");
+        }
+    }
 }
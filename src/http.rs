@@ -0,0 +1,407 @@
+use std::{
+    io::{self, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use log::debug;
+use serde_json::{Value, json};
+use tungstenite::{Message, WebSocket, handshake::derive_accept_key, protocol::Role};
+
+use crate::{
+    ipc::{self, ControlStatus, RpcError},
+    macro_manager::MacroError,
+    settings::HttpSettings,
+    state::AppState,
+};
+
+/// 请求行和请求头的最大长度
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+/// 请求体的最大长度
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+/// 读取请求的超时时间
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// WebSocket 检查状态变化的间隔
+const STREAM_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 解析后的 HTTP 请求
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn from_parsed(parsed: &httparse::Request) -> Self {
+        let target = parsed.path.unwrap_or("/");
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Self {
+            method: parsed.method.unwrap_or_default().to_string(),
+            path: path.to_string(),
+            query: query.to_string(),
+            headers: parsed
+                .headers
+                .iter()
+                .map(|h| (h.name.to_string(), String::from_utf8_lossy(h.value).to_string()))
+                .collect(),
+            body: Vec::new(),
+        }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// `Authorization: Bearer <token>` 或 `?token=<token>`, 浏览器的 WebSocket 不能设置请求头
+    fn token(&self) -> Option<String> {
+        if let Some(token) = self.header("authorization").and_then(|v| v.strip_prefix("Bearer ")) {
+            return Some(token.trim().to_string());
+        }
+        self.query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+            .and_then(percent_decode)
+    }
+
+    fn is_websocket_upgrade(&self) -> bool {
+        self.header("upgrade").is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+    }
+
+    /// 请求体为空时返回 `null`
+    fn json_body(&self) -> Result<Value, String> {
+        if self.body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Value::Null);
+        }
+        serde_json::from_slice(&self.body).map_err(|e| e.to_string())
+    }
+}
+
+/// 本地 HTTP/WebSocket 控制接口, 只监听 127.0.0.1, 所有请求都要携带令牌
+///
+/// REST 接口:
+/// - `GET /api/macros`: 所有宏的概要
+/// - `GET /api/macros/<name>`: 完整的宏, 包含事件
+/// - `POST /api/macros/<name>/play` `{repeat?, interval_ms?, speed?}`: 播放一个宏
+/// - `POST /api/play` `{names, repeat?, interval_ms?, speed?}`: 按顺序播放
+/// - `POST /api/stop` / `api/pause` / `api/resume`: 控制播放
+/// - `GET /api/status`: 播放和录制状态
+/// - `POST /api/recording/start` / `api/recording/stop`: 控制录制
+/// - `GET /api/recording/events`: 录制缓冲区中的事件
+///
+/// `GET /api/ws` 升级为 WebSocket, 状态变化时推送 `{"type":"status","status":...}`,
+/// 录制到新事件时推送 `{"type":"events","events":[...]}`.
+#[derive(Debug)]
+pub struct HttpServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl HttpServer {
+    /// 端口为 0 时由系统分配, 实际地址见 [`HttpServer::local_addr`]
+    pub fn start(state: Arc<AppState>, config: &HttpSettings) -> io::Result<Self> {
+        if config.token.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "http token is empty"));
+        }
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, config.port))?;
+        let addr = listener.local_addr()?;
+        // 非阻塞 accept, 停止时不用等下一个连接
+        listener.set_nonblocking(true)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = stop.clone();
+        let token = Arc::new(config.token.clone());
+        let handle = thread::spawn(move || {
+            while !stop_clone.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let state = state.clone();
+                        let token = token.clone();
+                        let stop = stop_clone.clone();
+                        thread::spawn(move || {
+                            if let Err(e) = serve_connection(&state, stream, &token, &stop) {
+                                debug!("HTTP connection closed: {e}");
+                            }
+                        });
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(50));
+                    },
+                    Err(e) => {
+                        debug!("HTTP accept failed: {e}");
+                        thread::sleep(Duration::from_millis(50));
+                    },
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// 停止监听, 已连接的 WebSocket 也会关闭
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// 每个连接只处理一个请求
+fn serve_connection(
+    state: &AppState, mut stream: TcpStream, token: &str, stop: &AtomicBool,
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let (request, rest) = match read_request(&mut stream) {
+        Ok(request) => request,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            return write_response(&mut stream, 400, &error_body(e));
+        },
+        Err(e) => return Err(e),
+    };
+    if !token_matches(request.token().as_deref(), token) {
+        return write_response(&mut stream, 401, &error_body("invalid token"));
+    }
+    if request.method == "GET" && request.path == "/api/ws" {
+        if !request.is_websocket_upgrade() {
+            return write_response(&mut stream, 400, &error_body("expected websocket upgrade"));
+        }
+        return stream_status(state, stream, &request, rest, stop);
+    }
+    let (status, body) = route(state, &request);
+    write_response(&mut stream, status, &body)
+}
+
+/// 读取请求头和请求体, 同时返回多读到的数据
+fn read_request(stream: &mut TcpStream) -> io::Result<(Request, Vec<u8>)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 4096];
+    let (mut request, head_len) = loop {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut parsed = httparse::Request::new(&mut headers);
+        match parsed.parse(&buf).map_err(invalid_data)? {
+            httparse::Status::Complete(len) => break (Request::from_parsed(&parsed), len),
+            httparse::Status::Partial if buf.len() > MAX_HEAD_SIZE => {
+                return Err(invalid_data("request head too large"));
+            },
+            httparse::Status::Partial => {},
+        }
+    };
+
+    let length = match request.header("content-length") {
+        Some(value) => value.trim().parse().map_err(|_| invalid_data("invalid content-length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_SIZE {
+        return Err(invalid_data("request body too large"));
+    }
+    let mut rest = buf.split_off(head_len);
+    while rest.len() < length {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        rest.extend_from_slice(&chunk[..n]);
+    }
+    let leftover = rest.split_off(length);
+    request.body = rest;
+    Ok((request, leftover))
+}
+
+fn invalid_data(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn error_body(message: impl ToString) -> Value {
+    json!({ "error": message.to_string() })
+}
+
+/// 执行 REST 请求, 返回状态码和 JSON 响应
+fn route(state: &AppState, request: &Request) -> (u16, Value) {
+    let Some(segments) =
+        request.path.trim_matches('/').split('/').map(percent_decode).collect::<Option<Vec<_>>>()
+    else {
+        return (400, error_body("invalid path"));
+    };
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let body = match request.json_body() {
+        Ok(body) => body,
+        Err(e) => return (400, error_body(e)),
+    };
+
+    let (method, params) = match (request.method.as_str(), &segments[..]) {
+        ("GET", ["api", "macros"]) => ("list", Value::Null),
+        ("GET", ["api", "macros", name]) => {
+            return match state.macro_manager.get_macro(name) {
+                Ok(saved_macro) => (200, json!(*saved_macro)),
+                Err(e @ MacroError::NotFound(_)) => (404, error_body(e)),
+                Err(e) => (500, error_body(e)),
+            };
+        },
+        ("POST", ["api", "macros", name, "play"]) => {
+            if state.macro_manager.get_macro_header(name).is_none() {
+                return (404, error_body(MacroError::NotFound(name.to_string())));
+            }
+            let mut params = match body {
+                Value::Null => json!({}),
+                Value::Object(_) => body,
+                _ => return (400, error_body("expected a JSON object")),
+            };
+            params["names"] = json!([name]);
+            ("play", params)
+        },
+        ("POST", ["api", "play"]) => ("play", body),
+        ("GET", ["api", "status"]) => ("status", Value::Null),
+        ("POST", ["api", action @ ("stop" | "pause" | "resume")]) => (*action, Value::Null),
+        ("POST", ["api", "recording", "start"]) => ("start_recording", Value::Null),
+        ("POST", ["api", "recording", "stop"]) => ("stop_recording", Value::Null),
+        ("GET", ["api", "recording", "events"]) => ("recorded_events", Value::Null),
+        _ => return (404, error_body(format!("no route for {} {}", request.method, request.path))),
+    };
+    match ipc::call(state, method, params) {
+        Ok(result) => (200, result),
+        Err(error) => (status_code(&error), error_body(error.message)),
+    }
+}
+
+fn status_code(error: &RpcError) -> u16 {
+    match error.code {
+        ipc::METHOD_NOT_FOUND => 404,
+        // 宏不存在、播放时录制等与当前状态冲突的请求
+        ipc::APP_ERROR => 409,
+        _ => 400,
+    }
+}
+
+fn write_response(stream: &mut TcpStream, status: u16, body: &Value) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// 完成 WebSocket 握手, 然后推送状态变化和新录制的事件, 直到客户端断开或服务停止
+fn stream_status(
+    state: &AppState, mut stream: TcpStream, request: &Request, rest: Vec<u8>, stop: &AtomicBool,
+) -> io::Result<()> {
+    let Some(key) = request.header("sec-websocket-key") else {
+        return write_response(&mut stream, 400, &error_body("missing sec-websocket-key"));
+    };
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(key.trim().as_bytes())
+    )?;
+    stream.flush()?;
+    // 读取超时就是检查间隔
+    stream.set_read_timeout(Some(STREAM_INTERVAL))?;
+    let mut socket = WebSocket::from_partially_read(stream, rest, Role::Server, None);
+
+    let mut last_status: Option<ControlStatus> = None;
+    let mut sent_events = 0;
+    while !stop.load(Ordering::SeqCst) {
+        let status = ipc::control_status(state);
+        if last_status.as_ref() != Some(&status) {
+            send(&mut socket, json!({ "type": "status", "status": status }))?;
+            last_status = Some(status);
+        }
+
+        // 重新开始录制或清空后从头发送
+        if state.recorder.get_event_count() < sent_events {
+            sent_events = 0;
+        }
+        let events = state.recorder.get_events_since(sent_events);
+        if !events.is_empty() {
+            sent_events += events.len();
+            send(&mut socket, json!({ "type": "events", "events": events }))?;
+        }
+
+        // 客户端的消息只用来处理 ping 和关闭
+        match socket.read() {
+            Ok(_) => {},
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {},
+            Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                return Ok(());
+            },
+            Err(e) => return Err(io::Error::other(e)),
+        }
+    }
+    let _ = socket.close(None);
+    let _ = socket.flush();
+    Ok(())
+}
+
+fn send(socket: &mut WebSocket<TcpStream>, message: Value) -> io::Result<()> {
+    socket.send(Message::text(message.to_string())).map_err(io::Error::other)
+}
+
+/// 比较全部字节, 耗时不随第一个不同字节的位置变化
+fn token_matches(given: Option<&str>, token: &str) -> bool {
+    let Some(given) = given else {
+        return false;
+    };
+    given.len() == token.len()
+        && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// 解码 URL 中的 `%XX`, 结果不是合法 UTF-8 时返回 `None`
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3).filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit()))?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
@@ -0,0 +1,378 @@
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::{
+    event::{MacroEvent, MacroStats},
+    player::PlaybackStatus,
+    state::AppState,
+};
+
+#[cfg(unix)]
+pub use socket::{IpcClient, IpcServer};
+
+/// JSON-RPC 标准错误码
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// 请求合法但执行失败, 如宏不存在
+pub const APP_ERROR: i64 = -32000;
+
+/// JSON-RPC 错误
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (code {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// `list` 返回的宏概要
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MacroSummary {
+    pub name: String,
+    pub group: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub read_only: bool,
+    pub stats: MacroStats,
+}
+
+/// 播放和录制状态, 控制类方法都返回执行后的状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlStatus {
+    pub playback: PlaybackStatus,
+    pub is_recording: bool,
+    /// 录制缓冲区中的事件数
+    pub recorded_events: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: Option<String>,
+    /// 没有 id(或 id 为 null)的请求是通知, 不回复
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayParams {
+    names: Vec<String>,
+    repeat: Option<u32>,
+    interval_ms: Option<u64>,
+    speed: Option<f64>,
+}
+
+/// 处理一行请求, 返回要回复的一行 JSON; 通知不需要回复时返回 `None`
+///
+/// 支持的方法:
+/// - `list`: 所有宏的概要
+/// - `play` `{names, repeat?, interval_ms?, speed?}`: 按顺序播放
+/// - `stop` / `pause` / `resume`: 控制播放
+/// - `status`: 播放和录制状态
+/// - `start_recording` / `stop_recording`: 控制录制
+/// - `recorded_events`: 录制缓冲区中的事件
+pub fn handle_line(state: &AppState, line: &str) -> Option<String> {
+    let request: Request = match serde_json::from_str::<Value>(line) {
+        Err(e) => {
+            let error = RpcError::new(PARSE_ERROR, e.to_string());
+            return Some(error_response(Value::Null, error));
+        },
+        Ok(value) => match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(e) => {
+                let error = RpcError::new(INVALID_REQUEST, e.to_string());
+                return Some(error_response(Value::Null, error));
+            },
+        },
+    };
+    let id = request.id.clone().filter(|id| !id.is_null());
+    let result = if request.jsonrpc.as_deref() != Some("2.0") {
+        Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""))
+    } else {
+        call(state, &request.method, request.params)
+    };
+
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string(),
+        Err(error) => error_response(id, error),
+    })
+}
+
+fn error_response(id: Value, error: RpcError) -> String {
+    json!({ "jsonrpc": "2.0", "id": id, "error": error }).to_string()
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(APP_ERROR, e.to_string()))
+}
+
+/// 执行一个方法, 方法列表见 [`handle_line`]; HTTP 接口也通过这里执行
+pub fn call(state: &AppState, method: &str, params_value: Value) -> Result<Value, RpcError> {
+    match method {
+        "list" => {
+            let manager = &state.macro_manager;
+            let macros: Vec<MacroSummary> = manager
+                .get_all_macros()
+                .iter()
+                .map(|m| MacroSummary {
+                    name: m.name.clone(),
+                    group: manager.get_macro_group(&m.name),
+                    description: m.description.clone(),
+                    tags: m.tags.clone(),
+                    created_at: m.created_at,
+                    updated_at: m.updated_at,
+                    read_only: manager.is_read_only(&m.name),
+                    stats: m.stats.clone(),
+                })
+                .collect();
+            return to_value(macros);
+        },
+        "status" => {},
+        "play" => {
+            let p: PlayParams = params(params_value)?;
+            if p.names.is_empty() {
+                return Err(RpcError::new(INVALID_PARAMS, "names is empty"));
+            }
+            let speed = p.speed.unwrap_or(1.0);
+            if !speed.is_finite() || speed <= 0.0 {
+                return Err(RpcError::new(INVALID_PARAMS, "speed must be a positive number"));
+            }
+            if state.recorder.is_recording() {
+                return Err(RpcError::new(APP_ERROR, "cannot play while recording"));
+            }
+            state
+                .play_macros(&p.names, p.repeat.unwrap_or(1), p.interval_ms.unwrap_or(0), speed)
+                .map_err(|e| RpcError::new(APP_ERROR, e.to_string()))?;
+        },
+        "stop" => state.stop_player(),
+        "pause" => state.pause_player(),
+        "resume" => state.resume_player(),
+        "start_recording" => {
+            if state.is_playing() {
+                return Err(RpcError::new(APP_ERROR, "cannot record while playing"));
+            }
+            state
+                .recorder
+                .start_recording()
+                .map_err(|e| RpcError::new(APP_ERROR, e.to_string()))?;
+        },
+        "stop_recording" => state.recorder.stop_recording(),
+        "recorded_events" => {
+            let events: Vec<MacroEvent> = state.recorder.get_events();
+            return to_value(events);
+        },
+        other => return Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method: {other}"))),
+    }
+    state.ui_repaint_after_secs(0.0);
+    to_value(control_status(state))
+}
+
+/// 当前的播放和录制状态
+pub fn control_status(state: &AppState) -> ControlStatus {
+    ControlStatus {
+        playback: (*state.get_player_playback_status()).clone(),
+        is_recording: state.recorder.is_recording(),
+        recorded_events: state.recorder.get_event_count(),
+    }
+}
+
+#[cfg(unix)]
+mod socket {
+    use std::{
+        fs,
+        io::{self, BufRead, BufReader, Write},
+        os::unix::{
+            fs::{DirBuilderExt, PermissionsExt},
+            net::{UnixListener, UnixStream},
+        },
+        path::{Path, PathBuf},
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread,
+        time::Duration,
+    };
+
+    use log::debug;
+    use serde_json::{Value, json};
+
+    use super::{ControlStatus, RpcError, handle_line};
+    use crate::state::AppState;
+
+    /// 本地控制接口: 监听 Unix socket, 每行一个 JSON-RPC 2.0 请求
+    ///
+    /// socket 放在只有当前用户可以访问的目录中, 文件本身也只有当前用户可以读写.
+    /// 每个连接在单独的线程中处理, 直到客户端断开.
+    #[derive(Debug)]
+    pub struct IpcServer {
+        path: PathBuf,
+        stop: Arc<AtomicBool>,
+        handle: Option<thread::JoinHandle<()>>,
+    }
+
+    impl IpcServer {
+        pub fn start(state: Arc<AppState>, path: &Path) -> io::Result<Self> {
+            // 先确认目录是私有的再 bind, 否则 bind 和 chmod 之间其他用户可以连接
+            let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+            let dir = dir.unwrap_or(Path::new("."));
+            fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+            if fs::metadata(dir)?.permissions().mode() & 0o077 != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("socket directory {} is accessible by other users", dir.display()),
+                ));
+            }
+            // 上次异常退出留下的 socket 文件可以删除, 还有程序在监听时不能抢占
+            if path.exists() {
+                if UnixStream::connect(path).is_ok() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AddrInUse,
+                        format!("another instance is listening on {}", path.display()),
+                    ));
+                }
+                fs::remove_file(path)?;
+            }
+            let listener = UnixListener::bind(path)?;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+            // 非阻塞 accept, 停止时不用等下一个连接
+            listener.set_nonblocking(true)?;
+
+            let stop = Arc::new(AtomicBool::new(false));
+            let stop_clone = stop.clone();
+            let handle = thread::spawn(move || {
+                while !stop_clone.load(Ordering::SeqCst) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            let state = state.clone();
+                            thread::spawn(move || {
+                                if let Err(e) = serve_connection(&state, stream) {
+                                    debug!("IPC connection closed: {e}");
+                                }
+                            });
+                        },
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(Duration::from_millis(50));
+                        },
+                        Err(e) => {
+                            debug!("IPC accept failed: {e}");
+                            thread::sleep(Duration::from_millis(50));
+                        },
+                    }
+                }
+            });
+
+            Ok(Self {
+                path: path.to_path_buf(),
+                stop,
+                handle: Some(handle),
+            })
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+
+        pub fn stop(&mut self) {
+            self.stop.store(true, Ordering::SeqCst);
+            if let Some(handle) = self.handle.take() {
+                let _ = handle.join();
+                let _ = fs::remove_file(&self.path);
+            }
+        }
+    }
+
+    impl Drop for IpcServer {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    fn serve_connection(state: &AppState, stream: UnixStream) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = handle_line(state, &line) {
+                writeln!(writer, "{response}")?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 控制接口的客户端, 一次一个请求
+    #[derive(Debug)]
+    pub struct IpcClient {
+        reader: BufReader<UnixStream>,
+        writer: UnixStream,
+        next_id: u64,
+    }
+
+    impl IpcClient {
+        pub fn connect(path: &Path) -> anyhow::Result<Self> {
+            let stream = UnixStream::connect(path)
+                .map_err(|e| anyhow::anyhow!("cannot connect to {}: {e}", path.display()))?;
+            Ok(Self {
+                writer: stream.try_clone()?,
+                reader: BufReader::new(stream),
+                next_id: 1,
+            })
+        }
+
+        /// 调用方法, 服务端返回的错误转换为 [`RpcError`]
+        pub fn call(&mut self, method: &str, params: Value) -> anyhow::Result<Value> {
+            let id = self.next_id;
+            self.next_id += 1;
+            let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+            writeln!(self.writer, "{request}")?;
+            self.writer.flush()?;
+
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                anyhow::bail!("connection closed by server");
+            }
+            let mut response: Value = serde_json::from_str(&line)?;
+            if response["id"] != json!(id) {
+                anyhow::bail!("unexpected response id: {}", response["id"]);
+            }
+            if let Some(error) = response.get("error") {
+                return Err(serde_json::from_value::<RpcError>(error.clone())?.into());
+            }
+            Ok(response["result"].take())
+        }
+
+        pub fn status(&mut self) -> anyhow::Result<ControlStatus> {
+            Ok(serde_json::from_value(self.call("status", Value::Null)?)?)
+        }
+    }
+}
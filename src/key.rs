@@ -142,3 +142,178 @@ pub fn pilot_key_code_from_str(key: &str) -> KeyConvert {
         _ => KeyConvert::None,
     }
 }
+
+/// 键名在各种脚本工具中的名称: (键名, xdotool keysym, AutoHotkey, pyautogui)
+///
+/// 同一个 keysym 对应多个键名时, 排在前面的是导入 xdotool 脚本时使用的键名.
+const SCRIPT_KEYS: &[(&str, &str, &str, &str)] = &[
+    ("Key0", "0", "0", "0"),
+    ("Key1", "1", "1", "1"),
+    ("Key2", "2", "2", "2"),
+    ("Key3", "3", "3", "3"),
+    ("Key4", "4", "4", "4"),
+    ("Key5", "5", "5", "5"),
+    ("Key6", "6", "6", "6"),
+    ("Key7", "7", "7", "7"),
+    ("Key8", "8", "8", "8"),
+    ("Key9", "9", "9", "9"),
+    ("A", "a", "a", "a"),
+    ("B", "b", "b", "b"),
+    ("C", "c", "c", "c"),
+    ("D", "d", "d", "d"),
+    ("E", "e", "e", "e"),
+    ("F", "f", "f", "f"),
+    ("G", "g", "g", "g"),
+    ("H", "h", "h", "h"),
+    ("I", "i", "i", "i"),
+    ("J", "j", "j", "j"),
+    ("K", "k", "k", "k"),
+    ("L", "l", "l", "l"),
+    ("M", "m", "m", "m"),
+    ("N", "n", "n", "n"),
+    ("O", "o", "o", "o"),
+    ("P", "p", "p", "p"),
+    ("Q", "q", "q", "q"),
+    ("R", "r", "r", "r"),
+    ("S", "s", "s", "s"),
+    ("T", "t", "t", "t"),
+    ("U", "u", "u", "u"),
+    ("V", "v", "v", "v"),
+    ("W", "w", "w", "w"),
+    ("X", "x", "x", "x"),
+    ("Y", "y", "y", "y"),
+    ("Z", "z", "z", "z"),
+    ("F1", "F1", "F1", "f1"),
+    ("F2", "F2", "F2", "f2"),
+    ("F3", "F3", "F3", "f3"),
+    ("F4", "F4", "F4", "f4"),
+    ("F5", "F5", "F5", "f5"),
+    ("F6", "F6", "F6", "f6"),
+    ("F7", "F7", "F7", "f7"),
+    ("F8", "F8", "F8", "f8"),
+    ("F9", "F9", "F9", "f9"),
+    ("F10", "F10", "F10", "f10"),
+    ("F11", "F11", "F11", "f11"),
+    ("F12", "F12", "F12", "f12"),
+    ("F13", "F13", "F13", "f13"),
+    ("F14", "F14", "F14", "f14"),
+    ("F15", "F15", "F15", "f15"),
+    ("F16", "F16", "F16", "f16"),
+    ("F17", "F17", "F17", "f17"),
+    ("F18", "F18", "F18", "f18"),
+    ("F19", "F19", "F19", "f19"),
+    ("F20", "F20", "F20", "f20"),
+    ("Escape", "Escape", "Escape", "esc"),
+    ("Space", "space", "Space", "space"),
+    ("Enter", "Return", "Enter", "enter"),
+    ("Backspace", "BackSpace", "Backspace", "backspace"),
+    ("Tab", "Tab", "Tab", "tab"),
+    ("CapsLock", "Caps_Lock", "CapsLock", "capslock"),
+    ("Up", "Up", "Up", "up"),
+    ("Down", "Down", "Down", "down"),
+    ("Left", "Left", "Left", "left"),
+    ("Right", "Right", "Right", "right"),
+    ("Home", "Home", "Home", "home"),
+    ("End", "End", "End", "end"),
+    ("PageUp", "Page_Up", "PgUp", "pageup"),
+    ("PageDown", "Page_Down", "PgDn", "pagedown"),
+    ("Delete", "Delete", "Delete", "delete"),
+    ("Insert", "Insert", "Insert", "insert"),
+    ("LControl", "Control_L", "LControl", "ctrlleft"),
+    ("RControl", "Control_R", "RControl", "ctrlright"),
+    ("LShift", "Shift_L", "LShift", "shiftleft"),
+    ("RShift", "Shift_R", "RShift", "shiftright"),
+    ("LAlt", "Alt_L", "LAlt", "altleft"),
+    ("RAlt", "Alt_R", "RAlt", "altright"),
+    ("LMeta", "Super_L", "LWin", "winleft"),
+    ("RMeta", "Super_R", "RWin", "winright"),
+    ("Command", "Super_L", "LWin", "command"),
+    ("RCommand", "Super_R", "RWin", "command"),
+    ("Numpad0", "KP_0", "Numpad0", "num0"),
+    ("Numpad1", "KP_1", "Numpad1", "num1"),
+    ("Numpad2", "KP_2", "Numpad2", "num2"),
+    ("Numpad3", "KP_3", "Numpad3", "num3"),
+    ("Numpad4", "KP_4", "Numpad4", "num4"),
+    ("Numpad5", "KP_5", "Numpad5", "num5"),
+    ("Numpad6", "KP_6", "Numpad6", "num6"),
+    ("Numpad7", "KP_7", "Numpad7", "num7"),
+    ("Numpad8", "KP_8", "Numpad8", "num8"),
+    ("Numpad9", "KP_9", "Numpad9", "num9"),
+    ("NumpadDecimal", "KP_Decimal", "NumpadDot", "decimal"),
+    ("NumpadEnter", "KP_Enter", "NumpadEnter", "enter"),
+    ("NumpadAdd", "KP_Add", "NumpadAdd", "add"),
+    ("NumpadSubtract", "KP_Subtract", "NumpadSub", "subtract"),
+    ("NumpadMultiply", "KP_Multiply", "NumpadMult", "multiply"),
+    ("NumpadDivide", "KP_Divide", "NumpadDiv", "divide"),
+    ("Grave", "grave", "`", "`"),
+    ("Minus", "minus", "-", "-"),
+    ("Equal", "equal", "=", "="),
+    ("LeftBracket", "bracketleft", "[", "["),
+    ("RightBracket", "bracketright", "]", "]"),
+    ("BackSlash", "backslash", "\\", "\\"),
+    ("Semicolon", "semicolon", ";", ";"),
+    ("Apostrophe", "apostrophe", "'", "'"),
+    ("Comma", "comma", ",", ","),
+    ("Dot", "period", ".", "."),
+    ("Slash", "slash", "/", "/"),
+];
+
+/// xdotool 常用的简写和别名
+const XDOTOOL_ALIASES: &[(&str, &str)] = &[
+    ("ctrl", "LControl"),
+    ("control", "LControl"),
+    ("shift", "LShift"),
+    ("alt", "LAlt"),
+    ("super", "LMeta"),
+    ("meta", "LMeta"),
+    ("Meta_L", "LMeta"),
+    ("Meta_R", "RMeta"),
+    ("enter", "Enter"),
+    ("Prior", "PageUp"),
+    ("Next", "PageDown"),
+    ("period", "Dot"),
+];
+
+/// 写入脚本注释的文本: 控制字符(包括换行)转义为 `\n`、`\u{1b}` 的形式,
+/// 避免宏文件中的内容在注释之外生成可执行的行
+pub fn comment_text(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { c.escape_default().to_string() } else { c.to_string() })
+        .collect()
+}
+
+/// 键名转换为 xdotool 使用的 keysym 名称
+pub fn xdotool_key_name(key: &str) -> Option<&'static str> {
+    script_keys(key).map(|(_, keysym, _, _)| *keysym)
+}
+
+/// 键名转换为 AutoHotkey v2 `Send` 使用的名称
+pub fn autohotkey_key_name(key: &str) -> Option<&'static str> {
+    script_keys(key).map(|(_, _, name, _)| *name)
+}
+
+/// 键名转换为 pyautogui 使用的名称
+pub fn pyautogui_key_name(key: &str) -> Option<&'static str> {
+    script_keys(key).map(|(_, _, _, name)| *name)
+}
+
+fn script_keys(
+    key: &str,
+) -> Option<&'static (&'static str, &'static str, &'static str, &'static str)> {
+    SCRIPT_KEYS.iter().find(|(name, ..)| *name == key)
+}
+
+/// xdotool 的 keysym 名称转换为键名, 单个字母不区分大小写
+pub fn key_from_xdotool_name(keysym: &str) -> Option<&'static str> {
+    let is_letter = keysym.len() == 1 && keysym.chars().all(|c| c.is_ascii_alphabetic());
+    SCRIPT_KEYS
+        .iter()
+        .find(|(_, name, ..)| *name == keysym || (is_letter && name.eq_ignore_ascii_case(keysym)))
+        .map(|(key, ..)| *key)
+        .or_else(|| {
+            XDOTOOL_ALIASES
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(keysym))
+                .map(|(_, key)| *key)
+        })
+}
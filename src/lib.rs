@@ -1,11 +1,29 @@
 #![allow(clippy::new_without_default)]
+pub mod autohotkey;
+pub mod bundle;
+pub mod cache;
+pub mod cli;
+pub mod compact;
+pub mod dsl;
 pub mod event;
+pub mod filter;
 pub mod font;
+pub mod history;
+pub mod http;
 pub mod hotkey;
 pub mod icon_data;
+pub mod ipc;
 pub mod key;
 pub mod macro_manager;
+pub mod migration;
 pub mod player;
+pub mod pyautogui;
 pub mod recorder;
+pub mod script;
+pub mod settings;
 pub mod state;
+pub mod storage;
+pub mod trash;
 pub mod ui;
+pub mod watcher;
+pub mod xdotool;
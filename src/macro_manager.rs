@@ -1,119 +1,1465 @@
-use crate::event::MacroEvent;
+use crate::{
+    bundle::{Bundle, ImportReport},
+    cache::LruCache,
+    compact, dsl,
+    event::{MacroEvent, MacroStats},
+    history::{History, RevisionDiff, RevisionInfo},
+    hotkey::Hotkey,
+    migration,
+    settings::{LibraryConfig, Settings},
+    storage::{self, StorageFormat},
+    trash::{Trash, TrashEntry},
+};
 
-use anyhow::Result;
 use autopilot::alert;
 use log::debug;
-use parking_lot::RwLock;
-use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, fs, io::BufReader, path::Path, sync::Arc, thread};
+use parking_lot::{Mutex, RwLock};
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{SeqAccess, Visitor},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+type Result<T, E = MacroError> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum MacroError {
+    InvalidName(String),
+    AlreadyExists(String),
+    NotFound(String),
+    /// 宏库是只读的
+    ReadOnly(String),
+    /// 快捷键已被占用, 内容为占用者的说明
+    HotkeyConflict(String),
+    /// 保留的组合不能作为快捷键, 见 [`Hotkey::is_reserved`]
+    ReservedHotkey(Hotkey),
+    Format(String),
+    Json(serde_json::Error),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for MacroError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MacroError::InvalidName(reason) => write!(f, "Invalid macro name: {reason}"),
+            MacroError::AlreadyExists(name) => write!(f, "Macro already exists: {name}"),
+            MacroError::NotFound(name) => write!(f, "Macro not found: {name}"),
+            MacroError::ReadOnly(library) => write!(f, "Macro library is read-only: {library}"),
+            MacroError::HotkeyConflict(owner) => write!(f, "Hotkey is already used by {owner}"),
+            MacroError::ReservedHotkey(hotkey) => write!(f, "Hotkey is reserved: {hotkey}"),
+            MacroError::Format(err) => write!(f, "Unsupported macro file: {err}"),
+            MacroError::Json(err) => write!(f, "{err}"),
+            MacroError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for MacroError {}
+
+impl From<std::io::Error> for MacroError {
+    fn from(err: std::io::Error) -> Self {
+        MacroError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for MacroError {
+    fn from(err: serde_json::Error) -> Self {
+        MacroError::Json(err)
+    }
+}
+
+/// 名称冲突时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// 返回 [`MacroError::AlreadyExists`]
+    Error,
+    /// 覆盖已有的宏
+    Overwrite,
+    /// 自动添加后缀, 如 "name (2)"
+    AutoSuffix,
+}
+
+/// 录制时的屏幕信息
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScreenInfo {
+    pub width: u32,
+    pub height: u32,
+    pub scale: f64,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SavedMacro {
+    /// 文件格式版本, 旧文件没有该字段, 按 0 处理
+    #[serde(default)]
+    pub format_version: u32,
     pub name: String,
     pub events: Vec<MacroEvent>,
     pub created_at: u64,
-    // pub updated_at: u64,
+    #[serde(default)]
+    pub updated_at: u64,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub screen: Option<ScreenInfo>,
+    /// 缓存的事件统计, 保存时重新计算
+    #[serde(default)]
+    pub stats: MacroStats,
+    /// 播放该宏的全局快捷键
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotkey: Option<Hotkey>,
+}
+
+impl SavedMacro {
+    pub fn new(name: &str, events: Vec<MacroEvent>) -> Self {
+        let now = unix_now();
+        let stats = MacroStats::from_events(&events);
+        Self {
+            format_version: migration::CURRENT_FORMAT_VERSION,
+            name: name.to_string(),
+            events,
+            created_at: now,
+            updated_at: now,
+            description: String::new(),
+            tags: Vec::new(),
+            author: default_author(),
+            screen: None,
+            stats,
+            hotkey: None,
+        }
+    }
+
+    pub fn with_screen(mut self, screen: Option<ScreenInfo>) -> Self {
+        self.screen = screen;
+        self
+    }
+
+    /// 读取宏文件, 旧版本格式会先经过迁移链升级
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        Self::from_value(serde_json::from_reader(reader)?)
+    }
+
+    /// 从 JSON 值读取宏, 同样经过迁移链
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        let value = migration::migrate(value).map_err(|e| MacroError::Format(e.to_string()))?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// 只保留元数据的副本, 不含事件
+    pub fn header(&self) -> Self {
+        Self {
+            format_version: self.format_version,
+            name: self.name.clone(),
+            events: Vec::new(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            description: self.description.clone(),
+            tags: self.tags.clone(),
+            author: self.author.clone(),
+            screen: self.screen,
+            stats: self.stats.clone(),
+            hotkey: self.hotkey.clone(),
+        }
+    }
+
+    /// 修改后刷新版本号、更新时间和统计
+    fn touch(&mut self) {
+        self.format_version = migration::CURRENT_FORMAT_VERSION;
+        self.updated_at = unix_now();
+        self.stats = MacroStats::from_events(&self.events);
+    }
+}
+
+/// 当前 Unix 时间(秒)
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// 宏文件: JSON、文本或压缩格式
+fn is_macro_file(path: &Path) -> bool {
+    StorageFormat::from_path(path).is_some()
+}
+
+/// 规范化库内分组: 去掉空段, 每段转换为安全的目录名(不含 `:`)
+fn normalize_local_group(local: &str) -> String {
+    local
+        .split(['/', '\\'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(storage::slugify)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn file_extension(path: &Path) -> &str {
+    path.extension().and_then(|s| s.to_str()).unwrap_or_default()
+}
+
+/// 按扩展名读取宏文件, 文本格式缺少名称时使用文件名
+fn read_macro_file(path: &Path) -> Result<SavedMacro> {
+    match StorageFormat::from_path(path) {
+        Some(StorageFormat::Text) => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            dsl::parse_macro(&fs::read_to_string(path)?, &stem)
+                .map_err(|e| MacroError::Format(e.to_string()))
+        },
+        Some(StorageFormat::Compact) => compact::decode(&fs::read(path)?),
+        _ => SavedMacro::from_reader(BufReader::new(fs::File::open(path)?)),
+    }
+}
+
+/// 宏文件中除事件外的部分, 字段与 [`SavedMacro`] 相同
+#[derive(Deserialize)]
+struct MacroHeader {
+    #[serde(default)]
+    format_version: u32,
+    name: String,
+    /// 逐个校验事件但不保存, 内容损坏的宏在加载时就能发现
+    #[serde(rename = "events")]
+    _events: CheckedEvents,
+    created_at: u64,
+    #[serde(default)]
+    updated_at: u64,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    author: String,
+    #[serde(default)]
+    screen: Option<ScreenInfo>,
+    #[serde(default)]
+    stats: MacroStats,
+    #[serde(default)]
+    hotkey: Option<Hotkey>,
+}
+
+/// 逐个解析后丢弃的事件列表
+struct CheckedEvents;
+
+impl<'de> Deserialize<'de> for CheckedEvents {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct EventsVisitor;
+
+        impl<'de> Visitor<'de> for EventsVisitor {
+            type Value = CheckedEvents;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a list of macro events")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self, mut seq: A,
+            ) -> std::result::Result<CheckedEvents, A::Error> {
+                while seq.next_element::<MacroEvent>()?.is_some() {}
+                Ok(CheckedEvents)
+            }
+        }
+
+        deserializer.deserialize_seq(EventsVisitor)
+    }
+}
+
+impl From<MacroHeader> for SavedMacro {
+    fn from(header: MacroHeader) -> Self {
+        Self {
+            format_version: header.format_version,
+            name: header.name,
+            events: Vec::new(),
+            created_at: header.created_at,
+            updated_at: header.updated_at,
+            description: header.description,
+            tags: header.tags,
+            author: header.author,
+            screen: header.screen,
+            stats: header.stats,
+            hotkey: header.hotkey,
+        }
+    }
+}
+
+/// 只读取宏文件的元数据, 返回的宏没有事件
+///
+/// 事件列表同样会校验, 损坏的宏在加载报告中列出, 而不是等到播放时才失败.
+/// 当前版本的 JSON 逐个校验事件但不保存; 需要迁移的旧文件(统计要由事件重新计算)、
+/// 文本和压缩格式完整解析后丢弃事件.
+fn read_macro_header(path: &Path) -> Result<SavedMacro> {
+    if StorageFormat::from_path(path) == Some(StorageFormat::Json)
+        && let Ok(header) = serde_json::from_slice::<MacroHeader>(&fs::read(path)?)
+        && header.format_version == migration::CURRENT_FORMAT_VERSION
+    {
+        return Ok(header.into());
+    }
+    read_macro_file(path).map(|saved_macro| saved_macro.header())
+}
+
+/// 按扩展名序列化宏, 保持文件原来的格式
+fn encode_macro_file(path: &Path, saved_macro: &SavedMacro) -> Result<Vec<u8>> {
+    match StorageFormat::from_path(path) {
+        Some(StorageFormat::Text) => Ok(dsl::print_macro(saved_macro).into_bytes()),
+        Some(StorageFormat::Compact) => compact::encode(saved_macro),
+        _ => Ok(serde_json::to_vec(saved_macro)?),
+    }
+}
+
+/// 当前系统用户名, 作为宏的默认作者
+fn default_author() -> String {
+    std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_default()
+}
+
+/// 隔离损坏文件的子目录
+pub const BROKEN_DIR: &str = "broken";
+
+/// 加载失败的宏文件
+#[derive(Debug, Clone)]
+pub struct LoadFailure {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// 加载报告, 记录成功数量和每个失败的文件
+#[derive(Debug, Clone, Default)]
+pub struct LoadReport {
+    pub loaded: usize,
+    pub failures: Vec<LoadFailure>,
+}
+
+impl LoadReport {
+    pub fn has_failures(&self) -> bool {
+        !self.failures.is_empty()
+    }
+}
+
+/// 一次磁盘变化检查的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReloadSummary {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl ReloadSummary {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// 文件的修改时间和大小, 用来判断文件是否变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// 挂载的宏库
+#[derive(Debug)]
+struct Library {
+    /// 库名称, 主库为空字符串
+    name: String,
+    root: PathBuf,
+    read_only: bool,
+    history: History,
+    trash: Trash,
+}
+
+impl Library {
+    fn new(name: &str, root: PathBuf, read_only: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            history: History::new(&root),
+            trash: Trash::new(&root),
+            root,
+            read_only,
+        }
+    }
+
+    fn is_primary(&self) -> bool {
+        self.name.is_empty()
+    }
+
+    /// 库内名称 -> 全局名称, 主库的宏不带前缀
+    fn qualify(&self, local: &str) -> String {
+        if self.is_primary() { local.to_string() } else { format!("{}:{local}", self.name) }
+    }
+
+    /// 库内分组 -> 全局分组, 其他库的根分组为 `库名:`
+    fn qualify_group(&self, local: &str) -> String {
+        self.qualify(local)
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(MacroError::ReadOnly(self.name.clone()));
+        }
+        Ok(())
+    }
+
+    /// 写入磁盘的宏数据使用库内名称
+    fn to_local(&self, saved_macro: &SavedMacro, local: &str) -> SavedMacro {
+        SavedMacro {
+            name: local.to_string(),
+            ..saved_macro.clone()
+        }
+    }
+}
+
+/// 宏库的概要信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryInfo {
+    /// 库名称, 主库为空字符串
+    pub name: String,
+    pub path: PathBuf,
+    pub read_only: bool,
 }
 
+/// 默认缓存的宏内容数量
+pub const DEFAULT_BODY_CACHE_SIZE: usize = 16;
+
+/// 宏管理器
+///
+/// 可以挂载多个宏库: 主库中的宏直接使用名称, 其他库中的宏以 `库名:宏名` 区分,
+/// 分组同理, 其他库的根分组为 `库名:`.
+///
+/// 启动时只读取宏的元数据, 事件在播放、编辑或导出时才从磁盘读取, 并缓存最近用过的宏.
 #[derive(Debug, Clone)]
 pub struct MacroManager {
+    /// 宏的元数据, 不含事件
     pub macros: Arc<RwLock<BTreeMap<String, Arc<SavedMacro>>>>,
-    storage_path: String,
+    /// 最近读取的完整宏内容
+    bodies: Arc<Mutex<LruCache<String, Arc<SavedMacro>>>>,
+    /// 宏名称 -> 磁盘文件, 文件名由名称生成, 与显示名称分离
+    files: Arc<RwLock<BTreeMap<String, PathBuf>>>,
+    /// 分组, 对应宏库下的子目录, 用 `/` 分隔的相对路径表示, 根分组为空字符串
+    groups: Arc<RwLock<BTreeSet<String>>>,
+    /// 第一个是主库
+    libraries: Arc<RwLock<Vec<Arc<Library>>>>,
+    load_report: Arc<RwLock<Arc<LoadReport>>>,
+    /// 最近一次删除的批次, 用于撤销
+    last_deleted: Arc<RwLock<Option<String>>>,
+    /// 已加载或自己写入的宏文件状态, 用于发现磁盘上的外部修改
+    known_files: Arc<RwLock<BTreeMap<PathBuf, FileStamp>>>,
+    /// 发现变化但还没稳定下来的文件, 下一次检查时状态不变才会重新加载
+    pending_files: Arc<RwLock<BTreeMap<PathBuf, FileStamp>>>,
+    /// 加载和检查变化互斥, 避免同一个文件被两边同时加入
+    scan_lock: Arc<Mutex<()>>,
+    /// 后台首次加载完成前不检查变化
+    loading: Arc<AtomicBool>,
+    /// 新宏使用的存储格式, 已有的宏保持原来的格式
+    default_format: Arc<RwLock<StorageFormat>>,
 }
 
 impl MacroManager {
     pub fn new() -> Self {
-        // 使用用户主目录下的应用程序数据目录
-        let storage_path = if let Some(home_dir) = dirs::home_dir() {
-            home_dir.join(".mousepilot").join("macros").to_string_lossy().to_string()
-        } else {
-            // 回退到当前目录
-            "macros".to_string()
-        };
-        debug!("storage_path: {storage_path}");
+        Self::from_settings(&Settings::load().with_env())
+    }
 
-        // alert::alert(&storage_path, Some("alert"), None, None);
+    /// 按设置挂载主库和其他宏库, 并在后台加载
+    pub fn from_settings(settings: &Settings) -> Self {
+        let storage_path = settings.macros_dir();
+        debug!("storage_path: {}", storage_path.display());
 
         // 确保存储目录存在
-        if !Path::new(&storage_path).exists() {
-            if let Err(e) = fs::create_dir_all(&storage_path) {
-                debug!("Failed to create macros directory: {e}");
-                alert::alert(&e.to_string(), Some("alert"), None, None);
-            }
+        if !storage_path.exists()
+            && let Err(e) = fs::create_dir_all(&storage_path)
+        {
+            debug!("Failed to create macros directory: {e}");
+            alert::alert(&e.to_string(), Some("alert"), None, None);
         }
 
-        let manager = Self {
-            macros: Default::default(),
-            storage_path,
-        };
-
+        let manager = Self::with_primary(storage_path);
+        manager.set_default_format(settings.storage_format);
+        manager.loading.store(true, Ordering::SeqCst);
+        let configs = settings.libraries.clone();
         let manager_clone = manager.clone();
         thread::spawn(move || {
             // 加载已保存的宏
             if let Err(e) = manager_clone.load_all_macros() {
                 debug!("Failed to load macros: {e}");
+                let failure = LoadFailure {
+                    path: manager_clone.primary().root.clone(),
+                    error: e.to_string(),
+                };
+                *manager_clone.load_report.write() = Arc::new(LoadReport {
+                    loaded: 0,
+                    failures: vec![failure],
+                });
             }
+            for config in configs.iter() {
+                if let Err(e) = manager_clone.mount_library(config) {
+                    debug!("Failed to mount library {}: {e}", config.name);
+                    manager_clone.report_failure(PathBuf::from(&config.path), e.to_string());
+                }
+            }
+            manager_clone.loading.store(false, Ordering::SeqCst);
         });
 
         manager
     }
 
-    pub fn save_macro(&self, name: &str, events: Vec<MacroEvent>) -> Result<()> {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    /// 等待后台首次加载完成, 超时返回 false
+    pub fn wait_until_loaded(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.loading.load(Ordering::SeqCst) {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
 
-        let saved_macro = SavedMacro {
-            name: name.to_string(),
-            events,
-            created_at: now,
+    /// 打开指定目录并同步加载其中的宏
+    pub fn open(storage_path: impl Into<String>) -> Result<Self> {
+        Self::open_with_libraries(storage_path, &[])
+    }
+
+    /// 打开主库并挂载其他宏库, 同步加载
+    pub fn open_with_libraries(
+        storage_path: impl Into<String>, libraries: &[LibraryConfig],
+    ) -> Result<Self> {
+        let storage_path = PathBuf::from(storage_path.into());
+        fs::create_dir_all(&storage_path)?;
+
+        let manager = Self::with_primary(storage_path);
+        manager.load_all_macros()?;
+        for config in libraries {
+            manager.mount_library(config)?;
+        }
+        Ok(manager)
+    }
+
+    fn with_primary(storage_path: PathBuf) -> Self {
+        Self {
+            macros: Default::default(),
+            bodies: Arc::new(Mutex::new(LruCache::new(DEFAULT_BODY_CACHE_SIZE))),
+            files: Default::default(),
+            groups: Default::default(),
+            libraries: Arc::new(RwLock::new(vec![Arc::new(Library::new("", storage_path, false))])),
+            load_report: Default::default(),
+            last_deleted: Default::default(),
+            known_files: Default::default(),
+            pending_files: Default::default(),
+            scan_lock: Default::default(),
+            loading: Default::default(),
+            default_format: Default::default(),
+        }
+    }
+
+    /// 挂载宏库并加载其中的宏
+    pub fn mount_library(&self, config: &LibraryConfig) -> Result<()> {
+        config.validate().map_err(|e| MacroError::InvalidName(e.to_string()))?;
+        if self.library(&config.name).is_some() {
+            return Err(MacroError::AlreadyExists(config.name.clone()));
+        }
+
+        let root = PathBuf::from(&config.path);
+        if !root.is_dir() {
+            if config.read_only {
+                return Err(MacroError::NotFound(config.path.clone()));
+            }
+            fs::create_dir_all(&root)?;
+        }
+
+        let library = Arc::new(Library::new(&config.name, root, config.read_only));
+        self.libraries.write().push(library.clone());
+        self.register_group(&library.qualify_group(""));
+
+        let mut report = LoadReport::default();
+        self.load_library(&library, &mut report)?;
+        self.merge_report(report);
+        Ok(())
+    }
+
+    /// 所有挂载的宏库, 第一个是主库
+    pub fn libraries(&self) -> Vec<LibraryInfo> {
+        self.libraries
+            .read()
+            .iter()
+            .map(|library| LibraryInfo {
+                name: library.name.clone(),
+                path: library.root.clone(),
+                read_only: library.read_only,
+            })
+            .collect()
+    }
+
+    /// 宏或分组所在库的名称, 主库为空字符串
+    pub fn library_of(&self, name: &str) -> String {
+        self.split_name(name).0.name.clone()
+    }
+
+    /// 宏或分组所在的库是否只读
+    pub fn is_read_only(&self, name: &str) -> bool {
+        self.split_name(name).0.read_only
+    }
+
+    fn primary(&self) -> Arc<Library> {
+        self.libraries.read()[0].clone()
+    }
+
+    fn library(&self, name: &str) -> Option<Arc<Library>> {
+        self.libraries
+            .read()
+            .iter()
+            .find(|l| !l.is_primary() && l.name == name)
+            .cloned()
+    }
+
+    /// 全局名称 -> (宏库, 库内名称); 前缀不是已挂载的库名时属于主库
+    fn split_name<'a>(&self, name: &'a str) -> (Arc<Library>, &'a str) {
+        if let Some((lib, local)) = name.split_once(':')
+            && let Some(library) = self.library(lib)
+        {
+            return (library, local);
+        }
+        (self.primary(), name)
+    }
+
+    /// 文件或目录所在的宏库
+    fn library_of_path(&self, path: &Path) -> Arc<Library> {
+        self.libraries
+            .read()
+            .iter()
+            .filter(|l| path.starts_with(&l.root))
+            .max_by_key(|l| l.root.components().count())
+            .cloned()
+            .unwrap_or_else(|| self.primary())
+    }
+
+    /// 保存宏, 返回最终使用的名称(自动添加后缀时与 `name` 不同)
+    pub fn save_macro(
+        &self, name: &str, events: Vec<MacroEvent>, policy: ConflictPolicy,
+    ) -> Result<String> {
+        self.save(SavedMacro::new(name, events), policy)
+    }
+
+    /// 保存完整的宏数据, 名称取自 `saved_macro.name`, 带库名前缀时保存到对应的库
+    pub fn save(&self, saved_macro: SavedMacro, policy: ConflictPolicy) -> Result<String> {
+        let root = self.split_name(&saved_macro.name).0.root.clone();
+        self.save_in(saved_macro, policy, &root, &[])
+    }
+
+    /// 保存宏, 新宏放在 `dir` 中, 已有的宏沿用原来的文件
+    ///
+    /// 宏的快捷键是保留的组合, 或者与其他宏、`taken` 中的快捷键冲突时, 保存时去掉快捷键.
+    fn save_in(
+        &self, mut saved_macro: SavedMacro, policy: ConflictPolicy, dir: &Path, taken: &[Hotkey],
+    ) -> Result<String> {
+        let (library, local) = self.split_name(&saved_macro.name);
+        library.check_writable()?;
+        storage::validate_name(local).map_err(MacroError::InvalidName)?;
+
+        // 持有写锁直到磁盘写入完成, 保证内存和磁盘一致
+        let mut macros = self.macros.write();
+        let mut files = self.files.write();
+        let name = Self::resolve_conflict(&macros, &saved_macro.name, policy)?;
+        let local = self.split_name(&name).1.to_string();
+        saved_macro.name = name.clone();
+        saved_macro.touch();
+        // 覆盖已有的宏时保留原来的创建时间
+        if let Some(existing) = macros.get(&name) {
+            saved_macro.created_at = existing.created_at;
+        }
+        if let Some(hotkey) = &saved_macro.hotkey
+            && (hotkey.is_reserved()
+                || taken.iter().any(|h| h.conflicts_with(hotkey))
+                || Self::hotkey_owner(&macros, &name, hotkey).is_some())
+        {
+            debug!("Dropping hotkey {hotkey} of macro {name}: reserved or already in use");
+            saved_macro.hotkey = None;
+        }
+
+        if macros.contains_key(&name)
+            && let Some(path) = files.get(&name)
+        {
+            let previous = self.load_body(&name, path)?;
+            library.history.record(&library.to_local(&previous, &local))?;
+        }
+        let file_path = match files.get(&name) {
+            Some(path) => path.clone(),
+            None => {
+                let extension = self.default_format().extension();
+                Self::allocate_file_path(&files, dir, &local, extension)
+            },
+        };
+        let contents = encode_macro_file(&file_path, &library.to_local(&saved_macro, &local))?;
+        storage::write_atomic(&file_path, &contents)?;
+        self.remember_file(&file_path);
+
+        files.insert(name.clone(), file_path);
+        macros.insert(name.clone(), Arc::new(saved_macro.header()));
+        self.bodies.lock().insert(name.clone(), Arc::new(saved_macro));
+        Ok(name)
+    }
+
+    /// 修改宏的元数据或事件并写回磁盘, 不能用来改名
+    pub fn edit_macro(&self, name: &str, edit: impl FnOnce(&mut SavedMacro)) -> Result<()> {
+        self.edit_macro_checked(name, |_| Ok(()), edit)
+    }
+
+    /// 同 [`Self::edit_macro`], 修改前在同一把写锁内先运行 `check`
+    fn edit_macro_checked(
+        &self, name: &str, check: impl FnOnce(&BTreeMap<String, Arc<SavedMacro>>) -> Result<()>,
+        edit: impl FnOnce(&mut SavedMacro),
+    ) -> Result<()> {
+        let (library, local) = self.split_name(name);
+        let mut macros = self.macros.write();
+        let files = self.files.read();
+        let Some(file_path) = files.get(name).filter(|_| macros.contains_key(name)) else {
+            return Err(MacroError::NotFound(name.to_string()));
         };
+        library.check_writable()?;
+        check(&macros)?;
 
-        let file_path = format!("{}/{}.json", self.storage_path, name);
-        let json = serde_json::to_string(&saved_macro)?;
-        fs::write(file_path, json)?;
+        let saved_macro = self.load_body(name, file_path)?;
+        library.history.record(&library.to_local(&saved_macro, local))?;
+        let mut saved_macro = SavedMacro::clone(&saved_macro);
+        edit(&mut saved_macro);
+        saved_macro.name = name.to_string();
+        saved_macro.touch();
 
-        self.macros.write().insert(name.to_string(), Arc::new(saved_macro));
+        let contents = encode_macro_file(file_path, &library.to_local(&saved_macro, local))?;
+        storage::write_atomic(file_path, &contents)?;
+        self.remember_file(file_path);
+        macros.insert(name.to_string(), Arc::new(saved_macro.header()));
+        self.bodies.lock().insert(name.to_string(), Arc::new(saved_macro));
         Ok(())
     }
 
+    /// 删除宏, 宏会先移到回收站
     pub fn delete_macro(&self, name: &str) -> Result<()> {
-        let file_path = format!("{}/{}.json", self.storage_path, name);
+        self.delete_macros(&[name.to_string()]).map(|_| ())
+    }
+
+    /// 批量删除, 返回删除的数量; 这一批可以通过 [`Self::undo_delete`] 一起恢复
+    pub fn delete_macros(&self, names: &[String]) -> Result<usize> {
+        for name in names {
+            self.split_name(name).0.check_writable()?;
+        }
+
+        let mut macros = self.macros.write();
+        let mut files = self.files.write();
+        // 同一毫秒内的多次删除也要分成不同批次
+        static BATCH_SEQ: AtomicU64 = AtomicU64::new(0);
+        let millis = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let batch = format!("{millis:013}-{}", BATCH_SEQ.fetch_add(1, Ordering::Relaxed));
+
+        let mut deleted = 0;
+        let mut failure = None;
+        for name in names {
+            let Some(file_path) = files.get(name).filter(|_| macros.contains_key(name)).cloned()
+            else {
+                continue;
+            };
+            if let Err(e) = self.discard_file(name, &file_path, &batch) {
+                failure = Some(e);
+                break;
+            }
+            files.remove(name);
+            macros.remove(name);
+            self.bodies.lock().remove(name);
+            deleted += 1;
+        }
+
+        // 中途失败时, 已经删除的宏仍然可以一起撤销
+        if deleted > 0 {
+            *self.last_deleted.write() = Some(batch);
+        }
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(deleted),
+        }
+    }
+
+    /// 删除宏文件: 正常的宏先放进回收站, 文件删除失败时撤回回收站里的副本;
+    /// 内容损坏的宏不能放进回收站, 原文件移到 broken/ 中保留
+    fn discard_file(&self, name: &str, file_path: &Path, batch: &str) -> Result<()> {
+        let (library, local) = self.split_name(name);
+        match self.load_body(name, file_path) {
+            Ok(saved_macro) => {
+                let group = self.local_group_of(file_path);
+                let id = library.trash.put(batch, &group, &library.to_local(&saved_macro, local))?;
+                if file_path.exists()
+                    && let Err(e) = fs::remove_file(file_path)
+                {
+                    if let Err(e) = library.trash.remove(&id) {
+                        debug!("Failed to remove trash entry {id}: {e}");
+                    }
+                    return Err(e.into());
+                }
+            },
+            Err(e) if file_path.exists() => {
+                debug!("Moving unreadable macro {} to {BROKEN_DIR}: {e}", file_path.display());
+                Self::move_to_broken(&library, file_path)?;
+            },
+            Err(_) => {},
+        }
+        Ok(())
+    }
+
+    /// 最近一次删除中还在回收站里的宏
+    pub fn last_deleted(&self) -> Vec<TrashEntry> {
+        let Some(batch) = self.last_deleted.read().clone() else {
+            return Vec::new();
+        };
+        self.list_trash().into_iter().filter(|e| e.batch == batch).collect()
+    }
+
+    /// 撤销最近一次删除, 返回恢复后的名称
+    pub fn undo_delete(&self) -> Result<Vec<String>> {
+        let entries = self.last_deleted();
+        let mut restored = Vec::new();
+        for entry in entries {
+            restored.push(self.restore_from_trash(&entry.id, ConflictPolicy::AutoSuffix)?);
+        }
+        *self.last_deleted.write() = None;
+        Ok(restored)
+    }
+
+    /// 所有库回收站中的宏, 最近删除的在前; ID、名称和分组都带库名前缀
+    pub fn list_trash(&self) -> Vec<TrashEntry> {
+        let libraries = self.libraries.read().clone();
+        let mut entries = Vec::new();
+        for library in libraries {
+            match library.trash.list() {
+                Ok(list) => entries.extend(list.into_iter().map(|entry| TrashEntry {
+                    id: library.qualify(&entry.id),
+                    name: library.qualify(&entry.name),
+                    group: library.qualify_group(&entry.group),
+                    ..entry
+                })),
+                Err(e) => debug!("Failed to list trash: {e}"),
+            }
+        }
+        entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then_with(|| a.id.cmp(&b.id)));
+        entries
+    }
+
+    /// 从回收站恢复到原来的分组, 返回最终使用的名称
+    pub fn restore_from_trash(&self, id: &str, policy: ConflictPolicy) -> Result<String> {
+        let (library, local_id) = self.split_name(id);
+        library.check_writable()?;
+        let (mut saved_macro, group) = library.trash.load(local_id)?;
+        saved_macro.name = library.qualify(&saved_macro.name);
+
+        let group = self.normalize_group(&library.qualify_group(&group));
+        let dir = self.group_dir(&group);
+        fs::create_dir_all(&dir)?;
+        self.register_group(&group);
+
+        let name = self.save_in(saved_macro, policy, &dir, &[])?;
+        library.trash.remove(local_id)?;
+        Ok(name)
+    }
+
+    /// 从回收站永久删除
+    pub fn purge_from_trash(&self, id: &str) -> Result<()> {
+        let (library, local_id) = self.split_name(id);
+        library.check_writable()?;
+        library.trash.remove(local_id)
+    }
+
+    /// 清空所有可写库的回收站, 返回删除的数量
+    pub fn empty_trash(&self) -> Result<usize> {
+        let libraries = self.libraries.read().clone();
+        let mut purged = 0;
+        for library in libraries.iter().filter(|l| !l.read_only) {
+            for entry in library.trash.list()? {
+                library.trash.remove(&entry.id)?;
+                purged += 1;
+            }
+        }
+        *self.last_deleted.write() = None;
+        Ok(purged)
+    }
+
+    /// 回收站保留天数, 0 表示永久保留
+    pub fn trash_retention_days(&self) -> u64 {
+        self.primary().trash.retention_days()
+    }
+
+    pub fn set_trash_retention_days(&self, days: u64) {
+        for library in self.libraries.read().iter() {
+            library.trash.set_retention_days(days);
+        }
+    }
+
+    /// 删除所有可写库回收站里超过保留期的宏
+    pub fn purge_expired_trash(&self) -> Result<usize> {
+        let libraries = self.libraries.read().clone();
+        let mut purged = 0;
+        for library in libraries.iter().filter(|l| !l.read_only) {
+            purged += library.trash.purge_expired()?;
+        }
+        Ok(purged)
+    }
+
+    /// 重命名宏, 返回最终使用的名称
+    ///
+    /// 先原子写入新文件, 成功后再把旧文件转为 `.bak`, 任何一步失败都会回滚,
+    /// 内存中的列表只在磁盘操作全部成功后才更新. 不能跨库改名.
+    pub fn rename_macro(
+        &self, old_name: &str, new_name: &str, policy: ConflictPolicy,
+    ) -> Result<String> {
+        let (library, old_local) = self.split_name(old_name);
+        let (new_library, new_local) = self.split_name(new_name);
+        storage::validate_name(new_local).map_err(MacroError::InvalidName)?;
+        if old_name == new_name {
+            return Ok(new_name.to_string());
+        }
+        library.check_writable()?;
+        if !Arc::ptr_eq(&library, &new_library) {
+            return Err(MacroError::InvalidName(format!(
+                "cannot rename across libraries: {old_name} -> {new_name}"
+            )));
+        }
+
+        let mut macros = self.macros.write();
+        let mut files = self.files.write();
+        let Some(old_path) = files.get(old_name).filter(|_| macros.contains_key(old_name)) else {
+            return Err(MacroError::NotFound(old_name.to_string()));
+        };
+        let macro_data = self.load_body(old_name, old_path)?;
+        let new_name = Self::resolve_conflict(&macros, new_name, policy)?;
+        let new_local = self.split_name(&new_name).1.to_string();
+        if macros.contains_key(&new_name)
+            && let Some(path) = files.get(&new_name)
+        {
+            let overwritten = self.load_body(&new_name, path)?;
+            library.history.record(&library.to_local(&overwritten, &new_local))?;
+        }
+
+        let before = library.to_local(&macro_data, &new_local);
+        let mut macro_data = SavedMacro::clone(&macro_data);
+        macro_data.name = new_name.clone();
+        macro_data.touch();
+        // 覆盖时沿用被覆盖宏的文件, 旧内容会保留为 .bak; 否则留在原分组, 保持原来的格式
+        let new_path = match (files.get(&new_name), files.get(old_name)) {
+            (Some(path), _) => path.clone(),
+            (None, Some(old_path)) => {
+                let dir = old_path.parent().unwrap_or(&library.root);
+                Self::allocate_file_path(&files, dir, &new_local, file_extension(old_path))
+            },
+            (None, None) => {
+                let extension = self.default_format().extension();
+                Self::allocate_file_path(&files, &library.root, &new_local, extension)
+            },
+        };
+        let contents = encode_macro_file(&new_path, &library.to_local(&macro_data, &new_local))?;
+        storage::write_atomic(&new_path, &contents)?;
+        self.remember_file(&new_path);
+
+        if let Some(old_path) = files.get(old_name)
+            && old_path.exists()
+            && let Err(e) = fs::rename(old_path, storage::backup_path(old_path))
+        {
+            let backup = storage::backup_path(&new_path);
+            if files.contains_key(&new_name) && backup.exists() {
+                let _ = fs::rename(backup, &new_path);
+            } else {
+                let _ = fs::remove_file(&new_path);
+            }
+            return Err(e.into());
+        }
+
+        files.remove(old_name);
+        files.insert(new_name.clone(), new_path);
+        macros.remove(old_name);
+        macros.insert(new_name.clone(), Arc::new(macro_data.header()));
+        let mut bodies = self.bodies.lock();
+        bodies.remove(old_name);
+        bodies.insert(new_name.clone(), Arc::new(macro_data));
+        drop(bodies);
+
+        // 历史版本跟随改名, 改名前的内容也作为一个版本保留
+        if let Err(e) = library.history.rename(old_local, &new_local) {
+            debug!("Failed to move history of {old_name}: {e}");
+        }
+        if let Err(e) = library.history.record(&before) {
+            debug!("Failed to record history of {new_name}: {e}");
+        }
+        Ok(new_name)
+    }
+
+    pub fn default_format(&self) -> StorageFormat {
+        *self.default_format.read()
+    }
+
+    /// 设置新宏的存储格式
+    pub fn set_default_format(&self, format: StorageFormat) {
+        *self.default_format.write() = format;
+    }
+
+    /// 宏文件的存储格式
+    pub fn storage_format(&self, name: &str) -> Option<StorageFormat> {
+        self.files.read().get(name).and_then(|path| StorageFormat::from_path(path))
+    }
+
+    /// 把宏转换为另一种存储格式, 文件留在原分组, 旧文件会被删除
+    pub fn convert_macro(&self, name: &str, format: StorageFormat) -> Result<()> {
+        let (library, local) = self.split_name(name);
+        // 只在锁内取出路径, 读写文件时不持有锁
+        let old_path = {
+            let macros = self.macros.read();
+            let files = self.files.read();
+            let Some(path) = files.get(name).filter(|_| macros.contains_key(name)) else {
+                return Err(MacroError::NotFound(name.to_string()));
+            };
+            path.clone()
+        };
+        if StorageFormat::from_path(&old_path) == Some(format) {
+            return Ok(());
+        }
+        library.check_writable()?;
+        let saved_macro = self.load_body(name, &old_path)?;
 
-        if Path::new(&file_path).exists() {
-            fs::remove_file(file_path)?;
+        let dir = old_path.parent().unwrap_or(&library.root);
+        let new_path =
+            Self::allocate_file_path(&self.files.read(), dir, local, format.extension());
+        let contents = encode_macro_file(&new_path, &library.to_local(&saved_macro, local))?;
+        storage::write_atomic(&new_path, &contents)?;
+        self.remember_file(&new_path);
+        // 旧文件删不掉时撤销新文件, 避免同一个宏在磁盘上出现两份
+        if let Err(e) = fs::remove_file(&old_path) {
+            let _ = fs::remove_file(&new_path);
+            return Err(e.into());
         }
+        self.files.write().insert(name.to_string(), new_path);
+        Ok(())
+    }
+
+    /// 每个宏保留的历史版本数
+    pub fn set_history_limit(&self, limit: usize) {
+        for library in self.libraries.read().iter() {
+            library.history.set_limit(limit);
+        }
+    }
 
-        self.macros.write().remove(name);
+    /// 宏的历史版本, 从旧到新排列
+    pub fn list_revisions(&self, name: &str) -> Result<Vec<RevisionInfo>> {
+        let (library, local) = self.split_name(name);
+        library.history.list(local)
+    }
+
+    /// 比较历史版本和当前版本的事件数和时长
+    pub fn diff_revision(&self, name: &str, id: &str) -> Result<RevisionDiff> {
+        let Some(current) = self.macros.read().get(name).cloned() else {
+            return Err(MacroError::NotFound(name.to_string()));
+        };
+        let (library, local) = self.split_name(name);
+        let revision = library.history.load(local, id)?;
+        Ok(RevisionDiff {
+            revision: revision.stats,
+            current: current.stats.clone(),
+        })
+    }
+
+    /// 恢复到历史版本, 当前内容会先记入历史, 恢复操作本身也可以撤销
+    pub fn restore_revision(&self, name: &str, id: &str) -> Result<()> {
+        if !self.macro_exists(name) {
+            return Err(MacroError::NotFound(name.to_string()));
+        }
+        let (library, local) = self.split_name(name);
+        let mut revision = library.history.load(local, id)?;
+        revision.name = name.to_string();
+        self.save(revision, ConflictPolicy::Overwrite)?;
         Ok(())
     }
 
-    pub fn rename_macro(&self, old_name: &str, new_name: &str) -> Result<()> {
-        let old_path = format!("{}/{}.json", self.storage_path, old_name);
-        let new_path = format!("{}/{}.json", self.storage_path, new_name);
+    /// 按冲突策略确定最终名称
+    fn resolve_conflict(
+        macros: &BTreeMap<String, Arc<SavedMacro>>, name: &str, policy: ConflictPolicy,
+    ) -> Result<String> {
+        if !macros.contains_key(name) {
+            return Ok(name.to_string());
+        }
+        match policy {
+            ConflictPolicy::Error => Err(MacroError::AlreadyExists(name.to_string())),
+            ConflictPolicy::Overwrite => Ok(name.to_string()),
+            ConflictPolicy::AutoSuffix => Ok(Self::suffixed_name(macros, name)),
+        }
+    }
+
+    /// 生成 "name (2)"、"name (3)" 形式的未使用名称
+    fn suffixed_name(macros: &BTreeMap<String, Arc<SavedMacro>>, name: &str) -> String {
+        (2..)
+            .map(|n| format!("{name} ({n})"))
+            .find(|candidate| !macros.contains_key(candidate))
+            .unwrap()
+    }
+
+    /// 名称已被占用时返回可用的带后缀名称, 否则原样返回
+    pub fn unique_name(&self, name: &str) -> String {
+        let macros = self.macros.read();
+        if macros.contains_key(name) {
+            Self::suffixed_name(&macros, name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// 在 `dir` 中为宏分配不冲突的文件路径, 文件名由库内名称生成
+    fn allocate_file_path(
+        files: &BTreeMap<String, PathBuf>, dir: &Path, local: &str, extension: &str,
+    ) -> PathBuf {
+        let slug = storage::slugify(local);
+        // 按不区分大小写比较, 兼容 macOS / Windows 文件系统
+        let taken = |path: &Path| {
+            path.exists()
+                || files.values().any(|p| {
+                    p.to_string_lossy().to_lowercase() == path.to_string_lossy().to_lowercase()
+                })
+        };
+
+        let mut path = dir.join(format!("{slug}.{extension}"));
+        let mut n = 2;
+        while taken(&path) {
+            path = dir.join(format!("{slug}~{n}.{extension}"));
+            n += 1;
+        }
+        path
+    }
+
+    /// 规范化分组路径: 保留库名前缀, 去掉空段, 每段转换为安全的目录名
+    fn normalize_group(&self, group: &str) -> String {
+        let (library, local) = self.split_name(group);
+        library.qualify_group(&normalize_local_group(local))
+    }
 
-        if Path::new(&old_path).exists() {
-            fs::rename(old_path, &new_path)?;
+    /// 父分组, 根分组没有父分组; 其他库的根分组 `库名:` 的父分组是主库的根分组
+    pub fn parent_group(group: &str) -> Option<&str> {
+        if group.is_empty() {
+            return None;
         }
-        let macro_data = self.macros.write().remove(old_name);
-        if let Some(macro_data) = macro_data {
-            let macro_data = SavedMacro {
-                name: new_name.to_string(),
-                events: macro_data.events.clone(),
-                created_at: macro_data.created_at,
+        // 规范化后的库内分组不含 `:`, 带 `:` 的一定是其他库的分组
+        if let Some((lib, local)) = group.split_once(':') {
+            if local.is_empty() {
+                return Some("");
+            }
+            let end = match local.rsplit_once('/') {
+                Some((parent, _)) => lib.len() + 1 + parent.len(),
+                None => lib.len() + 1,
             };
-            fs::write(new_path, serde_json::to_string(&macro_data)?)?;
-            self.macros.write().insert(new_name.to_string(), Arc::new(macro_data));
+            return Some(&group[..end]);
+        }
+        Some(group.rsplit_once('/').map(|(parent, _)| parent).unwrap_or(""))
+    }
+
+    fn group_dir(&self, group: &str) -> PathBuf {
+        let (library, local) = self.split_name(group);
+        local
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .fold(library.root.clone(), |p, s| p.join(s))
+    }
+
+    /// 由文件路径得到所在分组
+    fn group_of(&self, path: &Path) -> String {
+        path.parent().map(|dir| self.dir_group(dir)).unwrap_or_default()
+    }
+
+    /// 文件在所在库中的分组, 不带库名前缀
+    fn local_group_of(&self, path: &Path) -> String {
+        let library = self.library_of_path(path);
+        path.parent().map(|dir| Self::relative_group(&library, dir)).unwrap_or_default()
+    }
+
+    /// 由目录得到对应的分组
+    fn dir_group(&self, dir: &Path) -> String {
+        let library = self.library_of_path(dir);
+        library.qualify_group(&Self::relative_group(&library, dir))
+    }
+
+    fn relative_group(library: &Library, dir: &Path) -> String {
+        dir.strip_prefix(&library.root)
+            .ok()
+            .map(|rel| {
+                rel.components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .unwrap_or_default()
+    }
+
+    /// 记录分组及其所有上级分组
+    fn register_group(&self, group: &str) {
+        let mut groups = self.groups.write();
+        let mut group = Some(group);
+        while let Some(g) = group {
+            if !g.is_empty() {
+                groups.insert(g.to_string());
+            }
+            group = Self::parent_group(g);
+        }
+    }
+
+    /// 创建分组(子目录), 返回规范化后的分组路径
+    pub fn create_group(&self, group: &str) -> Result<String> {
+        let group = self.normalize_group(group);
+        let (library, local) = self.split_name(&group);
+        if local.is_empty() {
+            return Err(MacroError::InvalidName("group name is empty".to_string()));
+        }
+        if local.split('/').next() == Some(BROKEN_DIR) {
+            return Err(MacroError::InvalidName(format!("group name is reserved: {BROKEN_DIR}")));
         }
+        library.check_writable()?;
+        fs::create_dir_all(self.group_dir(&group))?;
+        self.register_group(&group);
+        Ok(group)
+    }
+
+    /// 所有分组(不含主库的根分组), 按路径排序
+    pub fn get_groups(&self) -> Vec<String> {
+        self.groups.read().iter().cloned().collect()
+    }
+
+    /// 宏所在的分组
+    pub fn get_macro_group(&self, name: &str) -> String {
+        self.files.read().get(name).map(|path| self.group_of(path)).unwrap_or_default()
+    }
+
+    /// 分组内的宏名称, 包含所有子分组
+    pub fn get_macro_names_in_group(&self, group: &str) -> Vec<String> {
+        let prefix = if group.ends_with(':') { group.to_string() } else { format!("{group}/") };
+        self.files
+            .read()
+            .iter()
+            .filter(|(_, path)| {
+                let g = self.group_of(path);
+                group.is_empty() || g == group || g.starts_with(&prefix)
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// 把宏移动到同一个库中的另一个分组
+    pub fn move_macro(&self, name: &str, group: &str) -> Result<()> {
+        let group = self.normalize_group(group);
+        let (library, local_group) = self.split_name(&group);
+        if local_group.split('/').next() == Some(BROKEN_DIR) {
+            return Err(MacroError::InvalidName(format!("group name is reserved: {BROKEN_DIR}")));
+        }
+        let (macro_library, local) = self.split_name(name);
+        if !Arc::ptr_eq(&library, &macro_library) {
+            return Err(MacroError::InvalidName(format!(
+                "cannot move across libraries: {name} -> {group}"
+            )));
+        }
+        let dir = self.group_dir(&group);
+
+        let mut files = self.files.write();
+        let Some(old_path) = files.get(name).cloned() else {
+            return Err(MacroError::NotFound(name.to_string()));
+        };
+        if old_path.parent() == Some(dir.as_path()) {
+            return Ok(());
+        }
+        library.check_writable()?;
+
+        fs::create_dir_all(&dir)?;
+        let new_path = Self::allocate_file_path(&files, &dir, local, file_extension(&old_path));
+        fs::rename(&old_path, &new_path)?;
+        self.remember_file(&new_path);
+        files.insert(name.to_string(), new_path);
+        drop(files);
 
+        self.register_group(&group);
         Ok(())
     }
 
+    /// 把宏导出为宏包, 宏名称和分组都去掉库名前缀
+    pub fn export_bundle(&self, names: &[String]) -> Result<Bundle> {
+        let macros = self.macros.read();
+        let files = self.files.read();
+        let mut bundle = Bundle::default();
+        for name in names {
+            let Some(path) = files.get(name).filter(|_| macros.contains_key(name)) else {
+                return Err(MacroError::NotFound(name.to_string()));
+            };
+            let saved_macro = self.load_body(name, path)?;
+            let (library, local) = self.split_name(name);
+            bundle.push(&self.local_group_of(path), &library.to_local(&saved_macro, local))?;
+        }
+        Ok(bundle)
+    }
+
+    /// 把宏包导入到 `group` 下, 宏包里的分组保留为子分组
+    ///
+    /// [`ConflictPolicy::Error`] 表示跳过同名的宏, 跳过的宏记录在结果中.
+    /// 导入前先校验整个宏包, 校验失败时不会写入任何宏.
+    /// 快捷键是保留的组合或与其他宏、`taken`(如内置快捷键)冲突时, 导入后不带快捷键.
+    pub fn import_bundle(
+        &self, bundle: &Bundle, group: &str, policy: ConflictPolicy, taken: &[Hotkey],
+    ) -> Result<ImportReport> {
+        let macros = bundle.verify()?;
+        let target = self.normalize_group(group);
+        let (library, local_target) = self.split_name(&target);
+        library.check_writable()?;
+
+        let mut groups = Vec::with_capacity(macros.len());
+        for entry in bundle.entries.iter() {
+            // 宏包里的名称和分组都是库内名称, 不能借 `库名:` 写进其他库
+            storage::validate_name(&entry.name).map_err(MacroError::InvalidName)?;
+            let entry_group = normalize_local_group(&entry.group);
+            let local = [local_target, entry_group.as_str()]
+                .into_iter()
+                .filter(|g| !g.is_empty())
+                .collect::<Vec<_>>()
+                .join("/");
+            let group = self.normalize_group(&library.qualify_group(&local));
+            if self.split_name(&group).1.split('/').next() == Some(BROKEN_DIR) {
+                return Err(MacroError::InvalidName(format!(
+                    "group name is reserved: {BROKEN_DIR}"
+                )));
+            }
+            groups.push(group);
+        }
+
+        let mut report = ImportReport::default();
+        for (mut saved_macro, group) in macros.into_iter().zip(groups) {
+            saved_macro.name = library.qualify(&saved_macro.name);
+            let dir = self.group_dir(&group);
+            fs::create_dir_all(&dir)?;
+            self.register_group(&group);
+            let had_hotkey = saved_macro.hotkey.is_some();
+            match self.save_in(saved_macro, policy, &dir, taken) {
+                Ok(name) => {
+                    let header = self.get_macro_header(&name);
+                    if had_hotkey && header.is_some_and(|m| m.hotkey.is_none()) {
+                        report.dropped_hotkeys.push(name.clone());
+                    }
+                    report.imported.push(name);
+                },
+                Err(MacroError::AlreadyExists(name)) => report.skipped.push(name),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(report)
+    }
+
+    /// 所有宏的元数据, 不含事件
     pub fn get_all_macros(&self) -> Vec<Arc<SavedMacro>> {
         self.macros.read().values().cloned().collect()
     }
 
+    /// 设置了快捷键的宏和它们的快捷键
+    pub fn macro_hotkeys(&self) -> Vec<(String, Hotkey)> {
+        self.macros
+            .read()
+            .values()
+            .filter_map(|m| m.hotkey.clone().map(|hotkey| (m.name.clone(), hotkey)))
+            .collect()
+    }
+
+    /// 检查快捷键是否为保留的组合, 或者和 `name` 以外的宏的快捷键冲突
+    pub fn check_hotkey(&self, name: &str, hotkey: &Hotkey) -> Result<()> {
+        Self::check_hotkey_in(&self.macros.read(), name, hotkey)
+    }
+
+    fn check_hotkey_in(
+        macros: &BTreeMap<String, Arc<SavedMacro>>, name: &str, hotkey: &Hotkey,
+    ) -> Result<()> {
+        if hotkey.is_reserved() {
+            return Err(MacroError::ReservedHotkey(hotkey.clone()));
+        }
+        match Self::hotkey_owner(macros, name, hotkey) {
+            Some(other) => Err(MacroError::HotkeyConflict(format!("macro {other}"))),
+            None => Ok(()),
+        }
+    }
+
+    /// 快捷键与之冲突的宏, 不含 `name` 自己
+    fn hotkey_owner(
+        macros: &BTreeMap<String, Arc<SavedMacro>>, name: &str, hotkey: &Hotkey,
+    ) -> Option<String> {
+        macros
+            .values()
+            .find(|m| m.name != name && m.hotkey.as_ref().is_some_and(|h| h.conflicts_with(hotkey)))
+            .map(|m| m.name.clone())
+    }
+
+    /// 设置或清除宏的快捷键, 不能与其他宏的快捷键冲突
+    ///
+    /// 冲突检查和写入在同一把写锁内完成, 并发设置同一个快捷键时只有一个会成功.
+    pub fn set_hotkey(&self, name: &str, hotkey: Option<Hotkey>) -> Result<()> {
+        let new_hotkey = hotkey.clone();
+        self.edit_macro_checked(
+            name,
+            |macros| match &hotkey {
+                Some(hotkey) => Self::check_hotkey_in(macros, name, hotkey),
+                None => Ok(()),
+            },
+            |m| m.hotkey = new_hotkey,
+        )
+    }
+
+    /// 宏的元数据, 不含事件
+    pub fn get_macro_header(&self, name: &str) -> Option<Arc<SavedMacro>> {
+        self.macros.read().get(name).cloned()
+    }
+
+    /// 宏的完整内容, 事件不在缓存中时从磁盘读取
+    pub fn get_macro(&self, name: &str) -> Result<Arc<SavedMacro>> {
+        let Some(path) = self.files.read().get(name).cloned() else {
+            return Err(MacroError::NotFound(name.to_string()));
+        };
+        self.load_body(name, &path)
+    }
+
+    /// 缓存的宏内容数量上限, 0 表示每次都从磁盘读取
+    pub fn set_body_cache_size(&self, size: usize) {
+        self.bodies.lock().set_capacity(size);
+    }
+
+    /// 事件已经在缓存中的宏
+    pub fn is_body_cached(&self, name: &str) -> bool {
+        self.bodies.lock().contains(name)
+    }
+
+    /// 读取宏的完整内容并放入缓存; 名称以内存中的为准, 不用文件里的
+    fn load_body(&self, name: &str, path: &Path) -> Result<Arc<SavedMacro>> {
+        if let Some(saved_macro) = self.bodies.lock().get(name) {
+            return Ok(saved_macro);
+        }
+        let mut saved_macro = read_macro_file(path)?;
+        saved_macro.name = name.to_string();
+        let saved_macro = Arc::new(saved_macro);
+        self.bodies.lock().insert(name.to_string(), saved_macro.clone());
+        Ok(saved_macro)
+    }
+
     pub fn get_macro_count(&self) -> usize {
         self.macros.read().len()
     }
@@ -126,33 +1472,363 @@ impl MacroManager {
         self.macros.read().contains_key(name)
     }
 
-    fn load_all_macros(&self) -> Result<()> {
-        let dir = fs::read_dir(&self.storage_path)?;
+    fn load_all_macros(&self) -> Result<Arc<LoadReport>> {
+        let mut report = LoadReport::default();
+        let libraries = self.libraries.read().clone();
+        for library in libraries {
+            self.load_library(&library, &mut report)?;
+        }
+
+        report.failures.sort_by(|a, b| a.path.cmp(&b.path));
+        let report = Arc::new(report);
+        *self.load_report.write() = report.clone();
+        Ok(report)
+    }
+
+    fn load_library(&self, library: &Library, report: &mut LoadReport) -> Result<()> {
+        let _scan = self.scan_lock.lock();
+        self.load_dir(library, &library.root, report)?;
+        if !library.read_only
+            && let Err(e) = library.trash.purge_expired()
+        {
+            debug!("Failed to purge trash: {e}");
+        }
+        Ok(())
+    }
+
+    /// 递归加载目录, 子目录作为分组; 跳过 `broken/` 和以 `.` 开头的目录
+    fn load_dir(&self, library: &Library, dir: &Path, report: &mut LoadReport) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    report.failures.push(LoadFailure {
+                        path: dir.to_path_buf(),
+                        error: e.to_string(),
+                    });
+                    continue;
+                },
+            };
+
+            if path.is_dir() {
+                let dir_name = path.file_name().unwrap_or_default().to_string_lossy();
+                let is_broken = dir == library.root && dir_name == BROKEN_DIR;
+                if is_broken || dir_name.starts_with('.') {
+                    continue;
+                }
+                self.register_group(&library.qualify_group(&Self::relative_group(library, &path)));
+                if let Err(e) = self.load_dir(library, &path, report) {
+                    report.failures.push(LoadFailure {
+                        path,
+                        error: e.to_string(),
+                    });
+                }
+                continue;
+            }
 
-        for entry in dir {
-            let entry = entry?;
-            let path = entry.path();
+            if !is_macro_file(&path) {
+                continue;
+            }
 
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
-                    if let Ok(file) = fs::File::open(&path) {
-                        if let Ok(saved_macro) = serde_json::from_reader(BufReader::new(file)) {
-                            self.macros.write().insert(name.to_string(), Arc::new(saved_macro));
-                        }
+            self.remember_file(&path);
+            let result = read_macro_header(&path);
+            match result {
+                Ok(mut saved_macro) => {
+                    self.migrate_colon_name(library, &path, &mut saved_macro);
+                    if let Err(error) = storage::validate_name(&saved_macro.name) {
+                        report.failures.push(LoadFailure { path, error });
+                        continue;
                     }
+                    // 显示名称来自文件内容, 而不是文件名
+                    let name = library.qualify(&saved_macro.name);
+                    if self.macros.read().contains_key(&name) {
+                        let error = format!("duplicate macro name: {name}");
+                        report.failures.push(LoadFailure { path, error });
+                        continue;
+                    }
+                    saved_macro.name = name.clone();
+                    self.files.write().insert(name.clone(), path);
+                    self.macros.write().insert(name, Arc::new(saved_macro));
+                    report.loaded += 1;
+                },
+                Err(e) => {
+                    debug!("Failed to load macro {}: {e}", path.display());
+                    report.failures.push(LoadFailure {
+                        path,
+                        error: e.to_string(),
+                    });
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// 旧版本允许名称中有 `:`, 会和库名前缀混淆; 加载时替换为 `_` 并写回文件,
+    /// 只读库只修改内存中的名称
+    fn migrate_colon_name(&self, library: &Library, path: &Path, saved_macro: &mut SavedMacro) {
+        if !saved_macro.name.contains(':') {
+            return;
+        }
+        saved_macro.name = saved_macro.name.replace(':', "_");
+        if library.read_only {
+            return;
+        }
+        let result = read_macro_file(path).and_then(|mut full| {
+            full.name = saved_macro.name.clone();
+            storage::write_atomic(path, &encode_macro_file(path, &full)?)?;
+            Ok(())
+        });
+        match result {
+            Ok(()) => self.remember_file(path),
+            Err(e) => debug!("Failed to migrate macro name in {}: {e}", path.display()),
+        }
+    }
+
+    /// 记录文件当前的状态, 之后的检查不会把这次写入当作外部修改
+    fn remember_file(&self, path: &Path) {
+        if let Some(stamp) = FileStamp::of(path) {
+            self.known_files.write().insert(path.to_path_buf(), stamp);
+            self.pending_files.write().remove(path);
+        }
+    }
+
+    /// 检查所有宏库的磁盘变化, 增量更新内存中的宏
+    ///
+    /// 文件状态要在连续两次检查中保持不变才会重新加载, 避免读到写了一半的文件;
+    /// 自己写入的文件已经记录过状态, 不会被当作变化.
+    pub fn poll_changes(&self) -> ReloadSummary {
+        let mut summary = ReloadSummary::default();
+        if self.loading.load(Ordering::SeqCst) {
+            return summary;
+        }
+        let _scan = self.scan_lock.lock();
+        let libraries = self.libraries.read().clone();
+        let mut current = BTreeMap::new();
+        let mut dirs = Vec::new();
+        for library in libraries.iter() {
+            Self::scan_dir(library, &library.root, &mut current, &mut dirs);
+        }
+
+        // 目录变化直接同步到分组
+        for (library, dir) in dirs {
+            self.register_group(&library.qualify_group(&Self::relative_group(&library, &dir)));
+        }
+        self.groups.write().retain(|group| self.group_dir(group).is_dir());
+
+        // 找出状态已经稳定的变化
+        let mut ready = Vec::new();
+        let removed: Vec<PathBuf> = {
+            let known = self.known_files.read();
+            let mut pending = self.pending_files.write();
+            for (path, stamp) in current.iter() {
+                if known.get(path) == Some(stamp) {
+                    pending.remove(path);
+                } else if pending.get(path) == Some(stamp) {
+                    pending.remove(path);
+                    ready.push(path.clone());
+                } else {
+                    pending.insert(path.clone(), *stamp);
                 }
             }
+            pending.retain(|path, _| current.contains_key(path));
+            known.keys().filter(|path| !current.contains_key(*path)).cloned().collect()
+        };
+
+        for path in removed {
+            self.known_files.write().remove(&path);
+            let mut macros = self.macros.write();
+            let mut files = self.files.write();
+            if let Some(name) = files.iter().find(|(_, p)| **p == path).map(|(n, _)| n.clone()) {
+                files.remove(&name);
+                macros.remove(&name);
+                self.bodies.lock().remove(&name);
+                summary.removed.push(name);
+            }
         }
 
-        Ok(())
+        for path in ready {
+            self.known_files.write().insert(path.clone(), current[&path]);
+            let library = self.library_of_path(&path);
+            let result = read_macro_header(&path).and_then(|mut saved_macro| {
+                self.migrate_colon_name(&library, &path, &mut saved_macro);
+                storage::validate_name(&saved_macro.name).map_err(MacroError::InvalidName)?;
+                Ok(saved_macro)
+            });
+            let mut saved_macro = match result {
+                Ok(saved_macro) => saved_macro,
+                Err(e) => {
+                    // 保留内存中的旧版本, 等文件修好后再加载
+                    debug!("Failed to reload macro {}: {e}", path.display());
+                    self.replace_failure(&path, Some(e.to_string()));
+                    continue;
+                },
+            };
+            let name = library.qualify(&saved_macro.name);
+            saved_macro.name = name.clone();
+
+            let mut macros = self.macros.write();
+            let mut files = self.files.write();
+            if files.get(&name).is_some_and(|p| *p != path) {
+                drop((macros, files));
+                let error = format!("duplicate macro name: {name}");
+                self.replace_failure(&path, Some(error));
+                continue;
+            }
+            // 文件里的名称被改掉时, 旧名称随之消失
+            let old_name = files.iter().find(|(_, p)| **p == path).map(|(n, _)| n.clone());
+            if let Some(old_name) = old_name.as_ref().filter(|old| **old != name) {
+                files.remove(old_name);
+                macros.remove(old_name);
+                self.bodies.lock().remove(old_name);
+                summary.removed.push(old_name.clone());
+            }
+            if old_name.as_ref() == Some(&name) {
+                summary.updated.push(name.clone());
+            } else {
+                summary.added.push(name.clone());
+            }
+            self.bodies.lock().remove(&name);
+            files.insert(name.clone(), path.clone());
+            macros.insert(name, Arc::new(saved_macro));
+            drop((macros, files));
+            self.replace_failure(&path, None);
+        }
+
+        summary
+    }
+
+    /// 列出宏库中的宏文件和子目录, 跳过规则和加载时相同
+    fn scan_dir(
+        library: &Arc<Library>, dir: &Path, files: &mut BTreeMap<PathBuf, FileStamp>,
+        dirs: &mut Vec<(Arc<Library>, PathBuf)>,
+    ) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                let dir_name = path.file_name().unwrap_or_default().to_string_lossy();
+                let is_broken = dir == library.root && dir_name == BROKEN_DIR;
+                if !is_broken && !dir_name.starts_with('.') {
+                    Self::scan_dir(library, &path, files, dirs);
+                    dirs.push((library.clone(), path));
+                }
+            } else if is_macro_file(&path)
+                && let Some(stamp) = FileStamp::of(&path)
+            {
+                files.insert(path, stamp);
+            }
+        }
+    }
+
+    /// 更新加载报告中某个文件的失败信息, `error` 为 `None` 表示文件已恢复正常
+    fn replace_failure(&self, path: &Path, error: Option<String>) {
+        let mut report = self.load_report.write();
+        let mut failures: Vec<LoadFailure> =
+            report.failures.iter().filter(|f| f.path != path).cloned().collect();
+        if let Some(error) = error {
+            failures.push(LoadFailure {
+                path: path.to_path_buf(),
+                error,
+            });
+            failures.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+        *report = Arc::new(LoadReport {
+            loaded: report.loaded,
+            failures,
+        });
+    }
+
+    /// 把新的加载结果合并进当前报告
+    fn merge_report(&self, report: LoadReport) {
+        let mut current = self.load_report.write();
+        let mut failures = current.failures.clone();
+        failures.extend(report.failures);
+        failures.sort_by(|a, b| a.path.cmp(&b.path));
+        *current = Arc::new(LoadReport {
+            loaded: current.loaded + report.loaded,
+            failures,
+        });
+    }
+
+    fn report_failure(&self, path: PathBuf, error: String) {
+        self.merge_report(LoadReport {
+            loaded: 0,
+            failures: vec![LoadFailure { path, error }],
+        });
+    }
+
+    /// 最近一次加载的报告
+    pub fn get_load_report(&self) -> Arc<LoadReport> {
+        self.load_report.read().clone()
+    }
+
+    /// 忽略加载失败, 不再提示
+    pub fn dismiss_load_failures(&self) {
+        let loaded = self.load_report.read().loaded;
+        *self.load_report.write() = Arc::new(LoadReport {
+            loaded,
+            failures: Vec::new(),
+        });
+    }
+
+    /// 把加载失败的文件移动到所在库的 `broken/` 子目录, 返回移动的文件数量; 只读库中的文件保持不动
+    pub fn quarantine_broken_files(&self) -> Result<usize> {
+        let report = self.get_load_report();
+        let mut remaining = Vec::new();
+        let mut moved = 0;
+
+        for failure in report.failures.iter() {
+            let library = self.library_of_path(&failure.path);
+            if !failure.path.is_file() || library.read_only {
+                remaining.push(failure.clone());
+                continue;
+            }
+
+            match Self::move_to_broken(&library, &failure.path) {
+                Ok(_) => moved += 1,
+                Err(e) => {
+                    debug!("Failed to quarantine {}: {e}", failure.path.display());
+                    remaining.push(failure.clone());
+                },
+            }
+        }
+
+        *self.load_report.write() = Arc::new(LoadReport {
+            loaded: report.loaded,
+            failures: remaining,
+        });
+        Ok(moved)
     }
 
+    /// 把文件移到宏库的 `broken/` 目录, 不覆盖其中已有的同名文件
+    fn move_to_broken(library: &Library, path: &Path) -> Result<PathBuf> {
+        let broken_dir = library.root.join(BROKEN_DIR);
+        fs::create_dir_all(&broken_dir)?;
+        let file_name = path.file_name().unwrap_or_default();
+        let mut target = broken_dir.join(file_name);
+        let mut n = 1;
+        while target.exists() {
+            target = broken_dir.join(format!("{}.{n}", file_name.to_string_lossy()));
+            n += 1;
+        }
+        fs::rename(path, &target)?;
+        Ok(target)
+    }
+
+    /// 指定宏的完整内容, 用于播放和导出; 读取失败的宏会被跳过
     pub fn get_macros(&self, names: &[String]) -> Vec<Arc<SavedMacro>> {
-        self.macros
-            .read()
-            .values()
-            .filter(|m| names.contains(&m.name))
-            .cloned()
+        let selected: Vec<String> =
+            self.macros.read().keys().filter(|name| names.contains(name)).cloned().collect();
+        selected
+            .iter()
+            .filter_map(|name| match self.get_macro(name) {
+                Ok(saved_macro) => Some(saved_macro),
+                Err(e) => {
+                    debug!("Failed to load macro {name}: {e}");
+                    None
+                },
+            })
             .collect()
     }
 }
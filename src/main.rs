@@ -5,7 +5,7 @@
 use anyhow::Result;
 
 use eframe::egui;
-use mousepilot::{font::*, ui::App};
+use mousepilot::{cli, font::*, settings::Settings, ui::App};
 
 fn main() -> Result<()> {
     mousepilot_main()
@@ -15,6 +15,22 @@ fn mousepilot_main() -> Result<()> {
     dotenvy::dotenv().ok();
     env_logger::init();
 
+    // 子命令之前的参数是全局选项
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help") {
+        print!("{}", cli::USAGE);
+        return Ok(());
+    }
+    let command = cli::command_index(&args).map(|i| args.split_off(i));
+
+    // 设置文件 < 环境变量 < 命令行参数
+    let settings = Settings::load().with_env();
+    if let Some(command) = command {
+        // 子命令不打开窗口, 可以在脚本和定时任务中使用
+        return cli::run(&settings.with_args(args)?, &command, &mut std::io::stdout());
+    }
+    let settings = settings.with_gui_args(args)?;
+
     let icon = load_icon()?;
 
     let native_options = eframe::NativeOptions {
@@ -47,7 +63,7 @@ fn mousepilot_main() -> Result<()> {
                 );
             }
 
-            let app = App::new(&cc.egui_ctx);
+            let app = App::new(&cc.egui_ctx, &settings);
             Ok(Box::new(app))
         }),
     ) {
@@ -0,0 +1,70 @@
+use anyhow::{Result, bail};
+use serde_json::{Map, Value};
+
+use crate::event::{MacroEvent, MacroStats};
+
+/// 当前宏文件格式版本
+pub const CURRENT_FORMAT_VERSION: u32 = 2;
+
+/// 单步迁移: 把版本 N 的 JSON 对象原地升级到 N + 1
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// 迁移链, 下标 i 的函数负责版本 i -> i + 1
+const MIGRATIONS: [Migration; CURRENT_FORMAT_VERSION as usize] = [v0_to_v1, v1_to_v2];
+
+/// 读取文件格式版本, 没有 `format_version` 字段的旧文件视为版本 0
+pub fn format_version(obj: &Map<String, Value>) -> Result<u32> {
+    match obj.get("format_version") {
+        None | Some(Value::Null) => Ok(0),
+        Some(v) => match v.as_u64().and_then(|v| u32::try_from(v).ok()) {
+            Some(v) => Ok(v),
+            None => bail!("invalid format_version: {v}"),
+        },
+    }
+}
+
+/// 把任意旧版本的宏 JSON 升级到当前版本
+pub fn migrate(mut value: Value) -> Result<Value> {
+    let Some(obj) = value.as_object_mut() else {
+        bail!("macro file is not a JSON object");
+    };
+
+    let version = format_version(obj)?;
+    if version > CURRENT_FORMAT_VERSION {
+        bail!(
+            "macro format version {version} is newer than supported version {CURRENT_FORMAT_VERSION}"
+        );
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(obj)?;
+        obj.insert("format_version".to_string(), Value::from(from as u32 + 1));
+    }
+
+    Ok(value)
+}
+
+/// v0: 最初的格式, 只有 name / events / created_at
+fn v0_to_v1(obj: &mut Map<String, Value>) -> Result<()> {
+    if !obj.get("name").is_some_and(Value::is_string) {
+        bail!("missing field `name`");
+    }
+    if !obj.get("events").is_some_and(Value::is_array) {
+        bail!("missing field `events`");
+    }
+    obj.entry("created_at").or_insert(Value::from(0u64));
+    Ok(())
+}
+
+/// v2: 增加 updated_at / description / tags / author / screen 和缓存的事件统计
+fn v1_to_v2(obj: &mut Map<String, Value>) -> Result<()> {
+    let created_at = obj.get("created_at").cloned().unwrap_or(Value::from(0u64));
+    obj.entry("updated_at").or_insert(created_at);
+
+    let Some(events) = obj.get("events") else {
+        bail!("missing field `events`");
+    };
+    let events: Vec<MacroEvent> = serde_json::from_value(events.clone())?;
+    obj.insert("stats".to_string(), serde_json::to_value(MacroStats::from_events(&events))?);
+    Ok(())
+}
@@ -2,6 +2,7 @@ use anyhow::Result;
 use autopilot::mouse;
 use log::debug;
 use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
 use std::{
     sync::{
         Arc,
@@ -14,9 +15,12 @@ use std::{
 use crate::{event::*, key::*, macro_manager::SavedMacro};
 
 // 播放进度信息
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct PlaybackStatus {
     pub is_playing: bool,
+    /// 暂停中, 此时 `is_playing` 仍为 true
+    #[serde(default)]
+    pub is_paused: bool,
     pub current_repeat: u32,
     pub total_repeats: u32,
     pub current_macro_index: usize,
@@ -45,28 +49,85 @@ impl PlaybackStatus {
     }
 }
 
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct MacroPlayer {
     macros: Arc<Vec<Arc<SavedMacro>>>,
     is_playing: Arc<AtomicBool>,
+    is_paused: Arc<AtomicBool>,
     play_handle: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
     interval_ms: u64,
+    /// 播放速度倍数, 2.0 表示事件间隔减半
+    speed: f64,
     playback_status: Arc<RwLock<Arc<PlaybackStatus>>>,
 }
 
+impl Default for MacroPlayer {
+    fn default() -> Self {
+        Self::new(Vec::new(), 0)
+    }
+}
+
 impl MacroPlayer {
     pub fn new(macros: Vec<Arc<SavedMacro>>, interval_ms: u64) -> Self {
         Self {
             macros: Arc::new(macros),
             is_playing: Arc::new(AtomicBool::new(false)),
+            is_paused: Arc::new(AtomicBool::new(false)),
             play_handle: Arc::new(Mutex::new(None)),
             interval_ms,
+            speed: 1.0,
             playback_status: Arc::new(RwLock::new(PlaybackStatus::new_arc())),
         }
     }
 
+    /// 设置播放速度, 不是正数时保持原速
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        if speed.is_finite() && speed > 0.0 {
+            self.speed = speed;
+        }
+        self
+    }
+
+    /// 按播放速度缩放时长
+    fn scaled(&self, ms: u64) -> u64 {
+        (ms as f64 / self.speed).round() as u64
+    }
+
     pub fn get_playback_status(&self) -> Arc<PlaybackStatus> {
-        self.playback_status.read().clone()
+        let status = self.playback_status.read().clone();
+        if status.is_playing && self.is_paused() {
+            return Arc::new(PlaybackStatus {
+                is_paused: true,
+                ..PlaybackStatus::clone(&status)
+            });
+        }
+        status
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused.load(Ordering::Relaxed)
+    }
+
+    /// 暂停播放, 恢复后从暂停的位置继续
+    pub fn pause(&self) {
+        if self.is_playing() {
+            self.is_paused.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn resume(&self) {
+        self.is_paused.store(false, Ordering::Relaxed);
+    }
+
+    /// 暂停时等待恢复, 暂停期间被停止时返回 false
+    fn wait_while_paused(&self) -> bool {
+        while self.is_paused.load(Ordering::Relaxed) {
+            if !self.is_playing.load(Ordering::Relaxed) {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.is_playing.load(Ordering::Relaxed)
     }
 
     pub fn is_playing(&self) -> bool {
@@ -78,6 +139,7 @@ impl MacroPlayer {
             return;
         }
         self.is_playing.store(false, Ordering::Relaxed);
+        self.is_paused.store(false, Ordering::Relaxed);
 
         if let Some(_handle) = self.play_handle.lock().take() {
             // handle.abort();
@@ -91,6 +153,9 @@ impl MacroPlayer {
             return;
         }
 
+        // 先标记为播放中, 线程启动前查询状态也能看到
+        self.is_playing.store(true, Ordering::Relaxed);
+        *self.playback_status.write() = Arc::new(self.initial_status(repeat_count));
         let player = self.clone();
         let handle = thread::spawn(move || {
             if let Err(e) = player.play_async_with_repeat(repeat_count) {
@@ -101,15 +166,27 @@ impl MacroPlayer {
         *self.play_handle.lock() = Some(handle);
     }
 
-    fn play_async_with_repeat(&self, repeat_count: u32) -> Result<()> {
-        self.is_playing.store(true, Ordering::Relaxed);
+    /// 在当前线程播放, 全部播放完或被停止后返回
+    pub fn play(&self, repeat_count: u32) -> Result<()> {
+        if self.is_playing.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        self.play_async_with_repeat(repeat_count)
+    }
 
-        let mut status = PlaybackStatus {
+    fn initial_status(&self, repeat_count: u32) -> PlaybackStatus {
+        PlaybackStatus {
             is_playing: true,
             total_repeats: repeat_count,
             total_macros: self.macros.len(),
             ..Default::default()
-        };
+        }
+    }
+
+    fn play_async_with_repeat(&self, repeat_count: u32) -> Result<()> {
+        self.is_playing.store(true, Ordering::Relaxed);
+
+        let mut status = self.initial_status(repeat_count);
 
         for repeat in 1..=repeat_count {
             status.current_repeat = repeat;
@@ -131,7 +208,7 @@ impl MacroPlayer {
                         }
                     })
                     .sum::<u64>() as u128;
-                let total_time = total_time + total_delay;
+                let total_time = self.scaled((total_time + total_delay) as u64) as u128;
 
                 status.current_macro_index = macro_index;
                 status.current_macro_name = saved_macro.name.clone();
@@ -157,6 +234,7 @@ impl MacroPlayer {
         }
 
         self.is_playing.store(false, Ordering::Relaxed);
+        self.is_paused.store(false, Ordering::Relaxed);
         *self.playback_status.write() = PlaybackStatus::new_arc();
 
         Ok(())
@@ -166,12 +244,12 @@ impl MacroPlayer {
         let mut last_timestamp = 0u128;
 
         for event in &saved_macro.events {
-            if !self.is_playing.load(Ordering::Relaxed) {
+            if !self.wait_while_paused() {
                 break;
             }
             // 计算延时
             let delay = event.timestamp.saturating_sub(last_timestamp);
-            if !self.sleep_efficient(delay as u64) {
+            if !self.sleep_efficient(self.scaled(delay as u64)) {
                 break;
             }
 
@@ -211,7 +289,7 @@ impl MacroPlayer {
                     },
                 },
                 MacroEventType::Delay { duration_ms } => {
-                    if !self.sleep_efficient(*duration_ms) {
+                    if !self.sleep_efficient(self.scaled(*duration_ms)) {
                         break;
                     }
                 },
@@ -237,13 +315,22 @@ impl MacroPlayer {
         };
 
         let mut elapsed = Duration::from_millis(0);
+        // 暂停的时间不计入延时
+        let mut paused = Duration::from_millis(0);
         while elapsed < target_duration {
             let sleep_time = sleep_time.min((target_duration - elapsed).as_millis() as u64);
             thread::sleep(Duration::from_millis(sleep_time));
+            if self.is_paused() {
+                let pause_start = Instant::now();
+                if !self.wait_while_paused() {
+                    return false;
+                }
+                paused += pause_start.elapsed();
+            }
             if !self.is_playing.load(Ordering::Relaxed) {
                 return false;
             }
-            elapsed = start.elapsed();
+            elapsed = start.elapsed().saturating_sub(paused);
         }
 
         true
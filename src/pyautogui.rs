@@ -0,0 +1,58 @@
+use crate::{
+    event::{Button, MacroEventType, with_gaps},
+    key::{comment_text, pyautogui_key_name},
+    macro_manager::SavedMacro,
+};
+
+/// 把宏导出为使用 pyautogui 的 Python 脚本
+///
+/// 关闭 pyautogui 默认的操作间隔, 时间完全由 `time.sleep` 控制.
+/// pyautogui 不支持的键以注释的形式保留在脚本中.
+pub fn export(saved_macro: &SavedMacro) -> String {
+    let mut script =
+        format!("# Exported from mousepilot: {}\n", comment_text(&saved_macro.name));
+    if let Some(screen) = saved_macro.screen {
+        script.push_str(&format!("# Recorded on a {}x{} screen\n", screen.width, screen.height));
+    }
+    script.push_str("import time\n\nimport pyautogui\n\npyautogui.PAUSE = 0\n\n");
+
+    for (gap, event) in with_gaps(&saved_macro.events) {
+        if gap > 0 {
+            script.push_str(&sleep_line(gap));
+        }
+        let line = match &event.event_type {
+            MacroEventType::MouseMove { x, y } => format!("pyautogui.moveTo({x}, {y})\n"),
+            MacroEventType::MouseClick { button, pressed } => {
+                let button = match button {
+                    Button::Left => "left",
+                    Button::Middle => "middle",
+                    Button::Right => "right",
+                };
+                let action = if *pressed { "mouseDown" } else { "mouseUp" };
+                format!("pyautogui.{action}(button=\"{button}\")\n")
+            },
+            MacroEventType::KeyPress { key } | MacroEventType::KeyRelease { key } => {
+                let action = match event.event_type {
+                    MacroEventType::KeyPress { .. } => "keyDown",
+                    _ => "keyUp",
+                };
+                match pyautogui_key_name(key) {
+                    Some(name) => format!("pyautogui.{action}({})\n", quote(name)),
+                    None => format!("# unsupported key: {action} {}\n", comment_text(key)),
+                }
+            },
+            MacroEventType::Delay { duration_ms } => sleep_line(*duration_ms),
+        };
+        script.push_str(&line);
+    }
+    script
+}
+
+fn sleep_line(duration_ms: u64) -> String {
+    format!("time.sleep({}.{:03})\n", duration_ms / 1000, duration_ms % 1000)
+}
+
+/// Python 字符串字面量
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
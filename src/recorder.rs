@@ -11,7 +11,11 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::{event::*, hotkey::Shortcut};
+use crate::{
+    event::*,
+    hotkey::{self, Hotkey, KeyCombo, SharedShortcuts, Shortcut},
+    macro_manager::ScreenInfo,
+};
 
 #[derive(Debug, Clone)]
 pub struct MacroRecorder {
@@ -20,12 +24,14 @@ pub struct MacroRecorder {
     start_time: Arc<Mutex<Option<Instant>>>,
     // last_mouse_pos: Arc<Mutex<(i32, i32)>>,
     recording_task: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
-    shortcuts: Arc<Vec<Shortcut>>,
+    /// 和 [`crate::state::AppState`] 共用, 修改绑定后立即生效
+    shortcuts: SharedShortcuts,
     click_time: Arc<Mutex<Option<Instant>>>,
+    screen: Arc<Mutex<Option<ScreenInfo>>>,
 }
 
 impl MacroRecorder {
-    pub fn new(shortcuts: Arc<Vec<Shortcut>>) -> Self {
+    pub fn new(shortcuts: SharedShortcuts) -> Self {
         Self {
             events: Arc::new(Mutex::new(Vec::new())),
             is_recording: Arc::new(AtomicBool::new(false)),
@@ -34,6 +40,7 @@ impl MacroRecorder {
             recording_task: Arc::new(Mutex::new(None)),
             shortcuts,
             click_time: Arc::new(Mutex::new(None)),
+            screen: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -44,6 +51,7 @@ impl MacroRecorder {
 
         self.is_recording.store(true, Ordering::SeqCst);
         *self.start_time.lock() = Some(Instant::now());
+        *self.screen.lock() = Some(Self::current_screen());
         self.events.lock().clear();
 
         // 启动异步录制任务
@@ -63,9 +71,11 @@ impl MacroRecorder {
         let device_state = DeviceState::new();
         let mut last_mouse_state = MouseState::default();
         let mut last_keys = Vec::new();
+        let mut swallowed_buttons = Vec::new();
 
         while is_recording.load(Ordering::SeqCst) {
             thread::sleep(Duration::from_millis(10));
+            let keys = device_state.get_keys();
 
             // const MIN_DIST: i32 = 8;
             // let lastpos = *self.last_mouse_pos.lock();
@@ -81,13 +91,12 @@ impl MacroRecorder {
                 // if mouse_state.coords != last_mouse_state.coords {
                 //     self.add_mouse_move(mouse_state.coords.0, mouse_state.coords.1);
                 // }
-                for (i, pressed) in mouse_state.button_pressed.iter().enumerate() {
-                    if *pressed {
-                        self.add_mouse_click(Button::from(i), true);
-                    } else if *last_mouse_state.button_pressed.get(i).unwrap_or(&false) {
-                        self.add_mouse_click(Button::from(i), false);
-                    }
-                }
+                self.record_buttons(
+                    &last_mouse_state.button_pressed,
+                    &mouse_state.button_pressed,
+                    &keys,
+                    &mut swallowed_buttons,
+                );
             }
             // } else {
             //     let (cur_x, cur_y) = mouse_state.coords;
@@ -97,15 +106,15 @@ impl MacroRecorder {
             // }
 
             // 监听键盘事件
-            let keys = device_state.get_keys();
             if keys != last_keys {
+                // 快捷键的按下和松开都不录制, 松开时按键已不在 `keys` 中, 用之前的状态判断
                 for key in &keys {
-                    if !last_keys.contains(key) {
+                    if !last_keys.contains(key) && !self.is_hotkey(key, &keys) {
                         self.add_key_event(&key.to_string(), true);
                     }
                 }
                 for key in &last_keys {
-                    if !keys.contains(key) {
+                    if !keys.contains(key) && !self.is_hotkey(key, &last_keys) {
                         self.add_key_event(&key.to_string(), false);
                     }
                 }
@@ -140,10 +149,29 @@ impl MacroRecorder {
             .unwrap_or(0)
     }
 
+    /// 录制开始时的屏幕信息
+    pub fn get_screen_info(&self) -> Option<ScreenInfo> {
+        *self.screen.lock()
+    }
+
+    fn current_screen() -> ScreenInfo {
+        let size = autopilot::screen::size();
+        ScreenInfo {
+            width: size.width as u32,
+            height: size.height as u32,
+            scale: autopilot::screen::scale(),
+        }
+    }
+
     pub fn get_events(&self) -> Vec<MacroEvent> {
         self.events.lock().clone()
     }
 
+    /// 第 `start` 个之后的事件, 用于增量读取
+    pub fn get_events_since(&self, start: usize) -> Vec<MacroEvent> {
+        self.events.lock().get(start..).map(<[MacroEvent]>::to_vec).unwrap_or_default()
+    }
+
     pub fn get_event_count(&self) -> usize {
         self.events.lock().len()
     }
@@ -171,24 +199,53 @@ impl MacroRecorder {
         }
     }
 
-    fn is_hotkey(&self, keys: &[Keycode]) -> bool {
-        for shortcut in self.shortcuts.iter() {
-            if shortcut.matches_keycode(&shortcut.key, keys) {
-                return true;
-            }
-        }
-        false
+    /// `key` 和同时按下的 `keys` 是否是全局快捷键(或按键序列中的一步)
+    pub fn is_hotkey(&self, key: &Keycode, keys: &[Keycode]) -> bool {
+        let Some(key) = Shortcut::to_key(key) else {
+            return false;
+        };
+        let combo = KeyCombo::pressed(key, keys);
+        let shortcuts = self.shortcuts.read();
+        shortcuts.iter().any(|s| !s.is_ui && s.hotkey.steps().contains(&combo))
     }
 
-    pub fn add_key_event(&self, key: &str, pressed: bool) {
-        // 检查是否为快捷键
-        if let Ok(keycode) = key.parse::<Keycode>() {
-            let keys = vec![keycode];
-            if self.is_hotkey(&keys) {
-                return; // 跳过快捷键事件
+    /// 同时按下的鼠标按键和 `keys` 中的修饰键是否组成全局快捷键
+    pub fn is_mouse_hotkey(&self, buttons: &[Button], keys: &[Keycode]) -> bool {
+        let shortcuts = self.shortcuts.read();
+        shortcuts.iter().any(|s| match &s.hotkey {
+            Hotkey::Mouse(chord) => !s.is_ui && chord.matches(buttons, keys),
+            _ => false,
+        })
+    }
+
+    /// 录制鼠标按键从 `last` 到 `pressed` 的变化
+    ///
+    /// 组成快捷键时新按下的键不录制, 记在 `swallowed` 中, 松开时同样不录制;
+    /// 之前已经录制了按下的键松开照常录制, 避免播放时一直不松开.
+    pub fn record_buttons(
+        &self, last: &[bool], pressed: &[bool], keys: &[Keycode], swallowed: &mut Vec<usize>,
+    ) {
+        let buttons = hotkey::pressed_buttons(pressed);
+        let is_hotkey = self.is_mouse_hotkey(&buttons, keys);
+        for (i, &down) in pressed.iter().enumerate() {
+            let was_down = last.get(i).copied().unwrap_or(false);
+            if down && !was_down {
+                if is_hotkey {
+                    swallowed.push(i);
+                } else {
+                    self.add_mouse_click(Button::from(i), true);
+                }
+            } else if !down && was_down {
+                if let Some(pos) = swallowed.iter().position(|&b| b == i) {
+                    swallowed.remove(pos);
+                } else {
+                    self.add_mouse_click(Button::from(i), false);
+                }
             }
         }
+    }
 
+    pub fn add_key_event(&self, key: &str, pressed: bool) {
         let elapsed = self.get_time_elapsed();
         let event = MacroEvent {
             event_type: if pressed {
@@ -218,5 +275,6 @@ impl MacroRecorder {
         self.events.lock().clear();
         *self.start_time.lock() = None;
         *self.click_time.lock() = None;
+        *self.screen.lock() = None;
     }
 }
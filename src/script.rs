@@ -0,0 +1,55 @@
+use crate::{autohotkey, macro_manager::SavedMacro, pyautogui, xdotool};
+
+/// 可以导出的脚本格式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptFormat {
+    /// Linux X11 下的 bash + xdotool
+    #[default]
+    Xdotool,
+    /// Windows 下的 AutoHotkey v2
+    AutoHotkey,
+    /// 跨平台的 Python + pyautogui
+    Pyautogui,
+}
+
+impl ScriptFormat {
+    pub const ALL: [ScriptFormat; 3] =
+        [ScriptFormat::Xdotool, ScriptFormat::AutoHotkey, ScriptFormat::Pyautogui];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ScriptFormat::Xdotool => "xdotool (bash)",
+            ScriptFormat::AutoHotkey => "AutoHotkey v2",
+            ScriptFormat::Pyautogui => "pyautogui (Python)",
+        }
+    }
+
+    /// 命令行中使用的名称
+    pub fn name(self) -> &'static str {
+        match self {
+            ScriptFormat::Xdotool => "xdotool",
+            ScriptFormat::AutoHotkey => "ahk",
+            ScriptFormat::Pyautogui => "pyautogui",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ScriptFormat::Xdotool => "sh",
+            ScriptFormat::AutoHotkey => "ahk",
+            ScriptFormat::Pyautogui => "py",
+        }
+    }
+
+    pub fn export(self, saved_macro: &SavedMacro) -> String {
+        match self {
+            ScriptFormat::Xdotool => xdotool::export(saved_macro),
+            ScriptFormat::AutoHotkey => autohotkey::export(saved_macro),
+            ScriptFormat::Pyautogui => pyautogui::export(saved_macro),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hotkey::Hotkey,
+    storage::{self, StorageFormat},
+};
+
+/// 设置文件名, 位于应用数据目录下
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// 覆盖主宏库目录的环境变量
+pub const ENV_MACROS_DIR: &str = "MOUSEPILOT_MACROS_DIR";
+
+/// 额外挂载的宏库, 多个库用系统路径分隔符(Unix 为 `:`, Windows 为 `;`)隔开,
+/// 每一项的格式见 [`LibraryConfig::parse`]
+pub const ENV_LIBRARIES: &str = "MOUSEPILOT_LIBRARIES";
+
+/// 覆盖控制接口 socket 路径的环境变量
+pub const ENV_IPC_SOCKET: &str = "MOUSEPILOT_IPC_SOCKET";
+
+/// 额外挂载的宏库
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryConfig {
+    /// 库名称, 库中的宏以 `库名:宏名` 区分
+    pub name: String,
+    pub path: String,
+    /// 只读库不能保存、改名或删除宏
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl LibraryConfig {
+    /// 解析 `[name=]path[?ro]`, 省略名称时使用目录名
+    ///
+    /// 例如 `shared=/repo/macros?ro` 把 `/repo/macros` 以只读方式挂载为 `shared`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (spec, read_only) = match spec.strip_suffix("?ro") {
+            Some(spec) => (spec, true),
+            None => (spec, false),
+        };
+        let (name, path) = match spec.split_once('=') {
+            Some((name, path)) => (name.trim().to_string(), path.trim()),
+            None => {
+                let path = spec.trim();
+                let name = Path::new(path)
+                    .file_name()
+                    .map(|s| storage::slugify(&s.to_string_lossy()).replace(['.', ' '], "_"))
+                    .unwrap_or_default();
+                (name, path)
+            },
+        };
+        if path.is_empty() {
+            bail!("library path is empty: {spec}");
+        }
+
+        let config = Self {
+            name,
+            path: path.to_string(),
+            read_only,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 库名称只能包含字母、数字、`-` 和 `_`
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            bail!("library name is empty: {}", self.path);
+        }
+        if !self.name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+            bail!("invalid library name: {}", self.name);
+        }
+        Ok(())
+    }
+}
+
+/// HTTP 控制接口的默认端口
+pub const DEFAULT_HTTP_PORT: u16 = 7878;
+
+/// 本地 HTTP/WebSocket 控制接口, 只监听 127.0.0.1, 默认关闭
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpSettings {
+    pub enabled: bool,
+    pub port: u16,
+    /// 请求需要携带的令牌, 启用时为空会自动生成并写回设置文件
+    pub token: String,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_HTTP_PORT,
+            token: String::new(),
+        }
+    }
+}
+
+impl HttpSettings {
+    /// 用系统随机数生成 32 位十六进制的令牌
+    pub fn generate_token() -> Result<String> {
+        let mut bytes = [0u8; 16];
+        getrandom::fill(&mut bytes)
+            .map_err(|e| anyhow::anyhow!("failed to generate token: {e}"))?;
+        Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+    }
+}
+
+/// 应用设置, 保存在 `~/.mousepilot/settings.json`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// 主宏库目录, 未设置时为 `~/.mousepilot/macros`
+    pub macros_dir: Option<String>,
+    /// 额外挂载的宏库
+    pub libraries: Vec<LibraryConfig>,
+    /// 新宏的存储格式
+    pub storage_format: StorageFormat,
+    /// 控制接口的 Unix socket, 未设置时为 `~/.mousepilot/run/mousepilot.sock`;
+    /// 所在目录必须只有当前用户可以访问
+    pub ipc_socket: Option<String>,
+    /// HTTP 控制接口
+    pub http: HttpSettings,
+    /// 修改过的快捷键绑定, 键为快捷键名称, 没有列出的使用默认按键
+    pub shortcuts: BTreeMap<String, Hotkey>,
+    /// 同一个全局快捷键两次触发的最短间隔(毫秒), 为 0 时不限制
+    pub hotkey_debounce_ms: u64,
+}
+
+impl Settings {
+    /// 应用数据目录 `~/.mousepilot`, 没有主目录时回退到当前目录
+    pub fn app_dir() -> PathBuf {
+        dirs::home_dir().map(|home| home.join(".mousepilot")).unwrap_or_default()
+    }
+
+    pub fn path() -> PathBuf {
+        Self::app_dir().join(SETTINGS_FILE)
+    }
+
+    /// 读取设置文件, 文件不存在或无法解析时使用默认设置
+    pub fn load() -> Self {
+        Self::load_or_default(&Self::path())
+    }
+
+    /// 读取指定的设置文件, 文件不存在或无法解析时使用默认设置
+    pub fn load_or_default(path: &Path) -> Self {
+        if !path.exists() {
+            return Self::default();
+        }
+        Self::load_from(path).unwrap_or_else(|e| {
+            debug!("Failed to load settings {}: {e}", path.display());
+            Self::default()
+        })
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(fs::File::open(path)?))?)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::path())
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // 设置中有 HTTP 令牌, 只允许当前用户读取
+        storage::write_atomic_private(path, serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// 主宏库目录
+    pub fn macros_dir(&self) -> PathBuf {
+        match &self.macros_dir {
+            Some(dir) => PathBuf::from(dir),
+            None if dirs::home_dir().is_some() => Self::app_dir().join("macros"),
+            // 回退到当前目录
+            None => PathBuf::from("macros"),
+        }
+    }
+
+    /// 控制接口的 socket 路径
+    pub fn ipc_socket(&self) -> PathBuf {
+        match &self.ipc_socket {
+            Some(path) => PathBuf::from(path),
+            None => Self::app_dir().join("run").join("mousepilot.sock"),
+        }
+    }
+
+    /// 叠加环境变量中的配置, 返回新的设置, 不影响要写回文件的设置
+    pub fn with_env(mut self) -> Self {
+        if let Some(dir) = std::env::var_os(ENV_MACROS_DIR) {
+            self.macros_dir = Some(dir.to_string_lossy().to_string());
+        }
+        if let Some(path) = std::env::var_os(ENV_IPC_SOCKET) {
+            self.ipc_socket = Some(path.to_string_lossy().to_string());
+        }
+        if let Some(value) = std::env::var_os(ENV_LIBRARIES) {
+            for spec in std::env::split_paths(&value) {
+                match LibraryConfig::parse(&spec.to_string_lossy()) {
+                    Ok(config) => self.add_library(config),
+                    Err(e) => debug!("Ignoring {ENV_LIBRARIES} entry: {e}"),
+                }
+            }
+        }
+        self
+    }
+
+    /// 叠加命令行参数: `--macros-dir <path>`、`--storage-format <json|text|compact>`、
+    /// `--ipc-socket <path>` 和可重复的 `--library <spec>`; 未知参数报错
+    pub fn with_args<I>(self, args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.apply_args(args, true)
+    }
+
+    /// 打开窗口时的参数, 桌面启动器可能附加 `%U`、`-psn_…` 等参数, 未知参数只记录日志
+    pub fn with_gui_args<I>(self, args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.apply_args(args, false)
+    }
+
+    fn apply_args<I>(mut self, args: I, strict: bool) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value)),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || match inline {
+                Some(value) => Ok(value.to_string()),
+                None => args.next().ok_or_else(|| anyhow::anyhow!("{flag} requires a value")),
+            };
+            match flag.as_str() {
+                "--macros-dir" => self.macros_dir = Some(value()?),
+                "--library" => self.add_library(LibraryConfig::parse(&value()?)?),
+                "--ipc-socket" => self.ipc_socket = Some(value()?),
+                "--storage-format" => {
+                    let name = value()?;
+                    let Some(format) = StorageFormat::from_name(&name) else {
+                        bail!("unknown storage format: {name}");
+                    };
+                    self.storage_format = format;
+                },
+                _ if strict => bail!("unknown argument: {arg}"),
+                _ => debug!("Ignoring unknown argument: {arg}"),
+            }
+        }
+        Ok(self)
+    }
+
+    /// 添加宏库, 同名的库会被替换
+    pub fn add_library(&mut self, config: LibraryConfig) {
+        self.libraries.retain(|l| l.name != config.name);
+        self.libraries.push(config);
+    }
+}
@@ -1,13 +1,23 @@
-use std::{collections::BTreeSet, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
 use eframe::egui;
+use log::debug;
 use parking_lot::{Mutex, RwLock};
 
 use crate::{
-    hotkey::Shortcut,
-    macro_manager::MacroManager,
+    hotkey::{self, BindingError, Hotkey, SharedShortcuts, Shortcut},
+    macro_manager::{MacroError, MacroManager},
     player::{MacroPlayer, PlaybackStatus},
     recorder::MacroRecorder,
+    settings::Settings,
+    watcher::{DEFAULT_POLL_INTERVAL, MacroWatcher},
 };
 
 pub struct AppState {
@@ -17,30 +27,62 @@ pub struct AppState {
     pub repeat_count: Mutex<u32>,
     pub selected_macros: RwLock<BTreeSet<String>>,
     pub macro_interval_ms: Mutex<u64>,
-    pub shortcuts: Arc<Vec<Shortcut>>,
+    /// 当前的快捷键绑定, 读取用 [`AppState::shortcuts`]
+    pub shortcuts: SharedShortcuts,
+    /// 录入新快捷键时暂停全局快捷键
+    hotkeys_suspended: AtomicBool,
     pub ui_context: egui::Context,
     pub mouse_position: Mutex<(i32, i32)>,
+    /// 设置文件中的内容, 不含环境变量和命令行参数的覆盖, 修改后写回文件
+    pub settings: RwLock<Settings>,
+    settings_file: PathBuf,
+    /// 监视宏目录的外部修改
+    pub watcher: Mutex<MacroWatcher>,
 }
 
 impl AppState {
-    pub fn new(ctx: &egui::Context) -> Self {
-        let shortcuts = Self::init_shortcuts();
+    pub fn new(ctx: &egui::Context, settings: &Settings) -> Self {
+        let shortcuts = Arc::new(RwLock::new(Arc::new(Self::load_shortcuts(&settings.shortcuts))));
+        let macro_manager = MacroManager::from_settings(settings);
+        // 磁盘上的宏变化后刷新界面
+        let repaint_ctx = ctx.clone();
+        let watcher =
+            MacroWatcher::start(macro_manager.clone(), DEFAULT_POLL_INTERVAL, move |_| {
+                repaint_ctx.request_repaint();
+            });
         Self {
             player: Mutex::new(MacroPlayer::default()),
-            macro_manager: MacroManager::new(),
+            macro_manager,
             recorder: MacroRecorder::new(shortcuts.clone()),
             repeat_count: Mutex::new(1),
             selected_macros: Default::default(),
             macro_interval_ms: Mutex::new(0),
             shortcuts,
+            hotkeys_suspended: AtomicBool::new(false),
             ui_context: ctx.clone(),
             mouse_position: Mutex::new((0, 0)),
+            settings: RwLock::new(Settings::load()),
+            settings_file: Settings::path(),
+            watcher: Mutex::new(watcher),
         }
     }
 
-    fn init_shortcuts() -> Arc<Vec<Shortcut>> {
-        // 初始化快捷键
-        let shortcuts = vec![
+    /// 使用指定的设置文件, 默认为 [`Settings::path`]
+    pub fn with_settings_file(mut self, path: PathBuf) -> Self {
+        let settings = Settings::load_or_default(&path);
+        *self.shortcuts.write() = Arc::new(Self::load_shortcuts(&settings.shortcuts));
+        self.settings = RwLock::new(settings);
+        self.settings_file = path;
+        self
+    }
+
+    pub fn settings_file(&self) -> &Path {
+        &self.settings_file
+    }
+
+    /// 内置快捷键的默认绑定
+    pub fn default_shortcuts() -> Vec<Shortcut> {
+        vec![
             Shortcut::new("start_recording", egui::Key::F5, false, false, false, "开始录制", false),
             Shortcut::new("stop", egui::Key::F4, false, false, false, "停止录制/播放", false),
             Shortcut::new("play_once", egui::Key::F7, false, false, false, "播放一次", false),
@@ -65,8 +107,75 @@ impl AppState {
                 true,
             ),
             Shortcut::new("help", egui::Key::F1, false, false, false, "显示/隐藏帮助", true),
-        ];
-        Arc::new(shortcuts)
+        ]
+    }
+
+    /// 默认绑定叠加设置中的绑定, 未知、保留或冲突的绑定被忽略
+    pub fn load_shortcuts(bindings: &BTreeMap<String, Hotkey>) -> Vec<Shortcut> {
+        let mut shortcuts = Self::default_shortcuts();
+        for (name, hotkey) in bindings.iter() {
+            if let Err(e) = hotkey::check_binding(&shortcuts, name, hotkey) {
+                debug!("Ignoring shortcut binding {name} = {hotkey}: {e}");
+                continue;
+            }
+            if let Some(shortcut) = shortcuts.iter_mut().find(|s| s.name == *name) {
+                shortcut.hotkey = hotkey.clone();
+            }
+        }
+        shortcuts
+    }
+
+    /// 当前快捷键的快照
+    pub fn shortcuts(&self) -> Arc<Vec<Shortcut>> {
+        self.shortcuts.read().clone()
+    }
+
+    /// 修改快捷键并写回设置文件, 录制器和全局监听立即使用新的绑定
+    pub fn rebind_shortcut(&self, name: &str, hotkey: Hotkey) -> Result<(), BindingError> {
+        let mut shortcuts = self.shortcuts.write();
+        hotkey::check_binding(&shortcuts, name, &hotkey)?;
+        let hotkeys = self.macro_manager.macro_hotkeys();
+        if let Some((owner, _)) = hotkeys.iter().find(|(_, h)| h.conflicts_with(&hotkey)) {
+            return Err(BindingError::Conflict(format!("macro {owner}")));
+        }
+        let mut updated = Vec::clone(&shortcuts);
+        if let Some(shortcut) = updated.iter_mut().find(|s| s.name == name) {
+            shortcut.hotkey = hotkey.clone();
+        }
+        *shortcuts = Arc::new(updated);
+        drop(shortcuts);
+
+        // 和默认相同时不保存
+        let is_default =
+            Self::default_shortcuts().iter().any(|s| s.name == name && s.hotkey == hotkey);
+        let mut settings = self.settings.write();
+        if is_default {
+            settings.shortcuts.remove(name);
+        } else {
+            settings.shortcuts.insert(name.to_string(), hotkey);
+        }
+        if let Err(e) = settings.save_to(&self.settings_file) {
+            debug!("Failed to save settings: {e}");
+        }
+        Ok(())
+    }
+
+    /// 恢复所有默认快捷键
+    pub fn reset_shortcuts(&self) {
+        *self.shortcuts.write() = Arc::new(Self::default_shortcuts());
+        let mut settings = self.settings.write();
+        settings.shortcuts.clear();
+        if let Err(e) = settings.save_to(&self.settings_file) {
+            debug!("Failed to save settings: {e}");
+        }
+    }
+
+    pub fn hotkeys_suspended(&self) -> bool {
+        self.hotkeys_suspended.load(Ordering::SeqCst)
+    }
+
+    pub fn set_hotkeys_suspended(&self, suspended: bool) {
+        self.hotkeys_suspended.store(suspended, Ordering::SeqCst);
     }
 
     pub fn set_player(&self, player: MacroPlayer) {
@@ -81,6 +190,18 @@ impl AppState {
         self.player.lock().is_playing()
     }
 
+    pub fn pause_player(&self) {
+        self.player.lock().pause();
+    }
+
+    pub fn resume_player(&self) {
+        self.player.lock().resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.player.lock().is_paused()
+    }
+
     pub fn get_repeat_count(&self) -> u32 {
         *self.repeat_count.lock()
     }
@@ -160,6 +281,45 @@ impl AppState {
         self.set_player(player);
     }
 
+    /// 按顺序播放指定的宏, 正在播放的会先停止; 返回播放的宏数量
+    pub fn play_macros(
+        &self, names: &[String], repeat_count: u32, macro_interval_ms: u64, speed: f64,
+    ) -> Result<usize, MacroError> {
+        let macros = names
+            .iter()
+            .map(|name| self.macro_manager.get_macro(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.stop_player();
+        let count = macros.len();
+        let player = MacroPlayer::new(macros, macro_interval_ms).with_speed(speed);
+        player.start_playing(repeat_count);
+        self.set_player(player);
+        self.ui_repaint_after_secs(0.2);
+        Ok(count)
+    }
+
+    /// 检查宏快捷键是否与内置快捷键或其他宏冲突
+    pub fn check_macro_hotkey(&self, name: &str, hotkey: &Hotkey) -> Result<(), MacroError> {
+        self.check_shortcut_conflict(hotkey)?;
+        self.macro_manager.check_hotkey(name, hotkey)
+    }
+
+    fn check_shortcut_conflict(&self, hotkey: &Hotkey) -> Result<(), MacroError> {
+        match self.shortcuts().iter().find(|s| s.hotkey.conflicts_with(hotkey)) {
+            Some(s) => Err(MacroError::HotkeyConflict(format!("shortcut {}", s.name))),
+            None => Ok(()),
+        }
+    }
+
+    /// 设置或清除宏的快捷键, 冲突时不修改
+    pub fn set_macro_hotkey(&self, name: &str, hotkey: Option<Hotkey>) -> Result<(), MacroError> {
+        if let Some(hotkey) = &hotkey {
+            self.check_shortcut_conflict(hotkey)?;
+        }
+        // 与其他宏的冲突由 MacroManager 在写入时一并检查
+        self.macro_manager.set_hotkey(name, hotkey)
+    }
+
     pub fn ui_repaint_after_secs(&self, secs: f32) {
         self.ui_context.request_repaint_after_secs(secs);
     }
@@ -0,0 +1,205 @@
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// 宏文件的存储格式, 由扩展名区分
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageFormat {
+    /// 普通 JSON, `.json`
+    #[default]
+    Json,
+    /// 可手工编辑的文本格式, `.mpm`
+    Text,
+    /// 差分编码并 gzip 压缩, 适合很长的录制, `.mpz`
+    Compact,
+}
+
+impl StorageFormat {
+    pub const ALL: [StorageFormat; 3] =
+        [StorageFormat::Json, StorageFormat::Text, StorageFormat::Compact];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StorageFormat::Json => "JSON",
+            StorageFormat::Text => "文本",
+            StorageFormat::Compact => "压缩",
+        }
+    }
+
+    /// 设置文件和命令行中使用的名称
+    pub fn name(self) -> &'static str {
+        match self {
+            StorageFormat::Json => "json",
+            StorageFormat::Text => "text",
+            StorageFormat::Compact => "compact",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            StorageFormat::Json => "json",
+            StorageFormat::Text => "mpm",
+            StorageFormat::Compact => "mpz",
+        }
+    }
+
+    /// 由扩展名判断格式, 不是宏文件时返回 None
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::ALL.into_iter().find(|f| f.extension() == extension)
+    }
+}
+
+/// 宏名称的最大长度(字符数)
+pub const MAX_NAME_CHARS: usize = 128;
+
+/// 文件名主干的最大长度(字符数), 为后缀和扩展名留出余量
+const MAX_STEM_CHARS: usize = 64;
+
+/// Windows 保留的设备名, 不能作为文件名
+const RESERVED_STEMS: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// 校验宏的库内名称, 任何 Unicode 名称都可以, 但不能为空或包含控制字符
+///
+/// `:` 用来分隔库名前缀, 库内名称不能包含 `:`, 否则 `库名:名称` 会有歧义.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("name is empty".to_string());
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(format!("name is longer than {MAX_NAME_CHARS} characters"));
+    }
+    if name.chars().any(char::is_control) {
+        return Err("name contains control characters".to_string());
+    }
+    if name.contains(':') {
+        return Err("name contains ':'".to_string());
+    }
+    Ok(())
+}
+
+/// 由显示名称生成安全的文件名主干
+///
+/// 只保留字母数字和少量标点, 其余字符(包括路径分隔符和 `:`)替换为 `_`,
+/// 结果不会包含目录, 在所有平台上都是合法文件名. 不同名称可能得到相同的主干,
+/// 调用方需要自己处理冲突.
+pub fn slugify(name: &str) -> String {
+    let slug: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric()
+                || matches!(c, ' ' | '-' | '_' | '(' | ')' | '[' | ']' | '.' | ',' | '+' | '=')
+            {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_STEM_CHARS)
+        .collect();
+
+    // Windows 不允许以点或空格结尾, 开头的点会生成隐藏文件
+    let slug = slug.trim_matches(|c| c == '.' || c == ' ');
+    if slug.is_empty() {
+        return "macro".to_string();
+    }
+
+    let base = slug.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_STEMS.iter().any(|r| r.eq_ignore_ascii_case(base)) {
+        return format!("_{slug}");
+    }
+
+    slug.to_string()
+}
+
+/// 备份文件路径: `foo.json` -> `foo.json.bak`
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".bak");
+    path.with_file_name(file_name)
+}
+
+/// 原子写入文件
+///
+/// 先写入同目录下的临时文件并 fsync, 再 rename 覆盖目标文件. 写入过程中崩溃或磁盘写满
+/// 不会破坏已有文件. 目标文件已存在时, 旧内容保留为 `.bak`.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    write_atomic_with(path, contents, false)
+}
+
+/// 和 [`write_atomic`] 相同, 但文件和 `.bak` 只有当前用户可以读写(Unix 上为 0600),
+/// 用于保存令牌等敏感内容
+pub fn write_atomic_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    write_atomic_with(path, contents, true)
+}
+
+fn write_atomic_with(path: &Path, contents: &[u8], private: bool) -> io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(path.file_name().unwrap_or_default());
+    tmp_name.push(".tmp");
+    let tmp_path = dir.join(tmp_name);
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp_path)?;
+        if private {
+            restrict_permissions(&tmp_path)?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+
+        if path.exists() {
+            let backup = backup_path(path);
+            fs::copy(path, &backup)?;
+            // 复制会带上旧文件的权限
+            if private {
+                restrict_permissions(&backup)?;
+            }
+        }
+        fs::rename(&tmp_path, path)?;
+        sync_dir(dir)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// 只允许当前用户读写
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// 同步目录项, 保证 rename 落盘
+#[cfg(unix)]
+pub fn sync_dir(dir: &Path) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+pub fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use std::{
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    event::MacroStats,
+    macro_manager::{MacroError, SavedMacro, unix_now},
+    storage,
+};
+
+/// 回收站目录, 位于宏目录下, 以 `.` 开头所以不会被当作分组加载
+pub const TRASH_DIR: &str = ".trash";
+
+/// 默认保留天数
+pub const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+type Result<T, E = MacroError> = std::result::Result<T, E>;
+
+/// 回收站文件的内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrashFile {
+    deleted_at: u64,
+    /// 同一次删除操作的宏共享同一个批次, 撤销时一起恢复
+    batch: String,
+    /// 删除前所在的分组
+    group: String,
+    /// 宏数据, 读取时和普通宏文件一样经过迁移
+    #[serde(rename = "macro")]
+    saved_macro: Value,
+}
+
+/// 回收站里的一个宏
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    /// 回收站文件名主干
+    pub id: String,
+    pub name: String,
+    pub group: String,
+    pub deleted_at: u64,
+    pub batch: String,
+    pub stats: MacroStats,
+}
+
+/// 被删除的宏先移到这里, 超过保留期后才真正删除
+#[derive(Debug, Clone)]
+pub struct Trash {
+    dir: PathBuf,
+    retention_days: Arc<AtomicU64>,
+}
+
+impl Trash {
+    pub fn new(storage_path: &Path) -> Self {
+        Self {
+            dir: storage_path.join(TRASH_DIR),
+            retention_days: Arc::new(AtomicU64::new(DEFAULT_RETENTION_DAYS)),
+        }
+    }
+
+    pub fn retention_days(&self) -> u64 {
+        self.retention_days.load(Ordering::Relaxed)
+    }
+
+    /// 设置保留天数, 0 表示永久保留
+    pub fn set_retention_days(&self, days: u64) {
+        self.retention_days.store(days, Ordering::Relaxed);
+    }
+
+    /// 放入回收站, 返回条目 ID
+    pub fn put(&self, batch: &str, group: &str, saved_macro: &SavedMacro) -> Result<String> {
+        fs::create_dir_all(&self.dir)?;
+        let slug = storage::slugify(&saved_macro.name);
+        let mut id = format!("{batch}-{slug}");
+        let mut n = 2;
+        while self.path(&id).exists() {
+            id = format!("{batch}-{slug}~{n}");
+            n += 1;
+        }
+
+        let file = TrashFile {
+            deleted_at: unix_now(),
+            batch: batch.to_string(),
+            group: group.to_string(),
+            saved_macro: serde_json::to_value(saved_macro)?,
+        };
+        storage::write_atomic(&self.path(&id), serde_json::to_string(&file)?.as_bytes())?;
+        Ok(id)
+    }
+
+    /// 回收站里的所有宏, 最近删除的在前
+    pub fn list(&self) -> Result<Vec<TrashEntry>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(id) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+            if let Ok((file, saved_macro)) = self.read(&id) {
+                entries.push(TrashEntry {
+                    id,
+                    name: saved_macro.name,
+                    group: file.group,
+                    deleted_at: file.deleted_at,
+                    batch: file.batch,
+                    stats: saved_macro.stats,
+                });
+            }
+        }
+        entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(entries)
+    }
+
+    /// 读取条目, 返回宏数据和删除前的分组
+    pub fn load(&self, id: &str) -> Result<(SavedMacro, String)> {
+        let (file, saved_macro) = self.read(id)?;
+        Ok((saved_macro, file.group))
+    }
+
+    /// 永久删除条目
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let path = self.path(id);
+        if !path.exists() {
+            return Err(MacroError::NotFound(id.to_string()));
+        }
+        fs::remove_file(&path)?;
+        let _ = fs::remove_file(storage::backup_path(&path));
+        Ok(())
+    }
+
+    /// 删除超过保留期的条目, 返回删除的数量
+    pub fn purge_expired(&self) -> Result<usize> {
+        let days = self.retention_days();
+        if days == 0 {
+            return Ok(0);
+        }
+
+        let cutoff = unix_now().saturating_sub(days.saturating_mul(86_400));
+        let mut purged = 0;
+        for entry in self.list()? {
+            if entry.deleted_at < cutoff {
+                self.remove(&entry.id)?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    fn read(&self, id: &str) -> Result<(TrashFile, SavedMacro)> {
+        let path = self.path(id);
+        if !path.exists() {
+            return Err(MacroError::NotFound(id.to_string()));
+        }
+        let file: TrashFile = serde_json::from_reader(BufReader::new(fs::File::open(path)?))?;
+        let saved_macro = SavedMacro::from_value(file.saved_macro.clone())?;
+        Ok((file, saved_macro))
+    }
+}
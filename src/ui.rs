@@ -1,42 +1,150 @@
 use eframe::egui;
 use log::debug;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use crate::bundle::{BUNDLE_EXTENSION, Bundle};
+use crate::event::Button;
+use crate::filter::{MacroFilter, SortOrder};
+use crate::history::RevisionDiff;
+use crate::http::HttpServer;
 use crate::hotkey::*;
+use crate::macro_manager::{ConflictPolicy, MacroError, MacroManager, SavedMacro, unix_now};
+use crate::script::ScriptFormat;
+use crate::settings::{HttpSettings, LibraryConfig, Settings};
 use crate::state::AppState;
+use crate::storage::StorageFormat;
+use crate::xdotool;
+
+/// 等待用户处理的名称冲突
+enum PendingConflict {
+    Save { saved_macro: Box<SavedMacro>, from_recording: bool },
+    Rename { old_name: String, new_name: String },
+}
+
+/// 宏信息编辑框的草稿
+struct MacroInfoDraft {
+    name: String,
+    description: String,
+    tags: String,
+    author: String,
+    /// 全局快捷键, 如 `Ctrl+F9`, 为空表示不设置
+    hotkey: String,
+    /// 保存失败的原因, 窗口保持打开
+    error: Option<String>,
+}
 
 pub struct App {
     state: Arc<AppState>,
     ui_has_focus: bool,
     editing_macro_name: Option<String>,
     new_macro_name: String,
-    deleting_macro: Option<String>,
+    deleting_macros: Option<Vec<String>>,
+    /// 最近一次删除的数量, 大于 0 时显示撤销按钮
+    undo_delete_count: usize,
+    show_trash: bool,
+    show_libraries: bool,
+    new_library: LibraryConfig,
+    show_bundle: bool,
+    bundle_path: String,
+    /// 导入到的分组
+    bundle_group: String,
+    bundle_policy: ConflictPolicy,
+    /// 最近一次导入或导出的结果
+    bundle_message: Option<String>,
+    script_path: String,
+    script_format: ScriptFormat,
     show_shortcuts_help: bool,
+    /// 正在录入新按键的快捷键
+    capturing_shortcut: Option<String>,
+    /// 录入中依次按下的组合
+    captured_steps: Vec<KeyCombo>,
+    /// 录入中同时按下的鼠标按键, 和 `captured_steps` 只保留后按下的一个
+    captured_mouse: Option<MouseChord>,
+    /// 最近一次修改快捷键的结果
+    shortcut_message: Option<String>,
     // 全局快捷键相关
     global_listener: Option<GlobalHotkeyListener>,
+    /// 本地控制接口, socket 被占用时为 None
+    #[cfg(unix)]
+    _ipc_server: Option<crate::ipc::IpcServer>,
+    /// HTTP 控制接口, 未启用时为 None
+    _http_server: Option<HttpServer>,
     // 延时宏相关
     delay_macro_ms: u64,
     delay_macro_name: String,
+    pending_conflict: Option<PendingConflict>,
+    /// 保存或重命名失败的原因, 弹窗显示
+    save_error: Option<String>,
+    editing_info: Option<MacroInfoDraft>,
+    new_group_name: String,
+    moving_macro: Option<String>,
+    viewing_history: Option<String>,
+    filter: MacroFilter,
+    /// 只显示最近 N 天修改过的宏
+    filter_days: Option<u64>,
+    show_filter_options: bool,
 }
 
 impl App {
-    pub fn new(ctx: &egui::Context) -> Self {
-        let state = Arc::new(AppState::new(ctx));
+    pub fn new(ctx: &egui::Context, settings: &Settings) -> Self {
+        let state = Arc::new(AppState::new(ctx, settings));
 
         // 创建全局快捷键监听器
         let global_listener = GlobalHotkeyListener::new();
 
+        #[cfg(unix)]
+        let ipc_server = match crate::ipc::IpcServer::start(state.clone(), &settings.ipc_socket()) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                debug!("控制接口启动失败: {e}");
+                None
+            },
+        };
+
         let app = Self {
             state: state.clone(),
             ui_has_focus: false,
             editing_macro_name: None,
             new_macro_name: String::new(),
-            deleting_macro: None,
+            deleting_macros: None,
+            undo_delete_count: 0,
+            show_trash: false,
+            show_libraries: false,
+            new_library: LibraryConfig {
+                name: String::new(),
+                path: String::new(),
+                read_only: false,
+            },
+            show_bundle: false,
+            bundle_path: format!("macros.{BUNDLE_EXTENSION}"),
+            bundle_group: String::new(),
+            bundle_policy: ConflictPolicy::Error,
+            bundle_message: None,
+            script_path: String::from("macro.sh"),
+            script_format: ScriptFormat::default(),
             show_shortcuts_help: false,
+            capturing_shortcut: None,
+            captured_steps: Vec::new(),
+            captured_mouse: None,
+            shortcut_message: None,
             global_listener: Some(global_listener),
+            #[cfg(unix)]
+            _ipc_server: ipc_server,
+            _http_server: Self::start_http_server(&state, settings),
             delay_macro_ms: 1000,
             delay_macro_name: String::from("延时宏"),
+            pending_conflict: None,
+            save_error: None,
+            editing_info: None,
+            new_group_name: String::new(),
+            moving_macro: None,
+            viewing_history: None,
+            filter: MacroFilter::default(),
+            filter_days: None,
+            show_filter_options: false,
         };
 
         // 启动全局快捷键监听
@@ -47,6 +155,138 @@ impl App {
         app
     }
 
+    /// 启用时启动 HTTP 控制接口, 没有令牌时生成一个并写回设置文件
+    fn start_http_server(state: &Arc<AppState>, settings: &Settings) -> Option<HttpServer> {
+        if !settings.http.enabled {
+            return None;
+        }
+        let mut config = settings.http.clone();
+        if config.token.is_empty() {
+            config.token = match HttpSettings::generate_token() {
+                Ok(token) => token,
+                Err(e) => {
+                    debug!("HTTP 控制接口启动失败: {e}");
+                    return None;
+                },
+            };
+            let mut file_settings = state.settings.write();
+            file_settings.http.token = config.token.clone();
+            if let Err(e) = file_settings.save_to(state.settings_file()) {
+                debug!("Failed to save settings: {e}");
+            }
+        }
+        match HttpServer::start(state.clone(), &config) {
+            Ok(server) => {
+                debug!("HTTP 控制接口: http://{}", server.local_addr());
+                Some(server)
+            },
+            Err(e) => {
+                debug!("HTTP 控制接口启动失败: {e}");
+                None
+            },
+        }
+    }
+
+    /// 按钮上显示的快捷键
+    fn shortcut_text(&self, name: &str) -> String {
+        self.state
+            .shortcuts()
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| s.display_text())
+            .unwrap_or_default()
+    }
+
+    /// 录入新快捷键: 依次按下的组合组成序列, 同一组合按两次为双击;
+    /// 同时按下多个鼠标按键或带修饰键点击为鼠标快捷键. Esc 取消
+    fn capture_shortcut(&mut self, ctx: &egui::Context) {
+        let (presses, chord) = ctx.input(|i| {
+            let presses: Vec<KeyCombo> = i
+                .events
+                .iter()
+                .filter_map(|event| match event {
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        repeat: false,
+                        modifiers,
+                        ..
+                    } => Some(KeyCombo {
+                        key: *key,
+                        ctrl: modifiers.ctrl,
+                        shift: modifiers.shift,
+                        alt: modifiers.alt,
+                        meta: modifiers.mac_cmd,
+                    }),
+                    _ => None,
+                })
+                .collect();
+            let buttons = [
+                (egui::PointerButton::Primary, Button::Left),
+                (egui::PointerButton::Middle, Button::Middle),
+                (egui::PointerButton::Secondary, Button::Right),
+            ]
+            .into_iter()
+            .filter(|(pointer, _)| i.pointer.button_down(*pointer))
+            .map(|(_, button)| button)
+            .collect();
+            let mut chord = MouseChord::new(buttons);
+            chord.ctrl = i.modifiers.ctrl;
+            chord.shift = i.modifiers.shift;
+            chord.alt = i.modifiers.alt;
+            chord.meta = i.modifiers.mac_cmd;
+            // 不带修饰键的单击用来点按钮, 不作为快捷键
+            (presses, (!chord.buttons.is_empty() && !chord.is_reserved()).then_some(chord))
+        });
+        for combo in presses {
+            if combo.key == egui::Key::Escape {
+                self.finish_capture();
+                return;
+            }
+            if self.captured_steps.len() == MAX_SEQUENCE_LEN {
+                self.captured_steps.remove(0);
+            }
+            self.captured_steps.push(combo);
+            self.captured_mouse = None;
+        }
+        if let Some(chord) = chord {
+            self.captured_steps.clear();
+            self.captured_mouse = Some(chord);
+        }
+    }
+
+    /// 已录入的快捷键
+    fn captured_hotkey(&self) -> Option<Hotkey> {
+        if let Some(chord) = &self.captured_mouse {
+            return Some(Hotkey::Mouse(chord.clone()));
+        }
+        match self.captured_steps.as_slice() {
+            [] => None,
+            [combo] => Some(Hotkey::Key(*combo)),
+            [first, second] if first == second => Some(Hotkey::DoubleTap(*first)),
+            steps => Some(Hotkey::Sequence(steps.to_vec())),
+        }
+    }
+
+    /// 结束录入, 恢复全局快捷键, 返回正在修改的快捷键名称
+    fn finish_capture(&mut self) -> Option<String> {
+        self.captured_steps.clear();
+        self.captured_mouse = None;
+        self.state.set_hotkeys_suspended(false);
+        self.capturing_shortcut.take()
+    }
+
+    fn apply_captured_shortcut(&mut self) {
+        let hotkey = self.captured_hotkey();
+        let (Some(name), Some(hotkey)) = (self.finish_capture(), hotkey) else {
+            return;
+        };
+        self.shortcut_message = Some(match self.state.rebind_shortcut(&name, hotkey.clone()) {
+            Ok(()) => format!("已设置为 {hotkey}"),
+            Err(e) => format!("无法设置 {hotkey}: {e}"),
+        });
+    }
+
     // 执行快捷键动作 - 保留用于UI内快捷键
     fn execute_shortcut(&mut self, shortcut_name: &str) {
         debug!("执行UI内快捷键: {shortcut_name}");
@@ -76,12 +316,17 @@ impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.ui_has_focus = ctx.input(|i| i.focused);
 
-        // UI 内快捷键
-        if self.ui_has_focus {
+        if self.capturing_shortcut.is_some() {
+            self.capture_shortcut(ctx);
+        } else if self.ui_has_focus {
+            // UI 内快捷键
             let mut shortcut_to_execute = None;
             ctx.input(|i| {
-                for shortcut in self.state.shortcuts.iter() {
-                    if i.key_pressed(shortcut.key) && shortcut.matches(shortcut.key, &i.modifiers) {
+                for shortcut in self.state.shortcuts().iter() {
+                    let Hotkey::Key(combo) = &shortcut.hotkey else {
+                        continue;
+                    };
+                    if i.key_pressed(combo.key) && shortcut.matches(combo.key, &i.modifiers) {
                         shortcut_to_execute = Some(shortcut.name.clone());
                     }
                 }
@@ -112,27 +357,75 @@ impl eframe::App for App {
         });
 
         // 删除确认对话框
-        if let Some(macro_name) = &self.deleting_macro {
-            match self.render_confirm_panel(
-                ctx,
-                "确认删除",
-                &format!("确定要删除<{macro_name}>吗？"),
-            ) {
+        if let Some(names) = &self.deleting_macros {
+            let message = match names.as_slice() {
+                [name] => format!("确定要把<{name}>移到回收站吗？"),
+                names => format!("确定要把选中的 {} 个宏移到回收站吗？", names.len()),
+            };
+            match self.render_confirm_panel(ctx, "确认删除", &message) {
                 Some(true) => {
-                    if let Err(e) = self.state.macro_manager.delete_macro(macro_name) {
-                        debug!("Failed to delete macro: {e}");
+                    match self.state.macro_manager.delete_macros(names) {
+                        Ok(count) => self.undo_delete_count = count,
+                        Err(e) => debug!("Failed to delete macro: {e}"),
                     }
                     // 删除后同步移除选中状态
-                    self.state.clear_selected_macros();
-                    self.deleting_macro = None;
+                    for name in names {
+                        self.state.remove_selected_macros(name);
+                    }
+                    self.deleting_macros = None;
                 },
                 Some(false) => {
-                    self.deleting_macro = None;
+                    self.deleting_macros = None;
                 },
                 None => {},
             }
         }
 
+        // 名称冲突对话框
+        if self.pending_conflict.is_some() {
+            self.render_conflict_panel(ctx);
+        }
+        if let Some(message) = &self.save_error
+            && self.render_error_panel(ctx, message)
+        {
+            self.save_error = None;
+        }
+
+        // 移动到分组
+        if self.moving_macro.is_some() {
+            self.render_move_panel(ctx);
+        }
+
+        // 宏库
+        if self.show_libraries {
+            self.render_libraries_panel(ctx);
+        }
+
+        // 导入导出
+        if self.show_bundle {
+            self.render_bundle_panel(ctx);
+        }
+
+        // 回收站
+        if self.show_trash {
+            self.render_trash_panel(ctx);
+        }
+
+        // 历史版本
+        if self.viewing_history.is_some() {
+            self.render_history_panel(ctx);
+        }
+
+        // 宏信息编辑
+        if self.editing_info.is_some() {
+            self.render_info_panel(ctx);
+        }
+
+        // 加载失败报告
+        if self.state.macro_manager.get_load_report().has_failures() {
+            self.render_load_report_panel(ctx);
+        }
+
         // 快捷键帮助窗口
         if self.show_shortcuts_help {
             self.render_help_panel(ctx);
@@ -141,6 +434,7 @@ impl eframe::App for App {
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
         self.state.recorder.stop_recording();
+        self.state.watcher.lock().stop();
 
         // 停止全局快捷键监听
         if let Some(listener) = &self.global_listener {
@@ -172,69 +466,334 @@ impl App {
         result
     }
 
+    /// 错误提示面板, 点击确定后返回 true
+    fn render_error_panel(&self, ctx: &egui::Context, message: &str) -> bool {
+        let mut closed = false;
+        egui::Window::new("操作失败").collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.label(message);
+            if ui.button("✅ 确定").clicked() {
+                closed = true;
+            }
+        });
+        closed
+    }
+
     fn render_macro_list(&mut self, ui: &mut egui::Ui) {
-        ui.label("宏列表");
+        ui.horizontal(|ui| {
+            ui.label("宏列表");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.toggle_value(&mut self.show_trash, "🗑 回收站");
+                ui.toggle_value(&mut self.show_libraries, "📚 宏库");
+                ui.toggle_value(&mut self.show_bundle, "📦 导入导出");
+            });
+        });
         ui.separator();
 
+        // 撤销删除
+        if self.undo_delete_count > 0 {
+            ui.horizontal(|ui| {
+                ui.label(format!("已删除 {} 个宏", self.undo_delete_count));
+                if ui.button("↩ 撤销").clicked() {
+                    match self.state.macro_manager.undo_delete() {
+                        Ok(names) => {
+                            for name in names {
+                                self.state.add_selected_macros(&name);
+                            }
+                        },
+                        Err(e) => debug!("Failed to undo delete: {e}"),
+                    }
+                    self.undo_delete_count = 0;
+                }
+                if ui.small_button("✖").clicked() {
+                    self.undo_delete_count = 0;
+                }
+            });
+            ui.separator();
+        }
+
+        // 新建分组
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_group_name)
+                    .hint_text("新分组, 可用 / 嵌套")
+                    .desired_width(ui.available_width() - 30.0),
+            );
+            if ui.button("➕").on_hover_text("新建分组").clicked() {
+                match self.state.macro_manager.create_group(&self.new_group_name) {
+                    Ok(_) => self.new_group_name.clear(),
+                    Err(e) => debug!("Failed to create group: {e}"),
+                }
+            }
+        });
+        self.render_filter_bar(ui);
+        ui.separator();
+
+        // 有筛选条件时平铺显示结果
+        if self.filter.is_active() {
+            let results = self.filter.apply(self.state.macro_manager.get_all_macros());
+            ui.horizontal(|ui| {
+                ui.label(format!("找到 {} 个宏", results.len()));
+                if ui.button("☑ 全选结果").clicked() {
+                    for macro_data in results.iter() {
+                        self.state.add_selected_macros(&macro_data.name);
+                    }
+                }
+            });
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for macro_data in results.iter() {
+                    self.render_macro_row(ui, macro_data);
+                }
+            });
+            return;
+        }
+
+        // 按分组整理宏
+        let manager = &self.state.macro_manager;
+        let mut grouped: BTreeMap<String, Vec<Arc<SavedMacro>>> = BTreeMap::new();
+        for macro_data in self.filter.apply(manager.get_all_macros()) {
+            grouped
+                .entry(manager.get_macro_group(&macro_data.name))
+                .or_default()
+                .push(macro_data);
+        }
+        let groups = manager.get_groups();
+
         // 宏列表
         egui::ScrollArea::vertical().show(ui, |ui| {
-            let macros = self.state.macro_manager.get_all_macros();
-            // macros.sort_by(|a, b| a.name.cmp(&b.name));
+            self.render_group(ui, "", &groups, &grouped);
+        });
+    }
 
-            for macro_data in macros.iter() {
-                ui.horizontal(|ui| {
-                    let mut is_selected = self.state.is_selected(&macro_data.name);
+    /// 搜索框、筛选条件和排序方式
+    fn render_filter_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.filter.query)
+                    .hint_text("🔍 搜索名称/标签/描述")
+                    .desired_width(ui.available_width() - 30.0),
+            );
+            ui.toggle_value(&mut self.show_filter_options, "⚙").on_hover_text("筛选和排序");
+        });
 
-                    if ui.checkbox(&mut is_selected, "").clicked() {
-                        if is_selected {
-                            self.state.add_selected_macros(&macro_data.name);
-                        } else {
-                            self.state.remove_selected_macros(&macro_data.name);
-                        }
+        if !self.show_filter_options {
+            return;
+        }
+
+        egui::Grid::new("macro_filter").num_columns(2).show(ui, |ui| {
+            ui.label("时长(秒):");
+            ui.horizontal(|ui| {
+                duration_bound_ui(ui, &mut self.filter.min_duration_ms);
+                ui.label("~");
+                duration_bound_ui(ui, &mut self.filter.max_duration_ms);
+            });
+            ui.end_row();
+
+            ui.label("包含:");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.filter.has_keyboard, "键盘");
+                ui.checkbox(&mut self.filter.has_clicks, "点击");
+            });
+            ui.end_row();
+
+            ui.label("修改于:");
+            egui::ComboBox::from_id_salt("macro_filter_date")
+                .selected_text(date_range_label(self.filter_days))
+                .show_ui(ui, |ui| {
+                    for days in [None, Some(1), Some(7), Some(30)] {
+                        ui.selectable_value(&mut self.filter_days, days, date_range_label(days));
+                    }
+                });
+            ui.end_row();
+
+            ui.label("排序:");
+            egui::ComboBox::from_id_salt("macro_filter_sort")
+                .selected_text(self.filter.sort.label())
+                .show_ui(ui, |ui| {
+                    for order in SortOrder::ALL {
+                        ui.selectable_value(&mut self.filter.sort, order, order.label());
                     }
+                });
+            ui.end_row();
+        });
 
-                    ui.label(&macro_data.name);
+        self.filter.updated_after =
+            self.filter_days.map(|days| unix_now().saturating_sub(days * 24 * 60 * 60));
 
+        if self.filter.is_active() && ui.button("清除筛选").clicked() {
+            self.filter = MacroFilter {
+                sort: self.filter.sort,
+                ..Default::default()
+            };
+            self.filter_days = None;
+        }
+    }
+
+    /// 渲染一个分组: 先是子分组, 再是分组内的宏
+    fn render_group(
+        &mut self, ui: &mut egui::Ui, group: &str, groups: &[String],
+        grouped: &BTreeMap<String, Vec<Arc<SavedMacro>>>,
+    ) {
+        for child in groups.iter().filter(|g| MacroManager::parent_group(g) == Some(group)) {
+            // 其他库的根分组显示为库名
+            let title = match child.strip_suffix(':') {
+                Some(library) if self.state.macro_manager.is_read_only(child) => {
+                    format!("📚 {library} 🔒")
+                },
+                Some(library) => format!("📚 {library}"),
+                None => format!("📂 {}", child.rsplit(['/', ':']).next().unwrap_or(child)),
+            };
+            let id = ui.make_persistent_id(("macro_group", child));
+            egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                .show_header(ui, |ui| {
+                    ui.label(title);
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        if ui.button("📝").clicked() {
-                            self.editing_macro_name = Some(macro_data.name.clone());
-                            self.new_macro_name = macro_data.name.clone();
+                        if ui.button("☑").on_hover_text("选中分组内所有宏").clicked() {
+                            let names = self.state.macro_manager.get_macro_names_in_group(child);
+                            for name in names {
+                                self.state.add_selected_macros(&name);
+                            }
                         }
+                    });
+                })
+                .body(|ui| self.render_group(ui, child, groups, grouped));
+        }
 
-                        if ui.button("🗑").clicked() {
-                            self.deleting_macro = Some(macro_data.name.clone());
-                        }
+        for macro_data in grouped.get(group).into_iter().flatten() {
+            self.render_macro_row(ui, macro_data);
+        }
+    }
+
+    fn render_macro_row(&mut self, ui: &mut egui::Ui, macro_data: &SavedMacro) {
+        ui.horizontal(|ui| {
+            let mut is_selected = self.state.is_selected(&macro_data.name);
+
+            if ui.checkbox(&mut is_selected, "").clicked() {
+                if is_selected {
+                    self.state.add_selected_macros(&macro_data.name);
+                } else {
+                    self.state.remove_selected_macros(&macro_data.name);
+                }
+            }
+
+            ui.label(&macro_data.name).on_hover_ui(|ui| macro_details_ui(ui, macro_data));
+            if let Some(hotkey) = &macro_data.hotkey {
+                ui.weak(hotkey.to_string());
+            }
+
+            // 只读库中的宏只能查看和播放
+            let writable = !self.state.macro_manager.is_read_only(&macro_data.name);
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .add_enabled(writable, egui::Button::new("ℹ"))
+                    .on_hover_text("编辑信息")
+                    .clicked()
+                {
+                    self.editing_info = Some(MacroInfoDraft {
+                        name: macro_data.name.clone(),
+                        description: macro_data.description.clone(),
+                        tags: macro_data.tags.join(", "),
+                        author: macro_data.author.clone(),
+                        hotkey: macro_data
+                            .hotkey
+                            .as_ref()
+                            .map(|h| h.to_string())
+                            .unwrap_or_default(),
+                        error: None,
                     });
-                });
+                }
 
-                // 重命名编辑框
-                if let Some(editing_name) = &self.editing_macro_name {
-                    if editing_name == &macro_data.name {
-                        let old_name = editing_name.clone();
-                        ui.horizontal(|ui| {
-                            ui.add(
-                                egui::TextEdit::singleline(&mut self.new_macro_name)
-                                    .desired_width(ui.available_width() - 67.0),
-                            );
-                            if ui.button("✅").clicked() {
-                                let new_name = self.new_macro_name.clone();
-                                if !new_name.is_empty() && new_name != old_name {
-                                    if let Err(e) =
-                                        self.state.macro_manager.rename_macro(&old_name, &new_name)
-                                    {
-                                        debug!("Failed to rename macro: {e}");
-                                    }
-                                }
-                                self.editing_macro_name = None;
-                            }
-                            if ui.button("❌").clicked() {
-                                self.editing_macro_name = None;
-                            }
-                        });
+                if ui.button("🕘").on_hover_text("历史版本").clicked() {
+                    self.viewing_history = Some(macro_data.name.clone());
+                }
+
+                if ui
+                    .add_enabled(writable, egui::Button::new("📁"))
+                    .on_hover_text("移动到分组")
+                    .clicked()
+                {
+                    self.moving_macro = Some(macro_data.name.clone());
+                }
+
+                if ui.add_enabled(writable, egui::Button::new("📝")).clicked() {
+                    self.editing_macro_name = Some(macro_data.name.clone());
+                    self.new_macro_name = macro_data.name.clone();
+                }
+
+                if ui.add_enabled(writable, egui::Button::new("🗑")).clicked() {
+                    self.deleting_macros = Some(vec![macro_data.name.clone()]);
+                }
+            });
+        });
+        ui.label(egui::RichText::new(macro_summary(macro_data)).small().weak());
+
+        // 重命名编辑框
+        if let Some(editing_name) = &self.editing_macro_name
+            && editing_name == &macro_data.name
+        {
+            let old_name = editing_name.clone();
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.new_macro_name)
+                        .desired_width(ui.available_width() - 67.0),
+                );
+                if ui.button("✅").clicked() {
+                    let new_name = self.new_macro_name.clone();
+                    if !new_name.is_empty() && new_name != old_name {
+                        self.rename_macro(old_name.clone(), new_name, ConflictPolicy::Error);
                     }
+                    self.editing_macro_name = None;
                 }
+                if ui.button("❌").clicked() {
+                    self.editing_macro_name = None;
+                }
+            });
+        }
+    }
+
+    /// 选择要移动到的分组
+    fn render_move_panel(&mut self, ctx: &egui::Context) {
+        let Some(name) = self.moving_macro.clone() else {
+            return;
+        };
+
+        let manager = &self.state.macro_manager;
+        let current = manager.get_macro_group(&name);
+        // 只能在同一个库内移动
+        let library = manager.library_of(&name);
+        let root = if library.is_empty() { String::new() } else { format!("{library}:") };
+        let mut target = None;
+        let mut open = true;
+        egui::Window::new(format!("移动: {name}"))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                    let groups = manager
+                        .get_groups()
+                        .into_iter()
+                        .filter(|g| g.starts_with(&root) && manager.library_of(g) == library);
+                    for group in std::iter::once(root.clone()).chain(groups) {
+                        let label = if group == root {
+                            "📂 (根目录)".to_string()
+                        } else {
+                            format!("📂 {group}")
+                        };
+                        if ui.selectable_label(group == current, label).clicked() {
+                            target = Some(group);
+                        }
+                    }
+                });
+            });
+
+        if let Some(group) = target {
+            if let Err(e) = manager.move_macro(&name, &group) {
+                debug!("Failed to move macro: {e}");
             }
-        });
+            self.moving_macro = None;
+        } else if !open {
+            self.moving_macro = None;
+        }
     }
 
     fn render_main_panel(&mut self, ui: &mut egui::Ui) {
@@ -250,9 +809,9 @@ impl App {
                     .add_enabled(
                         !is_playing,
                         egui::Button::new(if is_recording {
-                            "⏹ 停止录制 (F4)"
+                            format!("⏹ 停止录制 ({})", self.shortcut_text("stop"))
                         } else {
-                            "🔴 开始录制 (F5)"
+                            format!("🔴 开始录制 ({})", self.shortcut_text("start_recording"))
                         }),
                     )
                     .clicked()
@@ -307,15 +866,10 @@ impl App {
                         egui::TextEdit::singleline(&mut self.new_macro_name).desired_width(160.0),
                     );
                     if ui.button("💾 保存").clicked() && !self.new_macro_name.is_empty() {
-                        let events = self.state.recorder.get_events();
-                        if let Err(e) =
-                            self.state.macro_manager.save_macro(&self.new_macro_name, events)
-                        {
-                            debug!("Failed to save macro: {e}");
-                        } else {
-                            self.state.recorder.clear_events();
-                            self.new_macro_name.clear();
-                        }
+                        let saved_macro =
+                            SavedMacro::new(&self.new_macro_name, self.state.recorder.get_events())
+                                .with_screen(self.state.recorder.get_screen_info());
+                        self.save_macro(saved_macro, true, ConflictPolicy::Error);
                     }
                 });
             }
@@ -357,9 +911,8 @@ impl App {
                         },
                         timestamp: 0,
                     };
-                    if let Err(e) = self.state.macro_manager.save_macro(&macro_name, vec![event]) {
-                        debug!("Failed to save delay macro: {e}");
-                    }
+                    let saved_macro = SavedMacro::new(&macro_name, vec![event]);
+                    self.save_macro(saved_macro, false, ConflictPolicy::Error);
                 }
             });
         });
@@ -383,6 +936,19 @@ impl App {
                 if ui.button("清空").clicked() {
                     self.state.clear_selected_macros();
                 }
+                if selected_count > 0 && ui.button("🗑 删除选中").clicked() {
+                    // 只读库中的宏不能删除
+                    let manager = &self.state.macro_manager;
+                    let names: Vec<String> = self
+                        .state
+                        .get_selected_macros()
+                        .into_iter()
+                        .filter(|name| !manager.is_read_only(name))
+                        .collect();
+                    if !names.is_empty() {
+                        self.deleting_macros = Some(names);
+                    }
+                }
             });
         });
 
@@ -418,9 +984,9 @@ impl App {
                         // 播放一次
                         if ui
                             .button(if is_playing {
-                                "⏹ 停止播放 (F4)"
+                                format!("⏹ 停止播放 ({})", self.shortcut_text("stop"))
                             } else {
-                                "▶ 播放 1 次 (F7)"
+                                format!("▶ 播放 1 次 ({})", self.shortcut_text("play_once"))
                             })
                             .clicked()
                         {
@@ -434,9 +1000,9 @@ impl App {
                         // 播放多次
                         if ui
                             .button(if is_playing {
-                                "⏹ 停止播放 (F4)"
+                                format!("⏹ 停止播放 ({})", self.shortcut_text("stop"))
                             } else {
-                                "▶ 播放 (F8)"
+                                format!("▶ 播放 ({})", self.shortcut_text("play_multiple"))
                             })
                             .clicked()
                         {
@@ -448,6 +1014,17 @@ impl App {
                             }
                         }
 
+                        if is_playing {
+                            let paused = self.state.is_paused();
+                            if ui.button(if paused { "▶ 继续" } else { "⏸ 暂停" }).clicked() {
+                                if paused {
+                                    self.state.resume_player();
+                                } else {
+                                    self.state.pause_player();
+                                }
+                            }
+                        }
+
                         ui.horizontal(|ui| {
                             ui.spacing_mut().item_spacing.x = 0.0;
 
@@ -540,6 +1117,647 @@ impl App {
         });
     }
 
+    /// 保存宏, 名称冲突时弹窗询问
+    fn save_macro(
+        &mut self, saved_macro: SavedMacro, from_recording: bool, policy: ConflictPolicy,
+    ) {
+        match self.state.macro_manager.save(saved_macro.clone(), policy) {
+            Ok(_) => {
+                if from_recording {
+                    self.state.recorder.clear_events();
+                    self.new_macro_name.clear();
+                }
+            },
+            Err(MacroError::AlreadyExists(_)) => {
+                self.pending_conflict = Some(PendingConflict::Save {
+                    saved_macro: Box::new(saved_macro),
+                    from_recording,
+                });
+            },
+            Err(e) => {
+                debug!("Failed to save macro: {e}");
+                self.save_error = Some(format!("保存失败: {e}"));
+            },
+        }
+    }
+
+    /// 重命名宏, 名称冲突时弹窗询问
+    fn rename_macro(&mut self, old_name: String, new_name: String, policy: ConflictPolicy) {
+        match self.state.macro_manager.rename_macro(&old_name, &new_name, policy) {
+            Ok(final_name) => {
+                // 同步选中状态
+                if self.state.is_selected(&old_name) {
+                    self.state.remove_selected_macros(&old_name);
+                    self.state.add_selected_macros(&final_name);
+                }
+            },
+            Err(MacroError::AlreadyExists(_)) => {
+                self.pending_conflict = Some(PendingConflict::Rename { old_name, new_name });
+            },
+            Err(e) => {
+                debug!("Failed to rename macro: {e}");
+                self.save_error = Some(format!("重命名失败: {e}"));
+            },
+        }
+    }
+
+    /// 名称冲突对话框: 覆盖 / 自动重命名 / 取消
+    fn render_conflict_panel(&mut self, ctx: &egui::Context) {
+        let name = match &self.pending_conflict {
+            Some(PendingConflict::Save { saved_macro, .. }) => saved_macro.name.clone(),
+            Some(PendingConflict::Rename { new_name, .. }) => new_name.clone(),
+            None => return,
+        };
+
+        let mut choice = None;
+        egui::Window::new("名称冲突")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("<{name}>已存在, 要如何处理?"));
+                ui.horizontal(|ui| {
+                    if ui.button("覆盖").clicked() {
+                        choice = Some(Some(ConflictPolicy::Overwrite));
+                    }
+                    if ui
+                        .button(format!("另存为<{}>", self.state.macro_manager.unique_name(&name)))
+                        .clicked()
+                    {
+                        choice = Some(Some(ConflictPolicy::AutoSuffix));
+                    }
+                    if ui.button("❌ 取消").clicked() {
+                        choice = Some(None);
+                    }
+                });
+            });
+
+        let Some(choice) = choice else {
+            return;
+        };
+        let pending = self.pending_conflict.take();
+        let (Some(policy), Some(pending)) = (choice, pending) else {
+            return;
+        };
+        match pending {
+            PendingConflict::Save {
+                saved_macro,
+                from_recording,
+            } => {
+                self.save_macro(*saved_macro, from_recording, policy);
+            },
+            PendingConflict::Rename { old_name, new_name } => {
+                self.rename_macro(old_name, new_name, policy);
+            },
+        }
+    }
+
+    /// 编辑宏的描述、标签和作者
+    fn render_info_panel(&mut self, ctx: &egui::Context) {
+        let Some(draft) = &mut self.editing_info else {
+            return;
+        };
+
+        let mut result = None;
+        egui::Window::new(format!("宏信息: {}", draft.name))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("macro_info").num_columns(2).show(ui, |ui| {
+                    ui.label("描述:");
+                    ui.add(egui::TextEdit::multiline(&mut draft.description).desired_rows(3));
+                    ui.end_row();
+                    ui.label("标签:");
+                    ui.add(egui::TextEdit::singleline(&mut draft.tags).hint_text("用逗号分隔"));
+                    ui.end_row();
+                    ui.label("作者:");
+                    ui.text_edit_singleline(&mut draft.author);
+                    ui.end_row();
+                    ui.label("快捷键:");
+                    ui.add(egui::TextEdit::singleline(&mut draft.hotkey).hint_text("如 Ctrl+F9"));
+                    ui.end_row();
+                });
+                if let Some(error) = &draft.error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("✅ 保存").clicked() {
+                        result = Some(true);
+                    }
+                    if ui.button("❌ 取消").clicked() {
+                        result = Some(false);
+                    }
+                });
+            });
+
+        match result {
+            Some(true) => {
+                let Some(draft) = &mut self.editing_info else {
+                    return;
+                };
+                let hotkey = match draft.hotkey.trim() {
+                    "" => None,
+                    text => match Hotkey::parse(text) {
+                        Ok(hotkey) => Some(hotkey),
+                        Err(e) => {
+                            draft.error = Some(format!("快捷键无效: {e}"));
+                            return;
+                        },
+                    },
+                };
+                if let Some(hotkey) = &hotkey
+                    && let Err(e) = self.state.check_macro_hotkey(&draft.name, hotkey)
+                {
+                    draft.error = Some(format!("快捷键冲突: {e}"));
+                    return;
+                }
+                let Some(draft) = self.editing_info.take() else {
+                    return;
+                };
+                let tags = draft
+                    .tags
+                    .split([',', '，'])
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+                if let Err(e) = self.state.macro_manager.edit_macro(&draft.name, |m| {
+                    m.description = draft.description.trim().to_string();
+                    m.tags = tags;
+                    m.author = draft.author.trim().to_string();
+                    m.hotkey = hotkey;
+                }) {
+                    debug!("Failed to update macro info: {e}");
+                }
+            },
+            Some(false) => self.editing_info = None,
+            None => {},
+        }
+    }
+
+    /// 宏的历史版本列表, 可以恢复到任意版本
+    fn render_history_panel(&mut self, ctx: &egui::Context) {
+        let Some(name) = self.viewing_history.clone() else {
+            return;
+        };
+
+        let manager = &self.state.macro_manager;
+        let revisions = manager.list_revisions(&name).unwrap_or_else(|e| {
+            debug!("Failed to list revisions: {e}");
+            Vec::new()
+        });
+        let current = manager.get_macro_header(&name);
+        let mut restore = None;
+        let mut open = true;
+        egui::Window::new(format!("历史版本: {name}"))
+            .collapsible(false)
+            .resizable(true)
+            .default_size([360.0, 240.0])
+            .open(&mut open)
+            .show(ctx, |ui| {
+                if revisions.is_empty() {
+                    ui.label("暂无历史版本");
+                    return;
+                }
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("macro_history").num_columns(4).striped(true).show(ui, |ui| {
+                        // 最新的版本在最上面
+                        for revision in revisions.iter().rev() {
+                            ui.label(format_date(revision.updated_at));
+                            ui.label(format!(
+                                "{} 个事件, {}",
+                                revision.stats.event_count,
+                                format_duration(revision.stats.duration_ms)
+                            ));
+                            if let Some(current) = &current {
+                                let diff = RevisionDiff {
+                                    revision: revision.stats.clone(),
+                                    current: current.stats.clone(),
+                                };
+                                ui.label(
+                                    egui::RichText::new(format!(
+                                        "当前: 事件 {:+}, 时长 {}",
+                                        diff.event_delta(),
+                                        format_duration_delta(diff.duration_delta_ms())
+                                    ))
+                                    .weak(),
+                                );
+                            }
+                            if ui.button("↩ 恢复").clicked() {
+                                restore = Some(revision.id.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        if let Some(id) = restore
+            && let Err(e) = manager.restore_revision(&name, &id)
+        {
+            debug!("Failed to restore revision: {e}");
+        }
+        if !open {
+            self.viewing_history = None;
+        }
+    }
+
+    /// 已挂载的宏库, 可以添加新的库并写入设置文件
+    fn render_libraries_panel(&mut self, ctx: &egui::Context) {
+        let manager = &self.state.macro_manager;
+        let mut add = false;
+        egui::Window::new("宏库")
+            .collapsible(true)
+            .resizable(true)
+            .default_size([360.0, 200.0])
+            .open(&mut self.show_libraries)
+            .show(ctx, |ui| {
+                egui::Grid::new("macro_libraries").num_columns(3).striped(true).show(ui, |ui| {
+                    for library in manager.libraries() {
+                        let name = if library.name.is_empty() {
+                            "(主库)".to_string()
+                        } else {
+                            library.name
+                        };
+                        ui.label(name);
+                        ui.label(egui::RichText::new(library.path.display().to_string()).weak());
+                        ui.label(if library.read_only { "🔒 只读" } else { "" });
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+
+                egui::Grid::new("new_library").num_columns(2).show(ui, |ui| {
+                    ui.label("名称:");
+                    ui.text_edit_singleline(&mut self.new_library.name);
+                    ui.end_row();
+                    ui.label("目录:");
+                    ui.text_edit_singleline(&mut self.new_library.path);
+                    ui.end_row();
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.new_library.read_only, "只读");
+                    if ui.button("➕ 挂载").clicked() {
+                        add = true;
+                    }
+                });
+                ui.separator();
+
+                // 新宏的存储格式, 也可以把已有的宏都转换过去
+                let mut format = manager.default_format();
+                ui.horizontal(|ui| {
+                    ui.label("新宏格式:");
+                    egui::ComboBox::from_id_salt("storage_format")
+                        .selected_text(format.label())
+                        .show_ui(ui, |ui| {
+                            for option in StorageFormat::ALL {
+                                ui.selectable_value(&mut format, option, option.label());
+                            }
+                        });
+                    if ui.button("🔄 转换所有宏").on_hover_text("把可写的宏都转换为此格式").clicked()
+                    {
+                        for name in manager.get_macro_names() {
+                            if manager.is_read_only(&name) {
+                                continue;
+                            }
+                            if let Err(e) = manager.convert_macro(&name, format) {
+                                debug!("Failed to convert macro {name}: {e}");
+                            }
+                        }
+                    }
+                });
+                if format != manager.default_format() {
+                    manager.set_default_format(format);
+                    let mut settings = self.state.settings.write();
+                    settings.storage_format = format;
+                    if let Err(e) = settings.save_to(self.state.settings_file()) {
+                        debug!("Failed to save settings: {e}");
+                    }
+                }
+            });
+
+        if !add {
+            return;
+        }
+        let config = LibraryConfig {
+            name: self.new_library.name.trim().to_string(),
+            path: self.new_library.path.trim().to_string(),
+            read_only: self.new_library.read_only,
+        };
+        match manager.mount_library(&config) {
+            Ok(()) => {
+                let mut settings = self.state.settings.write();
+                settings.add_library(config);
+                if let Err(e) = settings.save_to(self.state.settings_file()) {
+                    debug!("Failed to save settings: {e}");
+                }
+                self.new_library.name.clear();
+                self.new_library.path.clear();
+            },
+            Err(e) => debug!("Failed to mount library: {e}"),
+        }
+    }
+
+    /// 宏包导入导出
+    fn render_bundle_panel(&mut self, ctx: &egui::Context) {
+        let manager = &self.state.macro_manager;
+        let selected: Vec<String> = self.state.get_selected_macros().into_iter().collect();
+        let mut export = false;
+        let mut import = false;
+        let mut export_script = false;
+        let mut import_script = false;
+        egui::Window::new("导入导出")
+            .collapsible(true)
+            .resizable(true)
+            .default_size([360.0, 200.0])
+            .open(&mut self.show_bundle)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("宏包文件:");
+                    ui.text_edit_singleline(&mut self.bundle_path);
+                });
+                ui.separator();
+
+                ui.add_enabled_ui(!selected.is_empty(), |ui| {
+                    if ui.button(format!("📤 导出选中的 {} 个宏", selected.len())).clicked()
+                    {
+                        export = true;
+                    }
+                });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("导入到分组:");
+                    let label = if self.bundle_group.is_empty() {
+                        "(根分组)".to_string()
+                    } else {
+                        self.bundle_group.clone()
+                    };
+                    egui::ComboBox::from_id_salt("bundle_group").selected_text(label).show_ui(
+                        ui,
+                        |ui| {
+                            ui.selectable_value(&mut self.bundle_group, String::new(), "(根分组)");
+                            for group in manager.get_groups() {
+                                if manager.is_read_only(&group) {
+                                    continue;
+                                }
+                                ui.selectable_value(&mut self.bundle_group, group.clone(), group);
+                            }
+                        },
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("同名时:");
+                    ui.radio_value(&mut self.bundle_policy, ConflictPolicy::Error, "跳过");
+                    ui.radio_value(&mut self.bundle_policy, ConflictPolicy::Overwrite, "覆盖");
+                    ui.radio_value(
+                        &mut self.bundle_policy,
+                        ConflictPolicy::AutoSuffix,
+                        "自动重命名",
+                    );
+                });
+                if ui.button("📥 导入").clicked() {
+                    import = true;
+                }
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("脚本文件:");
+                    ui.text_edit_singleline(&mut self.script_path);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("脚本格式:");
+                    let previous = self.script_format;
+                    egui::ComboBox::from_id_salt("script_format")
+                        .selected_text(self.script_format.label())
+                        .show_ui(ui, |ui| {
+                            for format in ScriptFormat::ALL {
+                                ui.selectable_value(
+                                    &mut self.script_format,
+                                    format,
+                                    format.label(),
+                                );
+                            }
+                        });
+                    // 切换格式时同步修改扩展名
+                    if self.script_format != previous {
+                        let path = Path::new(self.script_path.trim());
+                        self.script_path = path
+                            .with_extension(self.script_format.extension())
+                            .to_string_lossy()
+                            .to_string();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.add_enabled_ui(selected.len() == 1, |ui| {
+                        if ui
+                            .button("📜 导出选中的宏")
+                            .on_disabled_hover_text("请只选中一个宏")
+                            .clicked()
+                        {
+                            export_script = true;
+                        }
+                    });
+                    // 只有 xdotool 脚本可以导入
+                    ui.add_enabled_ui(self.script_format == ScriptFormat::Xdotool, |ui| {
+                        if ui.button("📜 导入到上面的分组").clicked() {
+                            import_script = true;
+                        }
+                    });
+                });
+
+                if let Some(message) = &self.bundle_message {
+                    ui.separator();
+                    ui.label(message);
+                }
+            });
+
+        let path = PathBuf::from(self.bundle_path.trim());
+        if export {
+            let result = manager.export_bundle(&selected).and_then(|b| b.write_to(&path));
+            self.bundle_message = Some(match result {
+                Ok(()) => format!("已导出 {} 个宏到 {}", selected.len(), path.display()),
+                Err(e) => format!("导出失败: {e}"),
+            });
+        }
+        if import {
+            let shortcuts = self.state.shortcuts();
+            let taken: Vec<Hotkey> = shortcuts.iter().map(|s| s.hotkey.clone()).collect();
+            let result = Bundle::read_from(&path).and_then(|b| {
+                manager.import_bundle(&b, &self.bundle_group, self.bundle_policy, &taken)
+            });
+            self.bundle_message = Some(match result {
+                Ok(report) => {
+                    let mut message = format!("已导入 {} 个宏", report.imported.len());
+                    if !report.skipped.is_empty() {
+                        message += &format!(", 跳过同名的宏: {}", report.skipped.join(", "));
+                    }
+                    if !report.dropped_hotkeys.is_empty() {
+                        let names = report.dropped_hotkeys.join(", ");
+                        message += &format!(", 快捷键已被占用, 已去掉: {names}");
+                    }
+                    message
+                },
+                Err(e) => format!("导入失败: {e}"),
+            });
+        }
+
+        let path = PathBuf::from(self.script_path.trim());
+        if export_script && let Some(saved_macro) = manager.get_macros(&selected).pop() {
+            let result = fs::write(&path, self.script_format.export(&saved_macro));
+            self.bundle_message = Some(match result {
+                Ok(()) => format!("已导出 {} 到 {}", saved_macro.name, path.display()),
+                Err(e) => format!("导出失败: {e}"),
+            });
+        }
+        if import_script {
+            self.bundle_message = Some(match self.import_script(&path) {
+                Ok(name) => format!("已导入 {name}"),
+                Err(e) => format!("导入失败: {e}"),
+            });
+        }
+    }
+
+    /// 导入 xdotool 脚本, 以文件名作为宏名称, 放到导入分组中
+    fn import_script(&self, path: &Path) -> Result<String, MacroError> {
+        let manager = &self.state.macro_manager;
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let library = manager.library_of(&self.bundle_group);
+        let name = if library.is_empty() { stem } else { format!("{library}:{stem}") };
+
+        let saved_macro = xdotool::import(&name, &fs::read_to_string(path)?)?;
+        let name = manager.save(saved_macro, ConflictPolicy::AutoSuffix)?;
+        if !self.bundle_group.is_empty() && !self.bundle_group.ends_with(':') {
+            manager.move_macro(&name, &self.bundle_group)?;
+        }
+        Ok(name)
+    }
+
+    /// 回收站: 恢复或永久删除
+    fn render_trash_panel(&mut self, ctx: &egui::Context) {
+        let manager = &self.state.macro_manager;
+        let entries = manager.list_trash();
+        let mut restore = None;
+        let mut purge = None;
+        let mut empty = false;
+        egui::Window::new("回收站")
+            .collapsible(true)
+            .resizable(true)
+            .default_size([360.0, 240.0])
+            .open(&mut self.show_trash)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("保留天数:");
+                    let mut days = manager.trash_retention_days();
+                    if ui
+                        .add(egui::DragValue::new(&mut days).range(0..=3650).suffix("天"))
+                        .on_hover_text("0 表示永久保留")
+                        .changed()
+                    {
+                        manager.set_trash_retention_days(days);
+                    }
+                    ui.add_enabled_ui(!entries.is_empty(), |ui| {
+                        if ui.button("清空回收站").clicked() {
+                            empty = true;
+                        }
+                    });
+                });
+                ui.separator();
+
+                if entries.is_empty() {
+                    ui.label("回收站是空的");
+                    return;
+                }
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    egui::Grid::new("macro_trash").num_columns(3).striped(true).show(ui, |ui| {
+                        for entry in entries.iter() {
+                            let location = if entry.group.is_empty() {
+                                entry.name.clone()
+                            } else {
+                                format!("{}/{}", entry.group, entry.name)
+                            };
+                            ui.label(location);
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "{} 删除, {} 个事件",
+                                    format_date(entry.deleted_at),
+                                    entry.stats.event_count
+                                ))
+                                .weak(),
+                            );
+                            ui.horizontal(|ui| {
+                                if ui.button("↩").on_hover_text("恢复").clicked() {
+                                    restore = Some(entry.id.clone());
+                                }
+                                if ui.button("❌").on_hover_text("永久删除").clicked() {
+                                    purge = Some(entry.id.clone());
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        if let Some(id) = restore
+            && let Err(e) = manager.restore_from_trash(&id, ConflictPolicy::AutoSuffix)
+        {
+            debug!("Failed to restore macro: {e}");
+        }
+        if let Some(id) = purge
+            && let Err(e) = manager.purge_from_trash(&id)
+        {
+            debug!("Failed to purge macro: {e}");
+        }
+        if empty {
+            match manager.empty_trash() {
+                Ok(_) => self.undo_delete_count = 0,
+                Err(e) => debug!("Failed to empty trash: {e}"),
+            }
+        }
+    }
+
+    /// 加载失败的宏文件列表
+    fn render_load_report_panel(&mut self, ctx: &egui::Context) {
+        let report = self.state.macro_manager.get_load_report();
+        egui::Window::new("宏加载失败")
+            .collapsible(true)
+            .resizable(true)
+            .default_size([360.0, 200.0])
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "已加载 {} 个宏, {} 个文件无法读取:",
+                    report.loaded,
+                    report.failures.len()
+                ));
+                ui.separator();
+
+                egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                    for failure in report.failures.iter() {
+                        let file_name = failure
+                            .path
+                            .file_name()
+                            .map(|s| s.to_string_lossy().to_string())
+                            .unwrap_or_else(|| failure.path.display().to_string());
+                        ui.label(egui::RichText::new(file_name).strong())
+                            .on_hover_text(failure.path.display().to_string());
+                        ui.label(egui::RichText::new(&failure.error).weak());
+                        ui.add_space(4.0);
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("📦 移到 broken/").clicked()
+                        && let Err(e) = self.state.macro_manager.quarantine_broken_files()
+                    {
+                        debug!("Failed to quarantine broken macros: {e}");
+                    }
+                    if ui.button("忽略").clicked() {
+                        self.state.macro_manager.dismiss_load_failures();
+                    }
+                });
+            });
+    }
+
     fn render_help_panel(&mut self, ctx: &egui::Context) {
         egui::Window::new("快捷键帮助")
             .collapsible(true)
@@ -552,12 +1770,33 @@ impl App {
                 ui.separator();
 
                 egui::ScrollArea::vertical().show(ui, |ui| {
-                    for shortcut in self.state.shortcuts.iter() {
+                    for shortcut in self.state.shortcuts().iter() {
                         ui.horizontal(|ui| {
                             ui.label(&shortcut.description);
                             ui.with_layout(
                                 egui::Layout::right_to_left(egui::Align::Center),
                                 |ui| {
+                                    if ui.small_button("✏").on_hover_text("修改快捷键").clicked() {
+                                        self.capturing_shortcut = Some(shortcut.name.clone());
+                                        self.shortcut_message = None;
+                                        // 录入时按下的键不能触发原来的快捷键
+                                        self.state.set_hotkeys_suspended(true);
+                                    }
+                                    if self.capturing_shortcut.as_ref() == Some(&shortcut.name) {
+                                        if ui.small_button("取消").clicked() {
+                                            self.finish_capture();
+                                        }
+                                        let captured = self.captured_hotkey();
+                                        let confirm = egui::Button::new("确定");
+                                        if ui.add_enabled(captured.is_some(), confirm).clicked() {
+                                            self.apply_captured_shortcut();
+                                        }
+                                        match captured {
+                                            Some(hotkey) => ui.strong(hotkey.to_string()),
+                                            None => ui.label("请按下新的快捷键"),
+                                        };
+                                        return;
+                                    }
                                     let color = if ui.visuals().dark_mode {
                                         egui::Color32::LIGHT_BLUE
                                     } else {
@@ -569,6 +1808,17 @@ impl App {
                         });
                     }
                 });
+                ui.separator();
+                if let Some(message) = &self.shortcut_message {
+                    ui.label(message);
+                }
+                if self.capturing_shortcut.is_some() {
+                    ui.weak("可以依次按下多个组合组成序列, 同一组合按两次为双击, 也可以同时按下多个鼠标按键");
+                }
+                if ui.button("恢复默认快捷键").clicked() {
+                    self.state.reset_shortcuts();
+                    self.shortcut_message = None;
+                }
             });
     }
 
@@ -576,3 +1826,95 @@ impl App {
         self.state.play_selected_macros(repeat_count);
     }
 }
+
+/// 宏列表中的一行摘要: 时长、事件数和标签
+fn macro_summary(saved_macro: &SavedMacro) -> String {
+    let mut s = format!(
+        "{} · {} 事件",
+        format_duration(saved_macro.stats.duration_ms),
+        saved_macro.stats.event_count
+    );
+    for tag in saved_macro.tags.iter() {
+        s += &format!(" #{tag}");
+    }
+    s
+}
+
+/// 鼠标悬停时显示的完整信息
+fn macro_details_ui(ui: &mut egui::Ui, saved_macro: &SavedMacro) {
+    if !saved_macro.description.is_empty() {
+        ui.label(&saved_macro.description);
+        ui.separator();
+    }
+    let stats = &saved_macro.stats;
+    ui.label(format!("时长: {}", format_duration(stats.duration_ms)));
+    ui.label(format!(
+        "事件: {} (移动 {} / 点击 {} / 按键 {} / 延时 {})",
+        stats.event_count, stats.mouse_moves, stats.mouse_clicks, stats.key_events, stats.delays
+    ));
+    if !saved_macro.tags.is_empty() {
+        ui.label(format!("标签: {}", saved_macro.tags.join(", ")));
+    }
+    if !saved_macro.author.is_empty() {
+        ui.label(format!("作者: {}", saved_macro.author));
+    }
+    if let Some(hotkey) = &saved_macro.hotkey {
+        ui.label(format!("快捷键: {hotkey}"));
+    }
+    if let Some(screen) = saved_macro.screen {
+        ui.label(format!("屏幕: {}x{} @{}x", screen.width, screen.height, screen.scale));
+    }
+    ui.label(format!("创建: {}", format_date(saved_macro.created_at)));
+    ui.label(format!("修改: {}", format_date(saved_macro.updated_at)));
+}
+
+/// 时长筛选的上下限, 0 表示不限
+fn duration_bound_ui(ui: &mut egui::Ui, bound: &mut Option<u64>) {
+    let mut secs = bound.map_or(0.0, |ms| ms as f64 / 1000.0);
+    ui.add(egui::DragValue::new(&mut secs).range(0.0..=86400.0).speed(0.5).max_decimals(1))
+        .on_hover_text("0 表示不限");
+    *bound = (secs > 0.0).then_some((secs * 1000.0) as u64);
+}
+
+fn date_range_label(days: Option<u64>) -> String {
+    match days {
+        None => "不限".to_string(),
+        Some(1) => "今天".to_string(),
+        Some(days) => format!("{days} 天内"),
+    }
+}
+
+fn format_duration_delta(ms: i64) -> String {
+    let sign = if ms < 0 { "-" } else { "+" };
+    format!("{sign}{}", format_duration(ms.unsigned_abs()))
+}
+
+fn format_duration(ms: u64) -> String {
+    if ms < 60_000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        format!("{}m{:02}s", ms / 60_000, ms % 60_000 / 1000)
+    }
+}
+
+/// 把 Unix 时间戳(秒)格式化为 UTC 日期时间
+fn format_date(secs: u64) -> String {
+    if secs == 0 {
+        return "-".to_string();
+    }
+    let days = (secs / 86400) as i64;
+    let time = secs % 86400;
+
+    // 公历日期换算, 参考 Howard Hinnant 的 civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year}-{month:02}-{day:02} {:02}:{:02} UTC", time / 3600, time % 3600 / 60)
+}
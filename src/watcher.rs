@@ -0,0 +1,70 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::macro_manager::{MacroManager, ReloadSummary};
+
+/// 默认检查间隔
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 在后台定期检查宏目录, 把磁盘上的外部修改同步到 [`MacroManager`]
+///
+/// 文件要在连续两次检查中保持不变才会重新加载, 所以变化大约在两个间隔后生效.
+#[derive(Debug)]
+pub struct MacroWatcher {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl MacroWatcher {
+    /// 启动监视线程, 每次有变化时调用 `on_change`
+    pub fn start<F>(manager: MacroManager, interval: Duration, on_change: F) -> Self
+    where
+        F: Fn(&ReloadSummary) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = stop.clone();
+        let handle = thread::spawn(move || {
+            let mut next = Instant::now() + interval;
+            while !stop_clone.load(Ordering::SeqCst) {
+                // 小步睡眠, 停止时不用等满一个间隔
+                thread::sleep(Duration::from_millis(50).min(interval));
+                if Instant::now() < next {
+                    continue;
+                }
+                next = Instant::now() + interval;
+
+                let summary = manager.poll_changes();
+                if !summary.is_empty() {
+                    debug!("Macros changed on disk: {summary:?}");
+                    on_change(&summary);
+                }
+            }
+        });
+
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for MacroWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
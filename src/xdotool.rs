@@ -0,0 +1,178 @@
+use crate::{
+    event::{Button, MacroEvent, MacroEventType, with_gaps},
+    key::{comment_text, key_from_xdotool_name, xdotool_key_name},
+    macro_manager::{MacroError, SavedMacro},
+};
+
+type Result<T, E = MacroError> = std::result::Result<T, E>;
+
+/// 导入时单条 `sleep` 允许的最长秒数
+const MAX_SLEEP_SECS: f64 = 86_400.0;
+
+/// 把宏导出为可直接运行的 bash 脚本, 每个事件对应一条 xdotool 命令
+///
+/// 事件之间的间隔和延时事件都转换为 `sleep`. xdotool 没有对应 keysym 的键
+/// 会以注释的形式保留在脚本中.
+pub fn export(saved_macro: &SavedMacro) -> String {
+    let mut script = String::from("#!/usr/bin/env bash\n");
+    script.push_str(&format!(
+        "# Exported from mousepilot: {}\n",
+        comment_text(&saved_macro.name)
+    ));
+    if let Some(screen) = saved_macro.screen {
+        script.push_str(&format!("# Recorded on a {}x{} screen\n", screen.width, screen.height));
+    }
+    script.push_str("set -e\n\n");
+
+    for (gap, event) in with_gaps(&saved_macro.events) {
+        if gap > 0 {
+            script.push_str(&sleep_line(gap));
+        }
+
+        let line = match &event.event_type {
+            MacroEventType::MouseMove { x, y } => format!("xdotool mousemove {x} {y}\n"),
+            MacroEventType::MouseClick { button, pressed } => {
+                let action = if *pressed { "mousedown" } else { "mouseup" };
+                format!("xdotool {action} {}\n", button_number(button))
+            },
+            MacroEventType::KeyPress { key } | MacroEventType::KeyRelease { key } => {
+                let action = match event.event_type {
+                    MacroEventType::KeyPress { .. } => "keydown",
+                    _ => "keyup",
+                };
+                match xdotool_key_name(key) {
+                    Some(keysym) => format!("xdotool {action} {keysym}\n"),
+                    None => format!("# unsupported key: {action} {}\n", comment_text(key)),
+                }
+            },
+            MacroEventType::Delay { duration_ms } => sleep_line(*duration_ms),
+        };
+        script.push_str(&line);
+    }
+    script
+}
+
+/// 从 xdotool 脚本导入宏
+///
+/// 只支持每行一条命令的简单脚本: `mousemove`、`mousedown`、`mouseup`、`click`、
+/// `keydown`、`keyup`、`key` 和 `sleep`. 空行、注释和 `set` 会被忽略,
+/// 其他内容返回带行号的错误.
+pub fn import(name: &str, script: &str) -> Result<SavedMacro> {
+    let mut events = Vec::new();
+    let mut clock = 0u128;
+    for (i, line) in script.lines().enumerate() {
+        let error = |reason: String| MacroError::Format(format!("line {}: {reason}", i + 1));
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        match words.first().copied() {
+            Some("set") => continue,
+            Some("sleep") => {},
+            Some("xdotool") => {
+                words.remove(0);
+            },
+            _ => return Err(error(format!("unsupported command: {line}"))),
+        }
+        // 忽略 `--sync` 之类的选项
+        words.retain(|w| !w.starts_with("--"));
+        let Some((command, args)) = words.split_first() else {
+            return Err(error("missing xdotool command".to_string()));
+        };
+
+        let mut push = |event_type| {
+            events.push(MacroEvent {
+                event_type,
+                timestamp: clock,
+            })
+        };
+        match (*command, args) {
+            ("sleep", [secs]) => {
+                let secs: f64 =
+                    secs.parse().map_err(|_| error(format!("invalid delay: {secs}")))?;
+                if !secs.is_finite() || !(0.0..=MAX_SLEEP_SECS).contains(&secs) {
+                    return Err(error(format!("invalid delay: {secs}")));
+                }
+                clock = clock
+                    .checked_add((secs * 1000.0).round() as u128)
+                    .ok_or_else(|| error("timestamp overflow".to_string()))?;
+            },
+            ("mousemove", [x, y]) => {
+                let parse =
+                    |v: &str| v.parse().map_err(|_| error(format!("invalid coordinate: {v}")));
+                push(MacroEventType::MouseMove {
+                    x: parse(x)?,
+                    y: parse(y)?,
+                });
+            },
+            ("mousedown" | "mouseup" | "click", [button]) => {
+                let button = parse_button(button)
+                    .ok_or_else(|| error(format!("invalid button: {button}")))?;
+                if *command != "mouseup" {
+                    push(MacroEventType::MouseClick {
+                        button: button.clone(),
+                        pressed: true,
+                    });
+                }
+                if *command != "mousedown" {
+                    push(MacroEventType::MouseClick {
+                        button,
+                        pressed: false,
+                    });
+                }
+            },
+            ("keydown" | "keyup" | "key", keys) if !keys.is_empty() => {
+                for keysym in keys.iter() {
+                    // `key ctrl+c` 依次按下组合键, 再反向抬起
+                    let combo = keysym
+                        .split('+')
+                        .map(|k| {
+                            key_from_xdotool_name(k)
+                                .ok_or_else(|| error(format!("unknown key: {k}")))
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    if *command != "keyup" {
+                        for key in combo.iter() {
+                            push(MacroEventType::KeyPress {
+                                key: key.to_string(),
+                            });
+                        }
+                    }
+                    if *command != "keydown" {
+                        for key in combo.iter().rev() {
+                            push(MacroEventType::KeyRelease {
+                                key: key.to_string(),
+                            });
+                        }
+                    }
+                }
+            },
+            _ => return Err(error(format!("unsupported command: {line}"))),
+        }
+    }
+    Ok(SavedMacro::new(name, events))
+}
+
+fn sleep_line(duration_ms: u64) -> String {
+    format!("sleep {}.{:03}\n", duration_ms / 1000, duration_ms % 1000)
+}
+
+/// xdotool 的鼠标按键编号: 1 左键, 2 中键, 3 右键
+fn button_number(button: &Button) -> u8 {
+    match button {
+        Button::Left => 1,
+        Button::Middle => 2,
+        Button::Right => 3,
+    }
+}
+
+fn parse_button(button: &str) -> Option<Button> {
+    match button {
+        "1" => Some(Button::Left),
+        "2" => Some(Button::Middle),
+        "3" => Some(Button::Right),
+        _ => None,
+    }
+}
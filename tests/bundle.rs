@@ -0,0 +1,139 @@
+#[cfg(test)]
+mod tests {
+    use mousepilot::{
+        bundle::{BUNDLE_VERSION, Bundle},
+        event::{MacroEvent, MacroEventType},
+        hotkey::Hotkey,
+        macro_manager::{ConflictPolicy, MacroError, MacroManager, SavedMacro},
+        settings::LibraryConfig,
+    };
+    use std::fs;
+
+    fn delay(duration_ms: u64) -> MacroEvent {
+        MacroEvent {
+            event_type: MacroEventType::Delay { duration_ms },
+            timestamp: 0,
+        }
+    }
+
+    fn source() -> (tempfile::TempDir, MacroManager) {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        manager.create_group("work/daily").unwrap();
+        manager.save_macro("login", vec![delay(10)], ConflictPolicy::Error).unwrap();
+        manager.move_macro("login", "work/daily").unwrap();
+        manager
+            .save_macro("logout", vec![delay(20), delay(30)], ConflictPolicy::Error)
+            .unwrap();
+        (dir, manager)
+    }
+
+    #[test]
+    fn export_and_import_keeps_groups() {
+        let (dir, manager) = source();
+        let names = ["login".to_string(), "logout".to_string()];
+        let path = dir.path().join("out").join("macros.mpbundle");
+        manager.export_bundle(&names).unwrap().write_to(&path).unwrap();
+
+        let bundle = Bundle::read_from(&path).unwrap();
+        assert_eq!(bundle.version, BUNDLE_VERSION);
+        assert_eq!(bundle.entries[0].group, "work/daily");
+        assert_eq!(bundle.entries[1].stats.event_count, 2);
+        assert!(bundle.entries[0].checksum.starts_with("crc32:"));
+
+        let target = tempfile::tempdir().unwrap();
+        let imported = MacroManager::open(target.path().to_string_lossy()).unwrap();
+        let report = imported.import_bundle(&bundle, "shared", ConflictPolicy::Error, &[]).unwrap();
+        assert_eq!(report.imported, names);
+        assert_eq!(imported.get_macro_group("login"), "shared/work/daily");
+        assert_eq!(imported.get_macro_group("logout"), "shared");
+        assert!(target.path().join("shared/work/daily/login.json").exists());
+
+        // 同名时按策略跳过或重命名
+        let report = imported.import_bundle(&bundle, "", ConflictPolicy::Error, &[]).unwrap();
+        assert!(report.imported.is_empty());
+        assert_eq!(report.skipped, names);
+        let report = imported.import_bundle(&bundle, "", ConflictPolicy::AutoSuffix, &[]).unwrap();
+        assert_eq!(report.imported, ["login (2)", "logout (2)"]);
+    }
+
+    #[test]
+    fn tampered_bundle_is_rejected_before_writing() {
+        let (_dir, manager) = source();
+        let mut bundle =
+            manager.export_bundle(&["login".to_string(), "logout".to_string()]).unwrap();
+        bundle.entries[1].data["description"] = "changed".into();
+
+        let target = tempfile::tempdir().unwrap();
+        let imported = MacroManager::open(target.path().to_string_lossy()).unwrap();
+        let result = imported.import_bundle(&bundle, "", ConflictPolicy::Overwrite, &[]);
+        assert!(matches!(result, Err(MacroError::Format(e)) if e.contains("checksum")));
+        assert_eq!(imported.get_macro_count(), 0);
+
+        let mut bundle = manager.export_bundle(&["login".to_string()]).unwrap();
+        bundle.version = BUNDLE_VERSION + 1;
+        assert!(bundle.verify().is_err());
+        assert!(manager.export_bundle(&["missing".to_string()]).is_err());
+    }
+
+    #[test]
+    fn imported_hotkeys_are_checked_for_conflicts() {
+        let (_dir, manager) = source();
+        manager.set_hotkey("login", Some(Hotkey::parse("Ctrl+F9").unwrap())).unwrap();
+        let mut bundle = manager.export_bundle(&["login".to_string()]).unwrap();
+        for (name, hotkey) in [("typing", "A"), ("builtin", "F4"), ("free", "Alt+F9")] {
+            let mut saved_macro = SavedMacro::new(name, vec![delay(10)]);
+            saved_macro.hotkey = Some(Hotkey::parse(hotkey).unwrap());
+            bundle.push("", &saved_macro).unwrap();
+        }
+
+        // 自动改名的副本不能带走原来的快捷键
+        let taken = [Hotkey::parse("F4").unwrap()];
+        let report =
+            manager.import_bundle(&bundle, "", ConflictPolicy::AutoSuffix, &taken).unwrap();
+        assert_eq!(report.imported, ["login (2)", "typing", "builtin", "free"]);
+        assert_eq!(report.dropped_hotkeys, ["login (2)", "typing", "builtin"]);
+        let hotkey = |name| manager.get_macro(name).unwrap().hotkey.clone();
+        assert_eq!(hotkey("login"), Some(Hotkey::parse("Ctrl+F9").unwrap()));
+        assert_eq!(hotkey("login (2)"), None);
+        assert_eq!(hotkey("typing"), None);
+        assert_eq!(hotkey("free"), Some(Hotkey::parse("Alt+F9").unwrap()));
+
+        // 覆盖自己时保留快捷键
+        let bundle = manager.export_bundle(&["free".to_string()]).unwrap();
+        let report = manager.import_bundle(&bundle, "", ConflictPolicy::Overwrite, &[]).unwrap();
+        assert!(report.dropped_hotkeys.is_empty());
+        assert_eq!(hotkey("free"), Some(Hotkey::parse("Alt+F9").unwrap()));
+    }
+
+    #[test]
+    fn bundle_entries_cannot_target_other_libraries() {
+        let root = tempfile::tempdir().unwrap();
+        let (home, shared) = (root.path().join("home"), root.path().join("shared"));
+        let libraries = [LibraryConfig {
+            name: "shared".to_string(),
+            path: shared.to_string_lossy().to_string(),
+            read_only: false,
+        }];
+        let manager =
+            MacroManager::open_with_libraries(home.to_string_lossy(), &libraries).unwrap();
+
+        // 手工构造的宏包, 名称带库名前缀
+        let mut saved_macro = SavedMacro::new("shared:x", vec![delay(10)]);
+        let mut bundle = Bundle::default();
+        bundle.push("", &saved_macro).unwrap();
+        let result = manager.import_bundle(&bundle, "", ConflictPolicy::Error, &[]);
+        assert!(matches!(result, Err(MacroError::InvalidName(_))));
+        assert_eq!(manager.get_macro_count(), 0);
+
+        // 分组带库名前缀时留在主库中
+        saved_macro.name = "x".to_string();
+        let mut bundle = Bundle::default();
+        bundle.push("shared:g", &saved_macro).unwrap();
+        let report = manager.import_bundle(&bundle, "", ConflictPolicy::Error, &[]).unwrap();
+        assert_eq!(report.imported, ["x"]);
+        assert_eq!(manager.library_of("x"), "");
+        assert_eq!(manager.get_macro_group("x"), "shared_g");
+        assert!(!shared.exists() || fs::read_dir(&shared).unwrap().count() == 0);
+    }
+}
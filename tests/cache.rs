@@ -0,0 +1,35 @@
+#[cfg(test)]
+mod tests {
+    use mousepilot::cache::LruCache;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+        // 访问 a 之后, b 变成最旧的
+        assert_eq!(cache.get("a"), Some(1));
+        cache.insert("c".to_string(), 3);
+        assert!(cache.contains("a") && cache.contains("c"));
+        assert_eq!(cache.get("b"), None);
+
+        // 覆盖已有的键不会淘汰其他条目
+        cache.insert("a".to_string(), 10);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("a"), Some(10));
+
+        cache.set_capacity(1);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.contains("a"));
+        assert_eq!(cache.remove("a"), Some(10));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn zero_capacity_caches_nothing() {
+        let mut cache = LruCache::new(0);
+        cache.insert(1, "x");
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&1), None);
+    }
+}
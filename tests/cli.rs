@@ -0,0 +1,150 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common::delay;
+    use mousepilot::{
+        cli,
+        event::{MacroEvent, MacroEventType},
+        macro_manager::{ConflictPolicy, MacroManager},
+        settings::Settings,
+    };
+    use std::{fs, path::Path};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn settings(dir: &Path) -> Settings {
+        Settings {
+            macros_dir: Some(dir.to_string_lossy().to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn run(dir: &Path, command: &[&str]) -> (anyhow::Result<()>, String) {
+        let mut out = Vec::new();
+        let result = cli::run(&settings(dir), &args(command), &mut out);
+        (result, String::from_utf8(out).unwrap())
+    }
+
+    fn sample(dir: &Path) {
+        let manager = MacroManager::open(dir.to_string_lossy()).unwrap();
+        manager.save_macro("login", vec![delay(100)], ConflictPolicy::Error).unwrap();
+        manager.save_macro("logout", vec![delay(50), delay(50)], ConflictPolicy::Error).unwrap();
+        manager.create_group("work").unwrap();
+        manager.save_macro("report", Vec::new(), ConflictPolicy::Error).unwrap();
+        manager.move_macro("report", "work").unwrap();
+    }
+
+    #[test]
+    fn command_follows_global_options() {
+        let full = args(&["--macros-dir", "/tmp/m", "--library=a=/a", "play", "--repeat", "2"]);
+        assert_eq!(cli::command_index(&full), Some(3));
+        assert_eq!(cli::command_index(&args(&["--macros-dir", "/tmp/m"])), None);
+        assert_eq!(cli::command_index(&args(&["-psn_0_12345"])), None);
+        assert_eq!(cli::command_index(&args(&["/home/me/macros.mpbundle", "list"])), Some(1));
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(cli::parse_duration("30"), Some(30_000));
+        assert_eq!(cli::parse_duration("1.5s"), Some(1500));
+        assert_eq!(cli::parse_duration("250ms"), Some(250));
+        assert_eq!(cli::parse_duration("2m"), Some(120_000));
+        assert_eq!(cli::parse_duration("-1s"), None);
+        assert_eq!(cli::parse_duration("soon"), None);
+    }
+
+    #[test]
+    fn trailing_enter_is_trimmed() {
+        let key = |key: &str, pressed| MacroEvent {
+            event_type: if pressed {
+                MacroEventType::KeyPress { key: key.to_string() }
+            } else {
+                MacroEventType::KeyRelease { key: key.to_string() }
+            },
+            timestamp: 0,
+        };
+        let mut events = vec![key("Enter", true), key("Enter", false), key("A", true)];
+        events.extend([key("A", false), key("Enter", true)]);
+        cli::trim_stop_key(&mut events);
+        assert_eq!(events.len(), 4);
+        assert!(matches!(&events[3].event_type, MacroEventType::KeyRelease { key } if key == "A"));
+
+        let mut events = vec![key("Enter", true)];
+        cli::trim_stop_key(&mut events);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn list_filters_and_prints_details() {
+        let dir = tempfile::tempdir().unwrap();
+        sample(dir.path());
+
+        let (result, out) = run(dir.path(), &["list"]);
+        result.unwrap();
+        assert_eq!(out, "login\nlogout\nreport\n");
+
+        let (_, out) = run(dir.path(), &["list", "logi"]);
+        assert_eq!(out, "login\n");
+        let (_, out) = run(dir.path(), &["list", "--group", "work"]);
+        assert_eq!(out, "report\n");
+
+        let (_, out) = run(dir.path(), &["list", "logout", "--long"]);
+        let fields: Vec<&str> = out.trim_end().split('\t').collect();
+        assert_eq!(fields[..4], ["logout", "", "2", "100ms"]);
+
+        assert!(run(dir.path(), &["list", "--bogus"]).0.is_err());
+    }
+
+    #[test]
+    fn validate_reports_broken_files() {
+        let dir = tempfile::tempdir().unwrap();
+        sample(dir.path());
+        let (result, out) = run(dir.path(), &["validate"]);
+        result.unwrap();
+        assert_eq!(out, "3 macros OK, 0 failed\n");
+
+        fs::write(dir.path().join("broken.json"), r#"{"name":"broken""#).unwrap();
+        let (result, out) = run(dir.path(), &["validate"]);
+        assert!(result.is_err());
+        assert!(out.contains("FAIL ") && out.contains("broken.json"));
+        assert!(out.ends_with("3 macros OK, 1 failed\n"));
+
+        assert!(run(dir.path(), &["validate", "missing"]).0.is_err());
+    }
+
+    #[test]
+    fn export_and_import_through_commands() {
+        let source = tempfile::tempdir().unwrap();
+        sample(source.path());
+        let bundle = source.path().join("all.mpbundle");
+        let bundle = bundle.to_string_lossy();
+        let (result, out) = run(source.path(), &["export", &bundle]);
+        result.unwrap();
+        assert_eq!(out, format!("Exported 3 macros to {bundle}\n"));
+
+        let target = tempfile::tempdir().unwrap();
+        run(target.path(), &["import", &bundle, "--group=shared"]).0.unwrap();
+        let (_, out) = run(target.path(), &["import", &bundle, "--group", "shared"]);
+        assert!(out.starts_with("Imported 0 macros\nSkipped existing macro: login\n"));
+        let (_, out) = run(target.path(), &["list", "--group", "shared/work"]);
+        assert_eq!(out, "report\n");
+    }
+
+    #[test]
+    fn bad_arguments_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let (result, out) = run(dir.path(), &["help"]);
+        result.unwrap();
+        assert!(out.starts_with("usage: mousepilot"));
+
+        assert!(run(dir.path(), &["frobnicate"]).0.is_err());
+        assert!(run(dir.path(), &["play"]).0.is_err());
+        assert!(run(dir.path(), &["play", "x", "--speed", "0"]).0.is_err());
+        assert!(run(dir.path(), &["play", "missing"]).0.is_err());
+        assert!(run(dir.path(), &["record", "--duration", "1s"]).0.is_err());
+        assert!(run(dir.path(), &["record", "--out", "x", "--duration", "later"]).0.is_err());
+    }
+}
@@ -0,0 +1,32 @@
+//! 集成测试共用的宏和应用状态
+#![allow(dead_code)]
+
+use mousepilot::{
+    event::{MacroEvent, MacroEventType},
+    macro_manager::{ConflictPolicy, MacroManager},
+    settings::Settings,
+    state::AppState,
+};
+use std::{path::Path, time::Duration};
+
+pub fn delay(duration_ms: u64) -> MacroEvent {
+    MacroEvent {
+        event_type: MacroEventType::Delay { duration_ms },
+        timestamp: 0,
+    }
+}
+
+/// 在 `dir` 中保存只含延时事件的宏(播放时不需要显示器), 返回加载完成的应用状态
+pub fn app_state(dir: &Path, macros: &[(&str, u64)]) -> AppState {
+    let manager = MacroManager::open(dir.to_string_lossy()).unwrap();
+    for (name, duration_ms) in macros {
+        manager.save_macro(name, vec![delay(*duration_ms)], ConflictPolicy::Error).unwrap();
+    }
+    let settings = Settings {
+        macros_dir: Some(dir.to_string_lossy().to_string()),
+        ..Default::default()
+    };
+    let state = AppState::new(&eframe::egui::Context::default(), &settings);
+    assert!(state.macro_manager.wait_until_loaded(Duration::from_secs(5)), "macros not loaded");
+    state
+}
@@ -0,0 +1,173 @@
+#[cfg(test)]
+mod tests {
+    use mousepilot::{
+        compact,
+        event::{Button, MacroEvent, MacroEventType},
+        macro_manager::{ConflictPolicy, MacroError, MacroManager, SavedMacro},
+        storage::StorageFormat,
+    };
+    use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+    use std::{
+        fs,
+        io::{BufReader, Read, Write},
+        time::Instant,
+    };
+
+    fn event(event_type: MacroEventType, timestamp: u128) -> MacroEvent {
+        MacroEvent {
+            event_type,
+            timestamp,
+        }
+    }
+
+    /// 模拟很长的录制: 密集的鼠标移动夹杂点击和按键
+    fn recording(moves: usize) -> SavedMacro {
+        let mut events = Vec::with_capacity(moves + 8);
+        for i in 0..moves {
+            let x = 500 + ((i as f64 / 40.0).sin() * 300.0) as i32;
+            let y = 400 + ((i as f64 / 55.0).cos() * 200.0) as i32;
+            events.push(event(MacroEventType::MouseMove { x, y }, i as u128 * 8));
+            if i % 1000 == 999 {
+                let t = i as u128 * 8 + 4;
+                events.push(event(
+                    MacroEventType::MouseClick {
+                        button: Button::Left,
+                        pressed: true,
+                    },
+                    t,
+                ));
+                events.push(event(
+                    MacroEventType::MouseClick {
+                        button: Button::Left,
+                        pressed: false,
+                    },
+                    t + 1,
+                ));
+            }
+        }
+        let end = moves as u128 * 8;
+        events.push(event(MacroEventType::KeyPress { key: "Return".into() }, end));
+        events.push(event(MacroEventType::KeyRelease { key: "Return".into() }, end + 30));
+        events.push(event(MacroEventType::Delay { duration_ms: 500 }, end + 30));
+        let mut saved_macro = SavedMacro::new("recording", events);
+        saved_macro.tags = vec!["long".to_string()];
+        saved_macro
+    }
+
+    fn json(saved_macro: &SavedMacro) -> serde_json::Value {
+        serde_json::to_value(saved_macro).unwrap()
+    }
+
+    #[test]
+    fn round_trip_keeps_every_event() {
+        let mut saved_macro = recording(5000);
+        // 负坐标和乱序时间戳也必须还原
+        saved_macro.events.push(event(MacroEventType::MouseMove { x: -1920, y: -5 }, 10));
+        saved_macro.events.push(event(
+            MacroEventType::MouseClick {
+                button: Button::Middle,
+                pressed: true,
+            },
+            0,
+        ));
+
+        let bytes = compact::encode(&saved_macro).unwrap();
+        let decoded = compact::decode(&bytes).unwrap();
+        assert_eq!(json(&decoded), json(&saved_macro));
+
+        let plain = serde_json::to_vec(&saved_macro).unwrap();
+        assert!(bytes.len() * 10 < plain.len(), "{} vs {}", bytes.len(), plain.len());
+    }
+
+    #[test]
+    fn corrupt_files_are_rejected() {
+        assert!(compact::decode(b"{\"name\":\"x\"}").is_err());
+
+        let mut bytes = compact::encode(&recording(10)).unwrap();
+        bytes.truncate(bytes.len() / 2);
+        assert!(compact::decode(&bytes).is_err());
+
+        // 坐标差累加溢出时报错而不是 panic
+        let bytes = compact::encode(&SavedMacro::new("x", Vec::new())).unwrap();
+        let mut json = Vec::new();
+        GzDecoder::new(&bytes[..]).read_to_end(&mut json).unwrap();
+        let mut file: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        file["events"] = serde_json::json!([{ "M": [0, 1, 0] }, { "M": [0, i64::MAX, 0] }]);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&serde_json::to_vec(&file).unwrap()).unwrap();
+        let result = compact::decode(&encoder.finish().unwrap());
+        assert!(matches!(result, Err(MacroError::Format(e)) if e.contains("overflow")));
+    }
+
+    #[test]
+    fn huge_timestamps_are_not_truncated() {
+        let delay = MacroEventType::Delay { duration_ms: 1 };
+        let saved_macro = SavedMacro::new("x", vec![event(delay, u64::MAX as u128)]);
+        let result = compact::encode(&saved_macro);
+        assert!(matches!(result, Err(MacroError::Format(e)) if e.contains("timestamp")));
+    }
+
+    #[test]
+    fn manager_writes_default_format_and_converts() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        manager.set_default_format(StorageFormat::Compact);
+        manager.save(recording(100), ConflictPolicy::Error).unwrap();
+        assert!(dir.path().join("recording.mpz").exists());
+        assert_eq!(manager.storage_format("recording"), Some(StorageFormat::Compact));
+
+        // 修改后仍然是压缩格式, 重新打开可以读取
+        manager.edit_macro("recording", |m| m.description = "edited".to_string()).unwrap();
+        let reopened = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        let loaded = &reopened.get_macros(&["recording".to_string()])[0];
+        assert_eq!(loaded.description, "edited");
+        assert_eq!(loaded.events.len(), 103);
+
+        reopened.convert_macro("recording", StorageFormat::Json).unwrap();
+        assert!(dir.path().join("recording.json").exists());
+        assert!(!dir.path().join("recording.mpz").exists());
+        reopened.convert_macro("recording", StorageFormat::Text).unwrap();
+        assert!(dir.path().join("recording.mpm").exists());
+        assert!(!dir.path().join("recording.json").exists());
+
+        let reopened = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(reopened.storage_format("recording"), Some(StorageFormat::Text));
+        assert!(reopened.convert_macro("missing", StorageFormat::Json).is_err());
+    }
+
+    // 比较 JSON 和压缩格式的加载时间:
+    // cargo test --release --test compact -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn benchmark_load_time() {
+        const RUNS: u32 = 5;
+        let saved_macro = recording(200_000);
+        let dir = tempfile::tempdir().unwrap();
+        for format in [StorageFormat::Json, StorageFormat::Compact] {
+            let path = dir.path().join(format!("recording.{}", format.extension()));
+            let bytes = match format {
+                StorageFormat::Compact => compact::encode(&saved_macro).unwrap(),
+                _ => serde_json::to_vec(&saved_macro).unwrap(),
+            };
+            fs::write(&path, &bytes).unwrap();
+
+            let start = Instant::now();
+            for _ in 0..RUNS {
+                let loaded = match format {
+                    StorageFormat::Compact => compact::decode(&fs::read(&path).unwrap()).unwrap(),
+                    _ => {
+                        let file = BufReader::new(fs::File::open(&path).unwrap());
+                        SavedMacro::from_reader(file).unwrap()
+                    },
+                };
+                assert_eq!(loaded.events.len(), saved_macro.events.len());
+            }
+            println!(
+                "{:>8}: {:>9} bytes, {:?} per load",
+                format.name(),
+                bytes.len(),
+                start.elapsed() / RUNS
+            );
+        }
+    }
+}
@@ -0,0 +1,183 @@
+#[cfg(test)]
+mod tests {
+    use mousepilot::{
+        dsl::{self, ParseError},
+        event::{Button, MacroEvent, MacroEventType},
+        hotkey::Hotkey,
+        macro_manager::{ConflictPolicy, MacroManager, SavedMacro, ScreenInfo},
+    };
+    use std::fs;
+
+    const SAMPLE: &str = r#"# 登录脚本
+name "login"
+tag "work"
+
+move 100 200
+wait 250ms
+click left
+wait 1.5s
+mouse down right
+mouse up right
+key down LControl
+key A
+key up LControl
+delay 2s
+type "Hi!"
+"#;
+
+    fn json(events: &[MacroEvent]) -> serde_json::Value {
+        serde_json::to_value(events).unwrap()
+    }
+
+    #[test]
+    fn parse_produces_timed_events() {
+        let saved_macro = dsl::parse_macro(SAMPLE, "fallback").unwrap();
+        assert_eq!(saved_macro.name, "login");
+        assert_eq!(saved_macro.tags, ["work"]);
+
+        let events = &saved_macro.events;
+        assert!(matches!(events[0].event_type, MacroEventType::MouseMove { x: 100, y: 200 }));
+        assert_eq!(events[1].timestamp, 250);
+        assert!(matches!(events[1].event_type, MacroEventType::MouseClick { pressed: true, .. }));
+        assert_eq!(events[3].timestamp, 1750);
+        assert!(matches!(events[9].event_type, MacroEventType::Delay { duration_ms: 2000 }));
+
+        // "Hi!" 中的大写字母和 ! 需要按住 Shift
+        let typed: Vec<String> = events[10..]
+            .iter()
+            .map(|e| match &e.event_type {
+                MacroEventType::KeyPress { key } => format!("+{key}"),
+                MacroEventType::KeyRelease { key } => format!("-{key}"),
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            typed,
+            ["+LShift", "+H", "-H", "-LShift", "+I", "-I", "+LShift", "+Key1", "-Key1", "-LShift"]
+        );
+        assert_eq!(saved_macro.stats.duration_ms, 1750 + 2000);
+    }
+
+    #[test]
+    fn errors_point_at_line_and_column() {
+        let error = |text: &str| dsl::parse_events(text).unwrap_err();
+        assert_eq!(
+            error("move 1 2\nwait 10"),
+            ParseError {
+                line: 2,
+                column: 6,
+                message: "duration needs a unit (ms or s): 10".to_string()
+            }
+        );
+        assert_eq!(error("click  sideways").column, 8);
+        assert_eq!(error("move 1").column, 7);
+        assert_eq!(error("move 1 2 3").column, 10);
+        assert_eq!(error("\n\n  jump 1").to_string(), "line 3, column 3: unknown command `jump`");
+        assert_eq!(error(r#"type "ok€""#).column, 9);
+        assert_eq!(error(r#"type "open"#).message, "unterminated string");
+        let error = dsl::parse_macro("hotkey \"Ctrl+Nope\"", "x").unwrap_err();
+        assert_eq!(error.to_string(), "line 1, column 8: unknown key: Nope");
+        let error = dsl::parse_macro("hotkey \"A\"", "x").unwrap_err();
+        assert_eq!(error.to_string(), "line 1, column 8: hotkey is reserved: A");
+    }
+
+    #[test]
+    fn printer_round_trips() {
+        let event = |event_type, timestamp| MacroEvent {
+            event_type,
+            timestamp,
+        };
+        let key = |key: &str| key.to_string();
+        let events = vec![
+            event(MacroEventType::MouseMove { x: -5, y: 7 }, 0),
+            event(
+                MacroEventType::MouseClick {
+                    button: Button::Middle,
+                    pressed: true,
+                },
+                3000,
+            ),
+            event(
+                MacroEventType::MouseClick {
+                    button: Button::Middle,
+                    pressed: false,
+                },
+                3000,
+            ),
+            event(
+                MacroEventType::MouseClick {
+                    button: Button::Left,
+                    pressed: true,
+                },
+                3010,
+            ),
+            event(
+                MacroEventType::MouseClick {
+                    button: Button::Left,
+                    pressed: false,
+                },
+                3050,
+            ),
+            event(MacroEventType::KeyPress { key: key("down") }, 3050),
+            event(MacroEventType::KeyRelease { key: key("down") }, 3050),
+            event(
+                MacroEventType::KeyPress {
+                    key: key("Odd Key"),
+                },
+                3060,
+            ),
+            event(MacroEventType::Delay { duration_ms: 125 }, 3060),
+            event(
+                MacroEventType::KeyRelease {
+                    key: key("Odd Key"),
+                },
+                3070,
+            ),
+        ];
+        let mut original = SavedMacro::new("round \"trip\"", events);
+        original.description = "line one\nline two".to_string();
+        original.hotkey = Some(Hotkey::parse("Ctrl+K, Ctrl+Alt+F9").unwrap());
+        original.screen = Some(ScreenInfo {
+            width: 2560,
+            height: 1440,
+            scale: 1.25,
+        });
+
+        let text = dsl::print_macro(&original);
+        assert!(text.contains("\nclick middle\n"));
+        assert!(text.contains("\nkey \"down\"\n"));
+        let parsed = dsl::parse_macro(&text, "").unwrap();
+        assert_eq!(json(&parsed.events), json(&original.events));
+        assert_eq!(parsed.name, original.name);
+        assert_eq!(parsed.description, original.description);
+        assert_eq!(parsed.created_at, original.created_at);
+        assert_eq!(parsed.screen, original.screen);
+        assert_eq!(parsed.hotkey, original.hotkey);
+        assert_eq!(dsl::print_macro(&parsed), text);
+    }
+
+    #[test]
+    fn manager_loads_and_keeps_text_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("login.mpm"), SAMPLE).unwrap();
+        fs::write(dir.path().join("unnamed.mpm"), "move 1 1\n").unwrap();
+        fs::write(dir.path().join("broken.mpm"), "move 1 1\nwait soon\n").unwrap();
+        fs::write(dir.path().join("json.json"), r#"{"name":"json","events":[],"created_at":0}"#)
+            .unwrap();
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(manager.get_macro_names(), ["json", "login", "unnamed"]);
+        let report = manager.get_load_report();
+        assert!(report.failures[0].error.contains("line 2, column 6"));
+
+        // 修改和改名后仍然保存为文本格式
+        manager.edit_macro("login", |m| m.description = "edited".to_string()).unwrap();
+        manager.rename_macro("login", "sign in", ConflictPolicy::Error).unwrap();
+        let text = fs::read_to_string(dir.path().join("sign in.mpm")).unwrap();
+        assert!(text.starts_with("name \"sign in\"\ndescription \"edited\"\n"));
+        assert!(!dir.path().join("sign in.json").exists());
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(manager.get_macros(&["sign in".to_string()])[0].description, "edited");
+    }
+}
@@ -0,0 +1,46 @@
+# Exported from mousepilot: golden demo
+# Recorded on a 1920x1080 screen
+import time
+
+import pyautogui
+
+pyautogui.PAUSE = 0
+
+pyautogui.moveTo(100, 200)
+time.sleep(0.050)
+pyautogui.mouseDown(button="left")
+time.sleep(0.070)
+pyautogui.mouseUp(button="left")
+time.sleep(0.280)
+pyautogui.mouseDown(button="right")
+time.sleep(0.050)
+pyautogui.mouseUp(button="right")
+time.sleep(0.050)
+pyautogui.mouseDown(button="middle")
+time.sleep(0.020)
+pyautogui.mouseUp(button="middle")
+time.sleep(2.000)
+time.sleep(0.080)
+pyautogui.keyDown("shiftleft")
+time.sleep(0.010)
+pyautogui.keyDown("a")
+time.sleep(0.050)
+pyautogui.keyUp("a")
+time.sleep(0.010)
+pyautogui.keyUp("shiftleft")
+time.sleep(0.030)
+pyautogui.keyDown("`")
+time.sleep(0.020)
+pyautogui.keyUp("`")
+time.sleep(0.080)
+pyautogui.keyDown("\\")
+time.sleep(0.020)
+pyautogui.keyUp("\\")
+time.sleep(0.680)
+pyautogui.keyDown("enter")
+time.sleep(0.040)
+pyautogui.keyUp("enter")
+time.sleep(0.060)
+# unsupported key: keyDown MediaPlay
+time.sleep(0.020)
+# unsupported key: keyUp MediaPlay
@@ -0,0 +1,43 @@
+#!/usr/bin/env bash
+# Exported from mousepilot: golden demo
+# Recorded on a 1920x1080 screen
+set -e
+
+xdotool mousemove 100 200
+sleep 0.050
+xdotool mousedown 1
+sleep 0.070
+xdotool mouseup 1
+sleep 0.280
+xdotool mousedown 3
+sleep 0.050
+xdotool mouseup 3
+sleep 0.050
+xdotool mousedown 2
+sleep 0.020
+xdotool mouseup 2
+sleep 2.000
+sleep 0.080
+xdotool keydown Shift_L
+sleep 0.010
+xdotool keydown a
+sleep 0.050
+xdotool keyup a
+sleep 0.010
+xdotool keyup Shift_L
+sleep 0.030
+xdotool keydown grave
+sleep 0.020
+xdotool keyup grave
+sleep 0.080
+xdotool keydown backslash
+sleep 0.020
+xdotool keyup backslash
+sleep 0.680
+xdotool keydown Return
+sleep 0.040
+xdotool keyup Return
+sleep 0.060
+# unsupported key: keydown MediaPlay
+sleep 0.020
+# unsupported key: keyup MediaPlay
@@ -0,0 +1,521 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common::{self, delay};
+    use device_query::Keycode;
+    use eframe::egui;
+    use mousepilot::{
+        event::MacroEventType,
+        hotkey::{self, BindingError, Hotkey, HotkeyDetector, KeyCombo, MouseChord, Shortcut},
+        macro_manager::{ConflictPolicy, MacroError, MacroManager},
+        settings::Settings,
+        state::AppState,
+    };
+    use std::{
+        collections::BTreeMap,
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    fn combo(text: &str) -> KeyCombo {
+        KeyCombo::parse(text).unwrap()
+    }
+
+    fn parse(text: &str) -> Hotkey {
+        Hotkey::parse(text).unwrap()
+    }
+
+    fn sample(dir: &Path) -> MacroManager {
+        let manager = MacroManager::open(dir.to_string_lossy()).unwrap();
+        manager.save_macro("login", vec![delay(10)], ConflictPolicy::Error).unwrap();
+        manager.save_macro("logout", vec![delay(10)], ConflictPolicy::Error).unwrap();
+        manager
+    }
+
+    fn app_state(dir: &Path) -> AppState {
+        common::app_state(dir, &[]).with_settings_file(dir.join("settings.json"))
+    }
+
+    #[test]
+    fn parse_and_display_key_combo() {
+        let hotkey = combo("ctrl + alt+F9");
+        assert_eq!(hotkey.key, egui::Key::F9);
+        assert!(hotkey.ctrl && hotkey.alt && !hotkey.shift);
+        assert_eq!(hotkey.to_string(), "Ctrl+Alt+F9");
+        assert_eq!(combo("Shift+a"), combo("Shift+A"));
+
+        assert!(KeyCombo::parse("").is_err());
+        assert!(KeyCombo::parse("Ctrl+").is_err());
+        assert!(KeyCombo::parse("Hyper+F1").is_err());
+        assert!(KeyCombo::parse("Ctrl+Ctrl+F1").is_err());
+
+        let json = serde_json::to_string(&hotkey).unwrap();
+        assert_eq!(json, r#""Ctrl+Alt+F9""#);
+        assert_eq!(serde_json::from_str::<KeyCombo>(&json).unwrap(), hotkey);
+        assert!(serde_json::from_str::<KeyCombo>(r#""Ctrl+Nope""#).is_err());
+    }
+
+    #[test]
+    fn key_combo_matches_pressed_keys() {
+        let hotkey = combo("Ctrl+F9");
+        assert!(hotkey.matches_keycode(&egui::Key::F9, &[Keycode::RControl, Keycode::F9]));
+        assert!(!hotkey.matches_keycode(&egui::Key::F9, &[Keycode::F9]));
+        assert!(!hotkey.matches_keycode(&egui::Key::F8, &[Keycode::LControl, Keycode::F8]));
+
+        let shortcut = Shortcut::new("stop", egui::Key::F4, false, false, false, "停止", false);
+        assert_eq!(shortcut.hotkey, parse("F4"));
+    }
+
+    #[test]
+    fn macro_hotkeys_are_saved_and_unique() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = sample(dir.path());
+
+        manager.set_hotkey("login", Some(parse("Ctrl+F9"))).unwrap();
+        assert!(matches!(
+            manager.set_hotkey("logout", Some(parse("Ctrl+F9"))),
+            Err(MacroError::HotkeyConflict(_))
+        ));
+        // 重新设置自己的快捷键不算冲突
+        manager.set_hotkey("login", Some(parse("Ctrl+F9"))).unwrap();
+        // 正常打字的按键不能作为宏的快捷键
+        for reserved in ["A", "Shift+A", "Escape", "MouseLeft"] {
+            assert!(matches!(
+                manager.set_hotkey("logout", Some(parse(reserved))),
+                Err(MacroError::ReservedHotkey(_))
+            ));
+        }
+        manager.set_hotkey("logout", Some(parse("Ctrl+F10"))).unwrap();
+        assert!(matches!(manager.set_hotkey("missing", None), Err(MacroError::NotFound(_))));
+
+        let reopened = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        let mut hotkeys = reopened.macro_hotkeys();
+        hotkeys.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            hotkeys,
+            [("login".to_string(), parse("Ctrl+F9")), ("logout".to_string(), parse("Ctrl+F10"))]
+        );
+
+        reopened.set_hotkey("login", None).unwrap();
+        assert_eq!(reopened.macro_hotkeys().len(), 1);
+        assert!(reopened.get_macro("login").unwrap().hotkey.is_none());
+    }
+
+    #[test]
+    fn concurrent_hotkey_assignments_do_not_both_succeed() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = sample(dir.path());
+        for i in 0..20 {
+            let hotkey = parse(&format!("Ctrl+Alt+F{}", i % 12 + 1));
+            let results = std::thread::scope(|scope| {
+                let handles = ["login", "logout"].map(|name| {
+                    let (manager, hotkey) = (&manager, hotkey.clone());
+                    scope.spawn(move || manager.set_hotkey(name, Some(hotkey)))
+                });
+                handles.map(|h| h.join().unwrap())
+            });
+            assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+            assert_eq!(manager.macro_hotkeys().len(), 1);
+            manager.set_hotkey("login", None).unwrap();
+            manager.set_hotkey("logout", None).unwrap();
+        }
+    }
+
+    #[test]
+    fn macro_hotkeys_cannot_shadow_shortcuts() {
+        let dir = tempfile::tempdir().unwrap();
+        sample(dir.path());
+        let state = app_state(dir.path());
+
+        let builtin = state.shortcuts().iter().find(|s| s.name == "stop").unwrap().hotkey.clone();
+        assert!(matches!(
+            state.set_macro_hotkey("login", Some(builtin)),
+            Err(MacroError::HotkeyConflict(_))
+        ));
+        assert!(matches!(
+            state.set_macro_hotkey("login", Some(parse("A"))),
+            Err(MacroError::ReservedHotkey(_))
+        ));
+        state.set_macro_hotkey("login", Some(parse("Alt+F9"))).unwrap();
+        assert!(state.check_macro_hotkey("logout", &parse("Alt+F9")).is_err());
+        assert!(state.check_macro_hotkey("login", &parse("Alt+F9")).is_ok());
+    }
+
+    #[test]
+    fn reserved_and_conflicting_bindings() {
+        assert!(combo("Escape").is_reserved());
+        assert!(combo("Ctrl+Escape").is_reserved());
+        assert!(combo("A").is_reserved());
+        assert!(combo("Shift+A").is_reserved());
+        assert!(!combo("F10").is_reserved());
+        assert!(!combo("Alt+A").is_reserved());
+
+        let shortcuts = AppState::default_shortcuts();
+        assert!(hotkey::check_binding(&shortcuts, "stop", &parse("Ctrl+F12")).is_ok());
+        // 改回自己当前的按键不算冲突
+        assert!(hotkey::check_binding(&shortcuts, "stop", &parse("F4")).is_ok());
+        assert!(matches!(
+            hotkey::check_binding(&shortcuts, "stop", &parse("F5")),
+            Err(BindingError::Conflict(_))
+        ));
+        assert!(matches!(
+            hotkey::check_binding(&shortcuts, "stop", &parse("Q")),
+            Err(BindingError::Reserved(_))
+        ));
+        assert!(matches!(
+            hotkey::check_binding(&shortcuts, "fly", &parse("F12")),
+            Err(BindingError::UnknownShortcut(_))
+        ));
+
+        // 无效的绑定被忽略, 保留默认按键
+        let bindings = BTreeMap::from([
+            ("stop".to_string(), parse("Ctrl+F12")),
+            ("start_recording".to_string(), parse("F4")),
+            ("fly".to_string(), parse("F11")),
+        ]);
+        let loaded = AppState::load_shortcuts(&bindings);
+        let find = |name: &str| loaded.iter().find(|s| s.name == name).unwrap().hotkey.clone();
+        assert_eq!(find("stop"), parse("Ctrl+F12"));
+        assert_eq!(find("start_recording"), parse("F5"));
+        assert_eq!(loaded.len(), shortcuts.len());
+    }
+
+    #[test]
+    fn rebind_shortcut_takes_effect_and_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        sample(dir.path());
+        let state = app_state(dir.path());
+        let f4 = [Keycode::F4];
+        assert!(state.recorder.is_hotkey(&Keycode::F4, &f4));
+
+        state.rebind_shortcut("stop", parse("Ctrl+F12")).unwrap();
+        // 录制器不需要重启就使用新按键
+        assert!(!state.recorder.is_hotkey(&Keycode::F4, &f4));
+        assert!(state.recorder.is_hotkey(&Keycode::F12, &[Keycode::LControl, Keycode::F12]));
+        assert!(!state.recorder.is_hotkey(&Keycode::A, &[Keycode::A]));
+
+        assert!(matches!(
+            state.rebind_shortcut("stop", parse("F5")),
+            Err(BindingError::Conflict(_))
+        ));
+        state.set_macro_hotkey("login", Some(parse("Alt+F9"))).unwrap();
+        assert!(matches!(
+            state.rebind_shortcut("stop", parse("Alt+F9")),
+            Err(BindingError::Conflict(_))
+        ));
+
+        let saved = Settings::load_from(&dir.path().join("settings.json")).unwrap();
+        assert_eq!(saved.shortcuts["stop"], parse("Ctrl+F12"));
+        let reopened = app_state(dir.path());
+        let stop = reopened.shortcuts().iter().find(|s| s.name == "stop").unwrap().hotkey.clone();
+        assert_eq!(stop, parse("Ctrl+F12"));
+
+        // 改回默认按键时不再保存
+        state.rebind_shortcut("stop", parse("F4")).unwrap();
+        state.rebind_shortcut("play_once", parse("F10")).unwrap();
+        let saved = Settings::load_from(&dir.path().join("settings.json")).unwrap();
+        assert_eq!(saved.shortcuts.keys().collect::<Vec<_>>(), ["play_once"]);
+        state.reset_shortcuts();
+        let saved = Settings::load_from(&dir.path().join("settings.json")).unwrap();
+        assert!(saved.shortcuts.is_empty());
+        assert!(state.recorder.is_hotkey(&Keycode::F7, &[Keycode::F7]));
+    }
+
+    /// 依次输入按键状态, 返回每一步触发的绑定
+    fn run(
+        detector: &mut HotkeyDetector, steps: &[&[Keycode]], step: Duration,
+    ) -> Vec<Vec<&'static str>> {
+        let bindings = || {
+            vec![
+                (parse("F7"), "play"),
+                (parse("Ctrl+Delete"), "clear"),
+                (parse("Ctrl+Shift+Delete"), "clear_all"),
+            ]
+        };
+        let start = Instant::now();
+        steps
+            .iter()
+            .enumerate()
+            .map(|(i, keys)| detector.update(keys, &[], start + step * i as u32, bindings))
+            .collect()
+    }
+
+    #[test]
+    fn hotkeys_fire_on_press_edge_with_exact_modifiers() {
+        use Keycode::*;
+        let mut detector = HotkeyDetector::default();
+        let fired = run(
+            &mut detector,
+            &[
+                &[F7],
+                // 按住 F7 时按下再松开其他键不会重复触发
+                &[F7],
+                &[F7, A],
+                &[F7],
+                &[],
+                &[F7],
+                &[LControl],
+                &[LControl, Delete],
+                &[LControl, LShift, Delete],
+                &[LControl, Delete],
+                &[],
+                &[RControl, LShift, Delete],
+                // 先按主键再按修饰键不触发
+                &[Delete],
+                &[Delete, LControl],
+            ],
+            Duration::from_millis(30),
+        );
+        let expected: Vec<Vec<&str>> = vec![
+            vec!["play"],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec!["play"],
+            vec![],
+            vec!["clear"],
+            vec![],
+            vec![],
+            vec![],
+            vec!["clear_all"],
+            vec![],
+            vec![],
+        ];
+        assert_eq!(fired, expected);
+
+        let hotkey = combo("Ctrl+Delete");
+        assert!(!hotkey.matches_keycode(&egui::Key::Delete, &[LControl, LShift, Delete]));
+        assert!(!hotkey.matches_keycode(&egui::Key::Delete, &[LControl, RAlt, Delete]));
+        let modifiers = egui::Modifiers {
+            ctrl: true,
+            shift: true,
+            ..Default::default()
+        };
+        let select_all = Shortcut::new("all", egui::Key::A, true, false, false, "全选", true);
+        assert!(select_all.matches(egui::Key::A, &egui::Modifiers::CTRL));
+        assert!(!select_all.matches(egui::Key::A, &modifiers));
+    }
+
+    #[test]
+    fn debounce_ignores_quick_repeats() {
+        use Keycode::*;
+        let mut detector = HotkeyDetector::new(Duration::from_millis(100));
+        let steps: &[&[Keycode]] = &[&[F7], &[], &[F7], &[], &[F7], &[], &[F7]];
+        let fired = run(&mut detector, steps, Duration::from_millis(30));
+        let count: Vec<usize> = fired.iter().map(Vec::len).collect();
+        // 每步 30ms, 距上次触发不到 100ms 的按下被忽略
+        assert_eq!(count, [1, 0, 0, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn parse_sequences_double_taps_and_mouse_chords() {
+        let cases = [
+            ("Ctrl+K, Ctrl+C", "Ctrl+K, Ctrl+C"),
+            ("double + shift+F9", "Double+Shift+F9"),
+            ("mouseright+MouseMiddle", "MouseMiddle+MouseRight"),
+            ("Ctrl+MouseLeft", "Ctrl+MouseLeft"),
+            ("Super+Alt+F2", "Alt+Meta+F2"),
+            ("Cmd+Q", "Meta+Q"),
+        ];
+        for (text, display) in cases {
+            let parsed = parse(text);
+            assert_eq!(parsed.to_string(), display);
+            assert_eq!(parse(display), parsed);
+            let json = serde_json::to_string(&parsed).unwrap();
+            assert_eq!(serde_json::from_str::<Hotkey>(&json).unwrap(), parsed);
+        }
+        assert!(matches!(parse("Ctrl+K, C"), Hotkey::Sequence(steps) if steps.len() == 2));
+        assert_eq!(parse("Double+F9"), Hotkey::DoubleTap(combo("F9")));
+        assert!(matches!(parse("F9"), Hotkey::Key(_)));
+
+        for bad in ["Ctrl+K,", "F1, F2, F3, F4, F5", "Double+", "MouseLeft+MouseLeft", "Mouse6"] {
+            assert!(Hotkey::parse(bad).is_err(), "{bad}");
+        }
+        assert!(serde_json::from_str::<Hotkey>(r#""MouseLeft+F1""#).is_err());
+
+        assert!(parse("MouseMiddle").is_reserved());
+        assert!(!parse("MouseMiddle+MouseRight").is_reserved());
+        assert!(!parse("Shift+MouseMiddle").is_reserved());
+        // 序列中的每一步都要满足保留规则
+        assert!(parse("Ctrl+K, C").is_reserved());
+        assert!(!parse("Ctrl+K, Ctrl+C").is_reserved());
+        assert!(parse("Double+A").is_reserved());
+        assert!(!parse("Meta+A").is_reserved());
+    }
+
+    #[test]
+    fn conflicts_include_prefixes() {
+        assert!(parse("Ctrl+K").conflicts_with(&parse("Ctrl+K, Ctrl+C")));
+        assert!(parse("Ctrl+K, Ctrl+C, Ctrl+D").conflicts_with(&parse("Ctrl+K, Ctrl+C")));
+        assert!(!parse("Ctrl+K, Ctrl+C").conflicts_with(&parse("Ctrl+K, Ctrl+D")));
+        assert!(parse("F9").conflicts_with(&parse("Double+F9")));
+        assert!(parse("Double+F9").conflicts_with(&parse("F9, F9")));
+        assert!(!parse("Double+F9").conflicts_with(&parse("F9, F10")));
+        assert!(parse("MouseMiddle+MouseRight").conflicts_with(&parse("MouseRight+MouseMiddle")));
+        assert!(!parse("MouseMiddle+MouseRight").conflicts_with(&parse("Ctrl+MouseMiddle")));
+
+        let shortcuts = AppState::default_shortcuts();
+        assert!(matches!(
+            hotkey::check_binding(&shortcuts, "stop", &parse("Double+F5")),
+            Err(BindingError::Conflict(_))
+        ));
+        assert!(hotkey::check_binding(&shortcuts, "stop", &parse("Ctrl+K, Ctrl+S")).is_ok());
+        assert!(hotkey::check_binding(&shortcuts, "stop", &parse("MouseLeft+MouseRight")).is_ok());
+        // 窗口内快捷键只能是组合键
+        assert!(matches!(
+            hotkey::check_binding(&shortcuts, "help", &parse("Double+F1")),
+            Err(BindingError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn sequences_and_double_taps_fire_within_timeout() {
+        use Keycode::*;
+        let bindings = || {
+            vec![
+                (parse("Ctrl+K, Ctrl+C"), "comment"),
+                (parse("Double+F9"), "double"),
+                (parse("Meta+F2"), "meta"),
+            ]
+        };
+        let start = Instant::now();
+        let mut detector = HotkeyDetector::default();
+        let mut step = |keys: &[Keycode], ms: u64| {
+            detector.update(keys, &[], start + Duration::from_millis(ms), bindings)
+        };
+        let none: Vec<&str> = Vec::new();
+
+        assert_eq!(step(&[LControl, K], 0), none);
+        assert_eq!(step(&[LControl], 100), none);
+        assert_eq!(step(&[LControl, C], 500), ["comment"]);
+        assert_eq!(step(&[], 600), none);
+        // 超时后重新开始
+        assert_eq!(step(&[LControl, K], 1000), none);
+        assert_eq!(step(&[LControl], 1100), none);
+        assert_eq!(step(&[LControl, C], 2200), none);
+        // 中间按了其他键
+        assert_eq!(step(&[LControl, K], 3000), none);
+        assert_eq!(step(&[LControl, X], 3100), none);
+        assert_eq!(step(&[LControl, C], 3200), none);
+        assert_eq!(step(&[], 3300), none);
+
+        assert_eq!(step(&[F9], 4000), none);
+        assert_eq!(step(&[], 4100), none);
+        assert_eq!(step(&[F9], 4200), ["double"]);
+        assert_eq!(step(&[], 4300), none);
+        // 连按第三次不再触发
+        assert_eq!(step(&[F9], 4400), none);
+        assert_eq!(step(&[], 4500), none);
+        assert_eq!(step(&[F9], 5500), none);
+        assert_eq!(step(&[], 5600), none);
+        // 两次间隔太长
+        assert_eq!(step(&[F9], 6100), none);
+        assert_eq!(step(&[], 6200), none);
+
+        assert_eq!(step(&[LMeta, F2], 7000), ["meta"]);
+        assert_eq!(step(&[], 7100), none);
+        assert_eq!(step(&[F2], 7200), none);
+        assert_eq!(step(&[LMeta, LShift, F2], 7300), none);
+    }
+
+    #[test]
+    fn mouse_chords_fire_on_press_edge() {
+        use mousepilot::event::Button::*;
+        let bindings =
+            || vec![(parse("MouseMiddle+MouseRight"), "chord"), (parse("Ctrl+MouseLeft"), "ctrl")];
+        let now = Instant::now();
+        let mut detector = HotkeyDetector::default();
+        let mut step = |keys: &[Keycode], buttons: &[mousepilot::event::Button]| {
+            detector.update(keys, buttons, now, bindings)
+        };
+        let none: Vec<&str> = Vec::new();
+
+        assert_eq!(step(&[], &[Middle]), none);
+        assert_eq!(step(&[], &[Middle, Right]), ["chord"]);
+        // 按住不重复触发, 多按一个键也不触发
+        assert_eq!(step(&[], &[Middle, Right]), none);
+        assert_eq!(step(&[], &[Left, Middle, Right]), none);
+        assert_eq!(step(&[], &[]), none);
+        assert_eq!(step(&[Keycode::LShift], &[Middle, Right]), none);
+        assert_eq!(step(&[], &[]), none);
+        assert_eq!(step(&[Keycode::RControl], &[Left]), ["ctrl"]);
+        assert_eq!(step(&[], &[]), none);
+        assert_eq!(step(&[], &[Left]), none);
+
+        assert_eq!(hotkey::pressed_buttons(&[false, true, false, true]), [Left, Middle]);
+        let chord = MouseChord::new(vec![Right, Middle, Right]);
+        assert_eq!(chord.buttons, [Middle, Right]);
+        assert!(chord.matches(&[Right, Middle], &[]));
+        assert!(!chord.matches(&[Right, Middle], &[Keycode::LAlt]));
+    }
+
+    #[test]
+    fn extended_hotkeys_as_shortcuts_and_macro_triggers() {
+        let dir = tempfile::tempdir().unwrap();
+        sample(dir.path());
+        let state = app_state(dir.path());
+
+        state.rebind_shortcut("stop", parse("Ctrl+K, Ctrl+S")).unwrap();
+        state.rebind_shortcut("play_once", parse("MouseMiddle+MouseRight")).unwrap();
+        // 序列中的每一步都不录制
+        let ctrl_k = [Keycode::LControl, Keycode::K];
+        assert!(state.recorder.is_hotkey(&Keycode::K, &ctrl_k));
+        assert!(state.recorder.is_hotkey(&Keycode::S, &[Keycode::LControl, Keycode::S]));
+        assert!(!state.recorder.is_hotkey(&Keycode::K, &[Keycode::K]));
+        use mousepilot::event::Button::*;
+        assert!(state.recorder.is_mouse_hotkey(&[Middle, Right], &[]));
+        assert!(!state.recorder.is_mouse_hotkey(&[Middle], &[]));
+
+        // 先按中键再按右键: 中键的按下和松开都录制, 组成快捷键的右键都不录制
+        let mut swallowed = Vec::new();
+        let steps: [[bool; 4]; 5] = [
+            [false; 4],
+            [false, false, false, true],
+            [false, false, true, true],
+            [false, false, false, true],
+            [false; 4],
+        ];
+        for pair in steps.windows(2) {
+            state.recorder.record_buttons(&pair[0], &pair[1], &[], &mut swallowed);
+        }
+        // 同时按下的两个键都不录制
+        state.recorder.record_buttons(&steps[0], &steps[2], &[], &mut swallowed);
+        state.recorder.record_buttons(&steps[2], &steps[0], &[], &mut swallowed);
+        let clicks: Vec<_> = state
+            .recorder
+            .get_events()
+            .into_iter()
+            .filter_map(|e| match e.event_type {
+                MacroEventType::MouseClick { button, pressed } => Some((button, pressed)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(clicks, [(Middle, true), (Middle, false)]);
+        assert!(swallowed.is_empty());
+
+        // 宏快捷键不能以内置序列开头, 也不能是它的前缀
+        assert!(state.set_macro_hotkey("login", Some(parse("Ctrl+K"))).is_err());
+        assert!(state.set_macro_hotkey("login", Some(parse("MouseRight+MouseMiddle"))).is_err());
+        state.set_macro_hotkey("login", Some(parse("Ctrl+K, Ctrl+L"))).unwrap();
+        state.set_macro_hotkey("logout", Some(parse("Double+F9"))).unwrap();
+        assert!(matches!(
+            state.rebind_shortcut("play_multiple", parse("F9")),
+            Err(BindingError::Conflict(_))
+        ));
+
+        let reopened = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        let mut hotkeys = reopened.macro_hotkeys();
+        hotkeys.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            hotkeys,
+            [
+                ("login".to_string(), parse("Ctrl+K, Ctrl+L")),
+                ("logout".to_string(), parse("Double+F9"))
+            ]
+        );
+        let saved = Settings::load_from(&dir.path().join("settings.json")).unwrap();
+        assert_eq!(saved.shortcuts["play_once"], parse("MouseMiddle+MouseRight"));
+    }
+}
@@ -0,0 +1,171 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mousepilot::{
+        http::HttpServer,
+        settings::{HttpSettings, Settings},
+        state::AppState,
+    };
+    use serde_json::{Value, json};
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream},
+        path::Path,
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+    use tungstenite::Message;
+
+    const TOKEN: &str = "secret";
+
+    fn start(dir: &Path) -> (Arc<AppState>, HttpServer) {
+        let state = Arc::new(common::app_state(dir, &[("短宏", 10), ("long", 5000)]));
+        let config = HttpSettings {
+            enabled: true,
+            port: 0,
+            token: TOKEN.to_string(),
+        };
+        let server = HttpServer::start(state.clone(), &config).unwrap();
+        (state, server)
+    }
+
+    /// 发送一个请求, 返回状态码和 JSON 响应
+    fn request(addr: SocketAddr, method: &str, path: &str, body: Option<&str>) -> (u16, Value) {
+        request_with_token(addr, method, path, body, Some(TOKEN))
+    }
+
+    fn request_with_token(
+        addr: SocketAddr, method: &str, path: &str, body: Option<&str>, token: Option<&str>,
+    ) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let body = body.unwrap_or_default();
+        let auth = token.map(|t| format!("Authorization: Bearer {t}\r\n")).unwrap_or_default();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\n{auth}Content-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn requests_need_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_state, server) = start(dir.path());
+        let addr = server.local_addr();
+        assert!(addr.ip().is_loopback());
+
+        assert_eq!(request_with_token(addr, "GET", "/api/status", None, None).0, 401);
+        assert_eq!(request_with_token(addr, "GET", "/api/status", None, Some("guess")).0, 401);
+        assert_eq!(request_with_token(addr, "GET", "/api/status?token=secret", None, None).0, 200);
+
+        // 没有令牌时不启动
+        let state = Arc::new(common::app_state(dir.path(), &[]));
+        assert!(HttpServer::start(state, &HttpSettings::default()).is_err());
+    }
+
+    #[test]
+    fn rest_endpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let (state, server) = start(dir.path());
+        let addr = server.local_addr();
+
+        let (status, macros) = request(addr, "GET", "/api/macros", None);
+        assert_eq!(status, 200);
+        let names: Vec<&str> =
+            macros.as_array().unwrap().iter().map(|m| m["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["long", "短宏"]);
+
+        // 路径中的名称需要 URL 编码
+        let (status, saved) = request(addr, "GET", "/api/macros/%E7%9F%AD%E5%AE%8F", None);
+        assert_eq!(status, 200);
+        assert_eq!(saved["events"].as_array().unwrap().len(), 1);
+        assert_eq!(request(addr, "GET", "/api/macros/missing", None).0, 404);
+        assert_eq!(request(addr, "POST", "/api/macros/missing/play", None).0, 404);
+        assert_eq!(request(addr, "GET", "/api/nothing", None).0, 404);
+        assert_eq!(request(addr, "POST", "/api/play", Some("{oops")).0, 400);
+        assert_eq!(request(addr, "POST", "/api/play", Some(r#"{"names":["missing"]}"#)).0, 409);
+
+        let (status, body) = request(addr, "POST", "/api/macros/long/play", Some(r#"{"speed":2}"#));
+        assert_eq!(status, 200);
+        assert_eq!(body["playback"]["is_playing"], true);
+        assert_eq!(request(addr, "POST", "/api/recording/start", None).0, 409);
+        let (_, body) = request(addr, "POST", "/api/pause", None);
+        assert_eq!(body["playback"]["is_paused"], true);
+        request(addr, "POST", "/api/resume", None);
+        request(addr, "POST", "/api/stop", None);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while state.is_playing() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let (_, body) = request(addr, "GET", "/api/status", None);
+        assert_eq!(body["playback"]["is_playing"], false);
+
+        state.recorder.add_delay(40);
+        let (status, events) = request(addr, "GET", "/api/recording/events", None);
+        assert_eq!(status, 200);
+        assert_eq!(events[0]["event_type"], json!({ "Delay": { "duration_ms": 40 } }));
+    }
+
+    #[test]
+    fn websocket_streams_status_and_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let (state, mut server) = start(dir.path());
+        let addr = server.local_addr();
+
+        let unauthorized = TcpStream::connect(addr).unwrap();
+        assert!(tungstenite::client(format!("ws://{addr}/api/ws"), unauthorized).is_err());
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let (mut socket, _) =
+            tungstenite::client(format!("ws://{addr}/api/ws?token={TOKEN}"), stream).unwrap();
+        let mut next = move || -> Value {
+            match socket.read().unwrap() {
+                Message::Text(text) => serde_json::from_str(&text).unwrap(),
+                other => panic!("unexpected message: {other:?}"),
+            }
+        };
+
+        // 连接后先推送当前状态
+        let message = next();
+        assert_eq!(message["type"], "status");
+        assert_eq!(message["status"]["playback"]["is_playing"], false);
+
+        state.recorder.add_delay(20);
+        state.recorder.add_delay(30);
+        let mut events = 0;
+        while events < 2 {
+            let message = next();
+            if message["type"] == "events" {
+                events += message["events"].as_array().unwrap().len();
+            }
+        }
+        assert_eq!(events, 2);
+
+        request(addr, "POST", "/api/play", Some(r#"{"names":["long"]}"#));
+        while next()["status"]["playback"]["is_playing"] != true {}
+        request(addr, "POST", "/api/stop", None);
+
+        // 服务停止后不再接受连接
+        server.stop();
+        assert!(TcpStream::connect(addr).is_err());
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let settings: Settings = serde_json::from_str("{}").unwrap();
+        assert!(!settings.http.enabled);
+        let token = HttpSettings::generate_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, HttpSettings::generate_token().unwrap());
+    }
+}
@@ -0,0 +1,162 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use super::common;
+    use mousepilot::{
+        event::{MacroEvent, MacroEventType},
+        ipc::{self, ControlStatus, IpcClient, IpcServer, MacroSummary, RpcError},
+        state::AppState,
+    };
+    use serde_json::{Value, json};
+    use std::{
+        fs,
+        io::{self, BufRead, BufReader, Write},
+        os::unix::{
+            fs::{DirBuilderExt, PermissionsExt},
+            net::UnixStream,
+        },
+        path::{Path, PathBuf},
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    fn app_state(dir: &Path) -> Arc<AppState> {
+        Arc::new(common::app_state(dir, &[("short", 10), ("long", 5000)]))
+    }
+
+    /// 私有目录中的 socket 路径, 控制接口拒绝其他用户可以访问的目录
+    fn socket_path(dir: &Path) -> PathBuf {
+        let run = dir.join("run");
+        fs::DirBuilder::new().mode(0o700).create(&run).unwrap();
+        run.join("control.sock")
+    }
+
+    fn rpc_code(result: anyhow::Result<Value>) -> i64 {
+        result.unwrap_err().downcast::<RpcError>().unwrap().code
+    }
+
+    #[test]
+    fn handle_line_follows_json_rpc() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = app_state(dir.path());
+        let response = |line: &str| -> Value {
+            serde_json::from_str(&ipc::handle_line(&state, line).unwrap()).unwrap()
+        };
+
+        let ok = response(r#"{"jsonrpc":"2.0","id":"a","method":"status"}"#);
+        assert_eq!(ok["id"], "a");
+        assert_eq!(ok["result"]["is_recording"], false);
+
+        assert_eq!(response("{not json")["error"]["code"], ipc::PARSE_ERROR);
+        assert_eq!(response(r#"{"id":1}"#)["error"]["code"], ipc::INVALID_REQUEST);
+        let wrong_version = response(r#"{"jsonrpc":"1.0","id":1,"method":"status"}"#);
+        assert_eq!(wrong_version["error"]["code"], ipc::INVALID_REQUEST);
+        let unknown = response(r#"{"jsonrpc":"2.0","id":2,"method":"fly"}"#);
+        assert_eq!(unknown["error"]["code"], ipc::METHOD_NOT_FOUND);
+        assert_eq!(unknown["id"], 2);
+
+        // 通知不回复
+        assert!(ipc::handle_line(&state, r#"{"jsonrpc":"2.0","method":"stop"}"#).is_none());
+    }
+
+    #[test]
+    fn control_playback_over_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = app_state(dir.path());
+        let socket = socket_path(dir.path());
+        let mut server = IpcServer::start(state.clone(), &socket).unwrap();
+        // 已经有服务在监听时不能再启动
+        assert!(IpcServer::start(state.clone(), &socket).is_err());
+        assert_eq!(fs::metadata(&socket).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // 目录不存在时创建为私有目录, 其他用户可以访问的目录拒绝启动
+        let private = dir.path().join("new").join("control.sock");
+        let mut other = IpcServer::start(state.clone(), &private).unwrap();
+        let mode = fs::metadata(dir.path().join("new")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        other.stop();
+        let shared = dir.path().join("shared");
+        fs::create_dir(&shared).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o755)).unwrap();
+        let result = IpcServer::start(state.clone(), &shared.join("control.sock"));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+
+        let mut client = IpcClient::connect(&socket).unwrap();
+        let macros: Vec<MacroSummary> =
+            serde_json::from_value(client.call("list", Value::Null).unwrap()).unwrap();
+        let names: Vec<&str> = macros.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["long", "short"]);
+
+        assert_eq!(rpc_code(client.call("play", json!({ "names": [] }))), ipc::INVALID_PARAMS);
+        assert_eq!(rpc_code(client.call("play", json!({ "names": ["missing"] }))), ipc::APP_ERROR);
+        assert_eq!(rpc_code(client.call("play", json!({ "name": "long" }))), ipc::INVALID_PARAMS);
+
+        let status: ControlStatus =
+            serde_json::from_value(client.call("play", json!({ "names": ["long"] })).unwrap())
+                .unwrap();
+        assert!(status.playback.is_playing);
+        assert_eq!(rpc_code(client.call("start_recording", Value::Null)), ipc::APP_ERROR);
+
+        client.call("pause", Value::Null).unwrap();
+        assert!(client.status().unwrap().playback.is_paused);
+        client.call("resume", Value::Null).unwrap();
+        assert!(!client.status().unwrap().playback.is_paused);
+        client.call("stop", Value::Null).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while client.status().unwrap().playback.is_playing && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!state.is_playing());
+
+        // 同一个连接上可以连续发送多行请求
+        let mut stream = UnixStream::connect(&socket).unwrap();
+        writeln!(stream, r#"{{"jsonrpc":"2.0","method":"stop"}}"#).unwrap();
+        writeln!(stream, r#"{{"jsonrpc":"2.0","id":7,"method":"status"}}"#).unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&line).unwrap()["id"], 7);
+
+        server.stop();
+        assert!(!socket.exists());
+        assert!(IpcClient::connect(&socket).is_err());
+    }
+
+    #[test]
+    fn fetch_recorded_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = app_state(dir.path());
+        let socket = socket_path(dir.path());
+        let _server = IpcServer::start(state.clone(), &socket).unwrap();
+
+        state.recorder.add_delay(20);
+        state.recorder.add_delay(30);
+        let mut client = IpcClient::connect(&socket).unwrap();
+        assert_eq!(client.status().unwrap().recorded_events, 2);
+        let events: Vec<MacroEvent> =
+            serde_json::from_value(client.call("recorded_events", Value::Null).unwrap()).unwrap();
+        let delays: Vec<_> = events
+            .iter()
+            .map(|e| match e.event_type {
+                MacroEventType::Delay { duration_ms } => duration_ms,
+                _ => 0,
+            })
+            .collect();
+        assert_eq!(delays, [20, 30]);
+    }
+
+    #[test]
+    fn stale_socket_file_is_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = app_state(dir.path());
+        let socket = socket_path(dir.path());
+        // 异常退出时留下的 socket 文件
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        assert!(socket.exists());
+
+        let _server = IpcServer::start(state, &socket).unwrap();
+        let mut client = IpcClient::connect(&socket).unwrap();
+        assert!(!client.status().unwrap().playback.is_playing);
+    }
+}
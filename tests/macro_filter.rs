@@ -0,0 +1,112 @@
+#[cfg(test)]
+mod tests {
+    use mousepilot::{
+        event::{MacroEvent, MacroEventType},
+        filter::{MacroFilter, SortOrder, fuzzy_score},
+        macro_manager::SavedMacro,
+    };
+    use std::sync::Arc;
+
+    fn saved(name: &str, events: Vec<MacroEvent>, updated_at: u64) -> Arc<SavedMacro> {
+        let mut saved_macro = SavedMacro::new(name, events);
+        saved_macro.created_at = updated_at;
+        saved_macro.updated_at = updated_at;
+        Arc::new(saved_macro)
+    }
+
+    fn event(event_type: MacroEventType) -> MacroEvent {
+        MacroEvent {
+            event_type,
+            timestamp: 0,
+        }
+    }
+
+    fn names(macros: &[Arc<SavedMacro>]) -> Vec<&str> {
+        macros.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    fn sample() -> Vec<Arc<SavedMacro>> {
+        let mut login = SavedMacro::new(
+            "Login form",
+            vec![event(MacroEventType::KeyPress {
+                key: "Enter".to_string(),
+            })],
+        );
+        login.tags = vec!["work".to_string()];
+        login.updated_at = 300;
+
+        let mut wait =
+            SavedMacro::new("等待", vec![event(MacroEventType::Delay { duration_ms: 5000 })]);
+        wait.description = "slow page load".to_string();
+        wait.updated_at = 100;
+
+        vec![Arc::new(login), Arc::new(wait), saved("logout", Vec::new(), 200)]
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        assert!(fuzzy_score("lgn", "Login form").is_some());
+        assert!(fuzzy_score("LOGIN", "login form").is_some());
+        assert!(fuzzy_score("nl", "login").is_none());
+        assert!(fuzzy_score("log", "logout").unwrap() > fuzzy_score("log", "l_o_g").unwrap());
+        assert_eq!(fuzzy_score("  ", "anything"), Some(0));
+    }
+
+    #[test]
+    fn query_matches_name_tags_and_description() {
+        let filter = MacroFilter {
+            query: "work".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(names(&filter.apply(sample())), ["Login form"]);
+
+        let filter = MacroFilter {
+            query: "page".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(names(&filter.apply(sample())), ["等待"]);
+
+        // 名称匹配排在前面
+        let filter = MacroFilter {
+            query: "lo".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(names(&filter.apply(sample()))[2], "等待");
+    }
+
+    #[test]
+    fn filters_by_stats_and_date() {
+        let filter = MacroFilter {
+            has_keyboard: true,
+            ..Default::default()
+        };
+        assert_eq!(names(&filter.apply(sample())), ["Login form"]);
+
+        let filter = MacroFilter {
+            min_duration_ms: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(names(&filter.apply(sample())), ["等待"]);
+
+        let filter = MacroFilter {
+            updated_after: Some(150),
+            ..Default::default()
+        };
+        assert_eq!(names(&filter.apply(sample())), ["Login form", "logout"]);
+
+        assert!(!MacroFilter::default().is_active());
+        assert!(filter.is_active());
+    }
+
+    #[test]
+    fn sort_orders() {
+        let mut filter = MacroFilter::default();
+        assert_eq!(names(&filter.apply(sample())), ["Login form", "logout", "等待"]);
+
+        filter.sort = SortOrder::RecentlyUpdated;
+        assert_eq!(names(&filter.apply(sample())), ["Login form", "logout", "等待"]);
+
+        filter.sort = SortOrder::Longest;
+        assert_eq!(names(&filter.apply(sample()))[0], "等待");
+    }
+}
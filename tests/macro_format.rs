@@ -0,0 +1,142 @@
+#[cfg(test)]
+mod tests {
+    use mousepilot::{
+        event::MacroEventType,
+        macro_manager::{BROKEN_DIR, MacroManager, SavedMacro},
+        migration::{self, CURRENT_FORMAT_VERSION},
+    };
+    use std::{fs, path::Path};
+
+    const FIXTURES: &str = "tests/fixtures";
+    /// 无法加载的样例, 不参与 `fixture_files`
+    const BROKEN_FIXTURES: &str = "broken";
+
+    fn fixture_files() -> Vec<std::path::PathBuf> {
+        let mut files = Vec::new();
+        for version in fs::read_dir(FIXTURES).unwrap() {
+            let version = version.unwrap().path();
+            if !version.is_dir() || version.ends_with(BROKEN_FIXTURES) {
+                continue;
+            }
+            for file in fs::read_dir(&version).unwrap() {
+                files.push(file.unwrap().path());
+            }
+        }
+        files.sort();
+        files
+    }
+
+    fn load_fixture(path: impl AsRef<Path>) -> SavedMacro {
+        let file = fs::File::open(Path::new(FIXTURES).join(path)).unwrap();
+        SavedMacro::from_reader(file).unwrap()
+    }
+
+    // 所有旧格式的样例文件都必须能加载, 并升级到当前版本
+    #[test]
+    fn all_fixtures_load() {
+        let files = fixture_files();
+        assert!(!files.is_empty());
+
+        for path in files {
+            let file = fs::File::open(&path).unwrap();
+            let saved_macro = SavedMacro::from_reader(file)
+                .unwrap_or_else(|e| panic!("{} failed to load: {e}", path.display()));
+            assert_eq!(saved_macro.format_version, CURRENT_FORMAT_VERSION);
+        }
+    }
+
+    #[test]
+    fn v0_recording_keeps_events() {
+        let saved_macro = load_fixture("v0/recording.json");
+        assert_eq!(saved_macro.name, "录制1");
+        assert_eq!(saved_macro.created_at, 1735689600);
+        assert_eq!(saved_macro.events.len(), 7);
+        assert!(matches!(
+            saved_macro.events[0].event_type,
+            MacroEventType::MouseMove { x: 100, y: 200 }
+        ));
+        assert_eq!(saved_macro.events.last().unwrap().timestamp, 640);
+
+        // 迁移到 v2 时补齐元数据和统计
+        assert_eq!(saved_macro.updated_at, saved_macro.created_at);
+        assert!(saved_macro.description.is_empty());
+        assert_eq!(saved_macro.stats.duration_ms, 640);
+        assert_eq!(saved_macro.stats.mouse_moves, 1);
+        assert_eq!(saved_macro.stats.mouse_clicks, 1);
+        assert_eq!(saved_macro.stats.key_events, 4);
+    }
+
+    #[test]
+    fn v0_delay_macro() {
+        let saved_macro = load_fixture("v0/delay.json");
+        assert!(matches!(
+            saved_macro.events[0].event_type,
+            MacroEventType::Delay { duration_ms: 1000 }
+        ));
+        assert_eq!(saved_macro.stats.duration_ms, 1000);
+        assert_eq!(saved_macro.stats.delays, 1);
+    }
+
+    #[test]
+    fn v2_metadata() {
+        let saved_macro = load_fixture("v2/described.json");
+        assert_eq!(saved_macro.description, "按一次回车");
+        assert_eq!(saved_macro.tags, ["keyboard", "demo"]);
+        assert_eq!(saved_macro.author, "alice");
+        assert_eq!(saved_macro.updated_at, 1760003600);
+        assert_eq!(saved_macro.screen.unwrap().width, 1920);
+        assert!(saved_macro.stats.has_keyboard());
+        assert!(!saved_macro.stats.has_clicks());
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let value = serde_json::json!({
+            "format_version": CURRENT_FORMAT_VERSION + 1,
+            "name": "future",
+            "events": [],
+            "created_at": 0,
+        });
+        assert!(migration::migrate(value).is_err());
+    }
+
+    #[test]
+    fn missing_fields_are_rejected() {
+        let value = serde_json::json!({ "events": [] });
+        assert!(migration::migrate(value).is_err());
+    }
+
+    #[test]
+    fn manager_loads_fixture_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = fixture_files();
+        for (i, path) in files.iter().enumerate() {
+            fs::copy(path, dir.path().join(format!("{i}.json"))).unwrap();
+        }
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(manager.get_macro_count(), files.len());
+    }
+
+    #[test]
+    fn v1_without_events_is_quarantined() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = Path::new(FIXTURES).join(BROKEN_FIXTURES).join("v1_no_events.json");
+        fs::copy(&fixture, dir.path().join("no_events.json")).unwrap();
+        fs::copy(Path::new(FIXTURES).join("v1/recording.json"), dir.path().join("ok.json"))
+            .unwrap();
+
+        let value = serde_json::from_slice(&fs::read(&fixture).unwrap()).unwrap();
+        assert!(migration::migrate(value).is_err());
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        let report = manager.get_load_report();
+        assert_eq!(report.loaded, 1);
+        assert_eq!(report.failures.len(), 1);
+        assert!(report.failures[0].path.ends_with("no_events.json"));
+        assert!(report.failures[0].error.contains("events"));
+
+        assert_eq!(manager.quarantine_broken_files().unwrap(), 1);
+        assert!(dir.path().join(BROKEN_DIR).join("no_events.json").exists());
+    }
+}
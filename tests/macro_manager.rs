@@ -0,0 +1,755 @@
+#[cfg(test)]
+mod tests {
+    use mousepilot::{
+        event::{MacroEvent, MacroEventType},
+        history::HISTORY_DIR,
+        macro_manager::{BROKEN_DIR, ConflictPolicy, MacroError, MacroManager},
+        settings::LibraryConfig,
+        storage,
+        trash::TRASH_DIR,
+    };
+    use std::{fs, path::Path};
+
+    const VALID: &str = r#"{"name":"ok","events":[],"created_at":0}"#;
+
+    #[test]
+    fn load_report_lists_broken_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("ok.json"), VALID).unwrap();
+        fs::write(dir.path().join("truncated.json"), r#"{"name":"trunc"#).unwrap();
+        fs::write(dir.path().join("wrong.json"), r#"{"name":1,"events":[]}"#).unwrap();
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        let report = manager.get_load_report();
+        assert_eq!(report.loaded, 1);
+        assert_eq!(report.failures.len(), 2);
+        assert!(report.failures[0].path.ends_with("truncated.json"));
+        assert!(report.failures[0].error.contains("line 1"));
+
+        assert_eq!(manager.quarantine_broken_files().unwrap(), 2);
+        assert!(!manager.get_load_report().has_failures());
+        assert!(dir.path().join(BROKEN_DIR).join("truncated.json").exists());
+        assert!(dir.path().join(BROKEN_DIR).join("wrong.json").exists());
+        assert!(dir.path().join("ok.json").exists());
+
+        // 重新打开后 broken/ 里的文件不再参与加载
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert!(!manager.get_load_report().has_failures());
+    }
+
+    #[test]
+    fn corrupt_events_are_reported_and_deletable() {
+        let dir = tempfile::tempdir().unwrap();
+        let bad_event = r#"{"name":"bad","events":[{"event_type":"Nope"}],"created_at":0}"#;
+        fs::write(dir.path().join("bad.json"), bad_event).unwrap();
+        fs::write(dir.path().join("ok.json"), VALID).unwrap();
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        let report = manager.get_load_report();
+        assert_eq!(report.loaded, 1);
+        assert_eq!(report.failures.len(), 1);
+        assert!(report.failures[0].path.ends_with("bad.json"));
+
+        // 加载后才损坏的文件仍然可以删除, 原文件移到 broken/ 中
+        fs::write(dir.path().join("ok.json"), bad_event.replace("bad", "ok")).unwrap();
+        assert!(manager.get_macro("ok").is_err());
+        assert_eq!(manager.delete_macros(&["ok".to_string()]).unwrap(), 1);
+        assert!(!manager.macro_exists("ok"));
+        assert!(dir.path().join(BROKEN_DIR).join("ok.json").exists());
+        assert!(manager.list_trash().is_empty());
+    }
+
+    fn json_files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".json"))
+            .collect();
+        files.sort();
+        files
+    }
+
+    const TRAVERSAL_NAMES: [&str; 10] = [
+        "../../.bashrc",
+        "..",
+        ".",
+        "a/b/c",
+        "..\\..\\windows",
+        "\\\\host\\evil",
+        "/etc/passwd",
+        "con",
+        "名字*星号?",
+        "  trailing dot. ",
+    ];
+
+    #[test]
+    fn traversal_names_stay_in_storage_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("macros");
+        let manager = MacroManager::open(dir.to_string_lossy()).unwrap();
+
+        for name in TRAVERSAL_NAMES {
+            manager.save_macro(name, Vec::new(), ConflictPolicy::Error).unwrap();
+        }
+
+        // 所有文件都直接位于宏目录下, 上级目录没有多出任何文件
+        assert_eq!(json_files(&dir).len(), TRAVERSAL_NAMES.len());
+        assert_eq!(fs::read_dir(root.path()).unwrap().count(), 1);
+        for file in json_files(&dir) {
+            assert!(!file.starts_with('.'), "{file}");
+            assert!(!file.contains(['/', '\\', ':']), "{file}");
+        }
+
+        // 显示名称原样保留
+        let manager = MacroManager::open(dir.to_string_lossy()).unwrap();
+        for name in TRAVERSAL_NAMES {
+            assert!(manager.macro_exists(name), "{name}");
+        }
+
+        for name in TRAVERSAL_NAMES {
+            manager.delete_macro(name).unwrap();
+        }
+        assert!(json_files(&dir).is_empty());
+    }
+
+    #[test]
+    fn rename_does_not_escape_storage_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("macros");
+        let manager = MacroManager::open(dir.to_string_lossy()).unwrap();
+
+        manager.save_macro("safe", Vec::new(), ConflictPolicy::Error).unwrap();
+        manager.rename_macro("safe", "../outside", ConflictPolicy::Error).unwrap();
+
+        assert_eq!(fs::read_dir(root.path()).unwrap().count(), 1);
+        assert_eq!(json_files(&dir).len(), 1);
+        assert!(manager.macro_exists("../outside"));
+        assert!(!manager.macro_exists("safe"));
+    }
+
+    #[test]
+    fn colliding_slugs_get_distinct_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+
+        manager.save_macro("a/b", Vec::new(), ConflictPolicy::Error).unwrap();
+        manager.save_macro("a\\b", Vec::new(), ConflictPolicy::Error).unwrap();
+        manager.save_macro("A_B", Vec::new(), ConflictPolicy::Error).unwrap();
+
+        assert_eq!(json_files(dir.path()).len(), 3);
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(manager.get_macro_count(), 3);
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+
+        assert!(manager.save_macro("", Vec::new(), ConflictPolicy::Error).is_err());
+        assert!(manager.save_macro("   ", Vec::new(), ConflictPolicy::Error).is_err());
+        assert!(manager.save_macro("line\nbreak", Vec::new(), ConflictPolicy::Error).is_err());
+        assert!(
+            manager
+                .save_macro(
+                    &"x".repeat(storage::MAX_NAME_CHARS + 1),
+                    Vec::new(),
+                    ConflictPolicy::Error
+                )
+                .is_err()
+        );
+        assert!(json_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn slugify_is_a_plain_file_name() {
+        assert_eq!(storage::slugify("延时宏(1s)"), "延时宏(1s)");
+        assert_eq!(storage::slugify("../../.bashrc"), "_.._.bashrc");
+        assert_eq!(storage::slugify("NUL.txt"), "_NUL.txt");
+        assert_eq!(storage::slugify("..."), "macro");
+    }
+
+    #[test]
+    fn save_keeps_backup_of_previous_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+
+        manager.save_macro("m", Vec::new(), ConflictPolicy::Error).unwrap();
+        let first = fs::read_to_string(dir.path().join("m.json")).unwrap();
+        manager.save_macro("m", vec![delay(500)], ConflictPolicy::Overwrite).unwrap();
+
+        let backup = fs::read_to_string(storage::backup_path(&dir.path().join("m.json"))).unwrap();
+        assert_eq!(backup, first);
+        assert_eq!(json_files(dir.path()), ["m.json"]);
+        // 没有残留的临时文件
+        let entries = fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name());
+        assert_eq!(entries.filter(|name| name != HISTORY_DIR).count(), 2);
+    }
+
+    #[test]
+    fn overwrite_keeps_created_at() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("ok.json"), VALID).unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+
+        manager.save_macro("ok", vec![delay(5)], ConflictPolicy::Overwrite).unwrap();
+        let saved_macro = manager.get_macro("ok").unwrap();
+        assert_eq!(saved_macro.created_at, 0);
+        assert!(saved_macro.updated_at > 0);
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(manager.get_macro_header("ok").unwrap().created_at, 0);
+    }
+
+    #[test]
+    fn rename_is_transactional() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+
+        manager.save_macro("old", vec![delay(100)], ConflictPolicy::Error).unwrap();
+        assert!(manager.rename_macro("missing", "new", ConflictPolicy::Error).is_err());
+        assert_eq!(manager.get_macro_names(), ["old"]);
+
+        manager.rename_macro("old", "new", ConflictPolicy::Error).unwrap();
+        assert_eq!(manager.get_macro_names(), ["new"]);
+        assert_eq!(json_files(dir.path()), ["new.json"]);
+        assert!(storage::backup_path(&dir.path().join("old.json")).exists());
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(manager.get_macro_names(), ["new"]);
+        assert_eq!(manager.get_macros(&["new".to_string()])[0].events.len(), 1);
+    }
+
+    #[test]
+    fn save_conflicts_follow_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+
+        manager.save_macro("m", vec![delay(1)], ConflictPolicy::Error).unwrap();
+        let err = manager.save_macro("m", vec![delay(2)], ConflictPolicy::Error).unwrap_err();
+        assert!(matches!(err, MacroError::AlreadyExists(name) if name == "m"));
+
+        let name = manager.save_macro("m", vec![delay(3)], ConflictPolicy::AutoSuffix).unwrap();
+        assert_eq!(name, "m (2)");
+        let name = manager.save_macro("m", vec![delay(4)], ConflictPolicy::AutoSuffix).unwrap();
+        assert_eq!(name, "m (3)");
+
+        manager.save_macro("m", vec![delay(5)], ConflictPolicy::Overwrite).unwrap();
+        assert_eq!(manager.get_macro_names(), ["m", "m (2)", "m (3)"]);
+        assert!(matches!(
+            manager.get_macros(&["m".to_string()])[0].events[0].event_type,
+            MacroEventType::Delay { duration_ms: 5 }
+        ));
+    }
+
+    #[test]
+    fn rename_conflicts_follow_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+
+        manager.save_macro("a", vec![delay(1)], ConflictPolicy::Error).unwrap();
+        manager.save_macro("b", vec![delay(2)], ConflictPolicy::Error).unwrap();
+
+        let err = manager.rename_macro("a", "b", ConflictPolicy::Error).unwrap_err();
+        assert!(matches!(err, MacroError::AlreadyExists(_)));
+        assert_eq!(manager.get_macro_names(), ["a", "b"]);
+
+        assert_eq!(manager.rename_macro("a", "b", ConflictPolicy::AutoSuffix).unwrap(), "b (2)");
+        assert_eq!(manager.get_macro_names(), ["b", "b (2)"]);
+
+        manager.rename_macro("b (2)", "b", ConflictPolicy::Overwrite).unwrap();
+        assert_eq!(manager.get_macro_names(), ["b"]);
+        assert_eq!(json_files(dir.path()), ["b.json"]);
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert!(matches!(
+            manager.get_macros(&["b".to_string()])[0].events[0].event_type,
+            MacroEventType::Delay { duration_ms: 1 }
+        ));
+    }
+
+    #[test]
+    fn edit_macro_updates_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+
+        manager.save_macro("m", vec![delay(250)], ConflictPolicy::Error).unwrap();
+        manager
+            .edit_macro("m", |m| {
+                m.description = "wait a bit".to_string();
+                m.tags = vec!["wait".to_string()];
+            })
+            .unwrap();
+        assert!(manager.edit_macro("missing", |_| {}).is_err());
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        let saved_macro = &manager.get_macros(&["m".to_string()])[0];
+        assert_eq!(saved_macro.description, "wait a bit");
+        assert_eq!(saved_macro.tags, ["wait"]);
+        assert_eq!(saved_macro.stats.duration_ms, 250);
+        assert!(saved_macro.updated_at >= saved_macro.created_at);
+    }
+
+    #[test]
+    fn groups_follow_subdirectories() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("work/daily")).unwrap();
+        fs::create_dir_all(dir.path().join("empty")).unwrap();
+        fs::create_dir_all(dir.path().join(BROKEN_DIR)).unwrap();
+        fs::write(dir.path().join("work/daily/ok.json"), VALID).unwrap();
+        fs::write(dir.path().join(BROKEN_DIR).join("bad.json"), "{").unwrap();
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(manager.get_groups(), ["empty", "work", "work/daily"]);
+        assert_eq!(manager.get_macro_group("ok"), "work/daily");
+        assert_eq!(manager.get_macro_names_in_group("work"), ["ok"]);
+        assert!(manager.get_macro_names_in_group("empty").is_empty());
+        assert!(!manager.get_load_report().has_failures());
+
+        // 重命名后留在原分组
+        manager.rename_macro("ok", "renamed", ConflictPolicy::Error).unwrap();
+        assert_eq!(manager.get_macro_group("renamed"), "work/daily");
+        assert_eq!(json_files(&dir.path().join("work/daily")), ["renamed.json"]);
+    }
+
+    #[test]
+    fn move_macro_between_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+
+        manager.save_macro("m", vec![delay(1)], ConflictPolicy::Error).unwrap();
+        assert_eq!(manager.create_group(" a / b:c ").unwrap(), "a/b_c");
+        assert!(manager.create_group(" / ").is_err());
+        assert!(manager.create_group(BROKEN_DIR).is_err());
+
+        manager.move_macro("m", "a/b_c").unwrap();
+        assert!(json_files(dir.path()).is_empty());
+        assert_eq!(json_files(&dir.path().join("a/b_c")), ["m.json"]);
+        assert!(manager.move_macro("missing", "a").is_err());
+
+        // 保存已有的宏时写回它所在的分组
+        manager.save_macro("m", vec![delay(2)], ConflictPolicy::Overwrite).unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(manager.get_macro_group("m"), "a/b_c");
+        assert_eq!(manager.get_groups(), ["a", "a/b_c"]);
+
+        manager.move_macro("m", "").unwrap();
+        assert_eq!(json_files(dir.path()), ["m.json"]);
+        assert_eq!(manager.get_macro_group("m"), "");
+    }
+
+    #[test]
+    fn revisions_can_be_listed_and_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+
+        manager.save_macro("m", vec![delay(100)], ConflictPolicy::Error).unwrap();
+        assert!(manager.list_revisions("m").unwrap().is_empty());
+
+        // 重新录制覆盖
+        manager
+            .save_macro("m", vec![delay(100), delay(200)], ConflictPolicy::Overwrite)
+            .unwrap();
+        let revisions = manager.list_revisions("m").unwrap();
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].stats.event_count, 1);
+
+        let diff = manager.diff_revision("m", &revisions[0].id).unwrap();
+        assert_eq!(diff.event_delta(), 1);
+        assert_eq!(diff.duration_delta_ms(), 200);
+
+        manager.restore_revision("m", &revisions[0].id).unwrap();
+        assert_eq!(manager.get_macros(&["m".to_string()])[0].events.len(), 1);
+        // 恢复前的内容也进入历史
+        assert_eq!(manager.list_revisions("m").unwrap().len(), 2);
+        assert!(manager.restore_revision("m", "missing").is_err());
+
+        // 改名后历史跟随
+        manager.rename_macro("m", "renamed", ConflictPolicy::Error).unwrap();
+        assert!(manager.list_revisions("m").unwrap().is_empty());
+        assert_eq!(manager.list_revisions("renamed").unwrap().len(), 3);
+
+        // 历史目录不会被当成宏或分组加载
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(manager.get_macro_names(), ["renamed"]);
+        assert!(manager.get_groups().is_empty());
+    }
+
+    #[test]
+    fn renamed_history_does_not_overwrite_same_slug_revisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        manager.save_macro("m", vec![delay(1)], ConflictPolicy::Error).unwrap();
+        manager.save_macro("m", vec![delay(2)], ConflictPolicy::Overwrite).unwrap();
+        let id = manager.list_revisions("m").unwrap()[0].id.clone();
+
+        // "a?" 和 "a!" 的 slug 相同, 放一个同 ID 的 "a?" 版本
+        let history = dir.path().join(HISTORY_DIR);
+        let mut value: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(history.join("m").join(format!("{id}.json"))).unwrap(),
+        )
+        .unwrap();
+        value["name"] = "a?".into();
+        fs::create_dir_all(history.join("a_")).unwrap();
+        fs::write(history.join("a_").join(format!("{id}.json")), value.to_string()).unwrap();
+
+        manager.rename_macro("m", "a!", ConflictPolicy::Error).unwrap();
+        assert_eq!(manager.list_revisions("a?").unwrap().len(), 1);
+        let revisions = manager.list_revisions("a!").unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_ne!(revisions[0].id, id);
+        assert!(revisions[0].id.starts_with(&id));
+    }
+
+    #[test]
+    fn history_keeps_last_revisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        manager.set_history_limit(3);
+
+        for ms in 1..=6 {
+            manager.save_macro("m", vec![delay(ms)], ConflictPolicy::Overwrite).unwrap();
+        }
+        let revisions = manager.list_revisions("m").unwrap();
+        let durations: Vec<u64> = revisions.iter().map(|r| r.stats.duration_ms).collect();
+        assert_eq!(durations, [3, 4, 5]);
+    }
+
+    #[test]
+    fn deleted_macros_go_to_trash() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+
+        manager.save_macro("a", vec![delay(1)], ConflictPolicy::Error).unwrap();
+        manager.save_macro("b", vec![delay(2)], ConflictPolicy::Error).unwrap();
+        manager.save_macro("c", vec![delay(3)], ConflictPolicy::Error).unwrap();
+        manager.create_group("g").unwrap();
+        manager.move_macro("c", "g").unwrap();
+
+        manager.delete_macro("a").unwrap();
+        let names = ["b".to_string(), "c".to_string(), "missing".to_string()];
+        assert_eq!(manager.delete_macros(&names).unwrap(), 2);
+        assert_eq!(manager.get_macro_count(), 0);
+        assert!(json_files(dir.path()).is_empty());
+        assert_eq!(manager.list_trash().len(), 3);
+
+        // 撤销只恢复最近一批, 并回到原分组
+        let mut restored = manager.undo_delete().unwrap();
+        restored.sort();
+        assert_eq!(restored, ["b", "c"]);
+        assert_eq!(manager.get_macro_group("c"), "g");
+        assert!(manager.undo_delete().unwrap().is_empty());
+
+        let trash = manager.list_trash();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].name, "a");
+
+        // 恢复时名称已被占用则自动加后缀
+        manager.save_macro("a", Vec::new(), ConflictPolicy::Error).unwrap();
+        let name = manager.restore_from_trash(&trash[0].id, ConflictPolicy::AutoSuffix).unwrap();
+        assert_eq!(name, "a (2)");
+        assert!(manager.list_trash().is_empty());
+
+        // 回收站不会被当作宏或分组加载
+        manager.delete_macro("a").unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(manager.get_macro_names(), ["a (2)", "b", "c"]);
+        assert_eq!(manager.get_groups(), ["g"]);
+
+        let id = manager.list_trash()[0].id.clone();
+        manager.purge_from_trash(&id).unwrap();
+        assert!(manager.purge_from_trash(&id).is_err());
+    }
+
+    #[test]
+    fn failed_batch_delete_can_be_undone() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        for name in ["a", "b", "c"] {
+            manager.save_macro(name, vec![delay(1)], ConflictPolicy::Error).unwrap();
+        }
+        // 用同名目录替换 b 的文件, 删除文件时出错
+        fs::remove_file(dir.path().join("b.json")).unwrap();
+        fs::create_dir_all(dir.path().join("b.json").join("x")).unwrap();
+
+        let names = ["a".to_string(), "b".to_string(), "c".to_string()];
+        assert!(manager.delete_macros(&names).is_err());
+        assert_eq!(manager.get_macro_names(), ["b", "c"]);
+        // 出错的宏不会在回收站里留下副本
+        let trash = manager.list_trash();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].name, "a");
+        assert_eq!(fs::read_dir(dir.path().join(TRASH_DIR)).unwrap().count(), 1);
+
+        assert_eq!(manager.undo_delete().unwrap(), ["a"]);
+        assert!(manager.list_trash().is_empty());
+    }
+
+    #[test]
+    fn expired_trash_is_purged() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        manager.save_macro("old", Vec::new(), ConflictPolicy::Error).unwrap();
+        manager.save_macro("new", Vec::new(), ConflictPolicy::Error).unwrap();
+        manager.delete_macros(&["old".to_string(), "new".to_string()]).unwrap();
+
+        // 把其中一个的删除时间改到很久以前
+        let id = manager.list_trash().into_iter().find(|e| e.name == "old").unwrap().id;
+        let path = dir.path().join(TRASH_DIR).join(format!("{id}.json"));
+        let mut value: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        value["deleted_at"] = 0.into();
+        fs::write(&path, value.to_string()).unwrap();
+
+        manager.set_trash_retention_days(0);
+        assert_eq!(manager.purge_expired_trash().unwrap(), 0);
+        // 极大的保留天数等于永久保留
+        manager.set_trash_retention_days(u64::MAX);
+        assert_eq!(manager.purge_expired_trash().unwrap(), 0);
+        manager.set_trash_retention_days(30);
+        assert_eq!(manager.purge_expired_trash().unwrap(), 1);
+        let trash = manager.list_trash();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].name, "new");
+
+        assert_eq!(manager.empty_trash().unwrap(), 1);
+        assert!(manager.list_trash().is_empty());
+    }
+
+    fn library(name: &str, path: &Path, read_only: bool) -> LibraryConfig {
+        LibraryConfig {
+            name: name.to_string(),
+            path: path.to_string_lossy().to_string(),
+            read_only,
+        }
+    }
+
+    #[test]
+    fn libraries_are_mounted_with_prefixed_names() {
+        let root = tempfile::tempdir().unwrap();
+        let (home, team, shared) =
+            (root.path().join("home"), root.path().join("team"), root.path().join("shared"));
+        fs::create_dir_all(shared.join("work")).unwrap();
+        fs::write(shared.join("work/ok.json"), VALID).unwrap();
+
+        let libraries = [library("team", &team, false), library("shared", &shared, true)];
+        let manager =
+            MacroManager::open_with_libraries(home.to_string_lossy(), &libraries).unwrap();
+        assert_eq!(manager.libraries().len(), 3);
+        assert_eq!(manager.get_macro_names(), ["shared:ok"]);
+        assert_eq!(manager.get_macro_group("shared:ok"), "shared:work");
+        assert_eq!(manager.get_groups(), ["shared:", "shared:work", "team:"]);
+        assert_eq!(manager.get_macro_names_in_group("shared:"), ["shared:ok"]);
+
+        // 同名的宏在不同库中互不冲突, 文件里保存库内名称
+        manager.save_macro("ok", Vec::new(), ConflictPolicy::Error).unwrap();
+        manager.save_macro("team:ok", Vec::new(), ConflictPolicy::Error).unwrap();
+        assert_eq!(json_files(&home), ["ok.json"]);
+        assert_eq!(json_files(&team), ["ok.json"]);
+        assert!(fs::read_to_string(team.join("ok.json")).unwrap().contains(r#""name":"ok""#));
+
+        // 库内名称不能包含 `:`, 主库的宏不会被同名前缀的库抢走
+        let result = manager.save_macro("other:x", Vec::new(), ConflictPolicy::Error);
+        assert!(matches!(result, Err(MacroError::InvalidName(_))));
+        assert!(manager.save_macro("team:a:b", Vec::new(), ConflictPolicy::Error).is_err());
+        assert!(manager.rename_macro("ok", "x:y", ConflictPolicy::Error).is_err());
+        assert_eq!(manager.library_of("other:x"), "");
+        assert_eq!(manager.library_of("team:ok"), "team");
+
+        // 旧文件里带 `:` 的名称加载时改名, 不会被当作库名前缀
+        fs::write(home.join("colon.json"), r#"{"name":"team:ok","events":[],"created_at":0}"#)
+            .unwrap();
+
+        manager.rename_macro("team:ok", "team:renamed", ConflictPolicy::Error).unwrap();
+        assert!(manager.rename_macro("team:renamed", "moved", ConflictPolicy::Error).is_err());
+        assert_eq!(manager.create_group("team:a/b").unwrap(), "team:a/b");
+        manager.move_macro("team:renamed", "team:a/b").unwrap();
+        assert!(manager.move_macro("team:renamed", "a").is_err());
+        assert!(team.join("a/b/renamed.json").exists());
+
+        let manager =
+            MacroManager::open_with_libraries(home.to_string_lossy(), &libraries).unwrap();
+        assert_eq!(manager.get_macro_names(), ["ok", "shared:ok", "team:renamed", "team_ok"]);
+        assert_eq!(manager.library_of("team_ok"), "");
+        assert!(!manager.get_load_report().has_failures());
+    }
+
+    #[test]
+    fn old_names_with_colons_are_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = r#"{"name":"Step 1: login","events":[],"created_at":0,"description":"d"}"#;
+        fs::write(dir.path().join("step.json"), old).unwrap();
+
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert!(!manager.get_load_report().has_failures());
+        assert_eq!(manager.get_macro_names(), ["Step 1_ login"]);
+        assert_eq!(manager.get_macro("Step 1_ login").unwrap().description, "d");
+        let contents = fs::read_to_string(dir.path().join("step.json")).unwrap();
+        assert!(contents.contains(r#""name":"Step 1_ login""#));
+
+        // 迁移后的宏可以正常覆盖保存
+        manager.save_macro("Step 1_ login", Vec::new(), ConflictPolicy::Overwrite).unwrap();
+        assert_eq!(json_files(dir.path()), ["step.json"]);
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert_eq!(manager.get_macro_names(), ["Step 1_ login"]);
+    }
+
+    #[test]
+    fn read_only_libraries_reject_changes() {
+        let root = tempfile::tempdir().unwrap();
+        let shared = root.path().join("shared");
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(shared.join("ok.json"), VALID).unwrap();
+        let before = fs::read_to_string(shared.join("ok.json")).unwrap();
+
+        let libraries = [library("shared", &shared, true)];
+        let home = root.path().join("home");
+        let manager =
+            MacroManager::open_with_libraries(home.to_string_lossy(), &libraries).unwrap();
+        assert!(manager.is_read_only("shared:ok"));
+        assert!(!manager.is_read_only("ok"));
+
+        fn read_only<T>(result: Result<T, MacroError>) {
+            assert!(matches!(result, Err(MacroError::ReadOnly(lib)) if lib == "shared"));
+        }
+        read_only(manager.save_macro("shared:ok", Vec::new(), ConflictPolicy::Overwrite));
+        read_only(manager.save_macro("shared:new", Vec::new(), ConflictPolicy::Error));
+        read_only(manager.edit_macro("shared:ok", |m| m.description = "x".to_string()));
+        read_only(manager.rename_macro("shared:ok", "shared:x", ConflictPolicy::Error));
+        read_only(manager.delete_macro("shared:ok"));
+        read_only(manager.create_group("shared:g"));
+
+        assert_eq!(fs::read_dir(&shared).unwrap().count(), 1);
+        assert_eq!(fs::read_to_string(shared.join("ok.json")).unwrap(), before);
+
+        // 只读库目录不存在时不会被创建
+        let missing = [library("missing", &root.path().join("missing"), true)];
+        assert!(MacroManager::open_with_libraries(home.to_string_lossy(), &missing).is_err());
+        assert!(!root.path().join("missing").exists());
+    }
+
+    #[test]
+    fn poll_changes_applies_external_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("ok.json"), VALID).unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        assert!(manager.poll_changes().is_empty());
+
+        // 新增、修改和删除都要在状态稳定后的第二次检查才生效
+        fs::create_dir(dir.path().join("work")).unwrap();
+        fs::write(
+            dir.path().join("work").join("new.json"),
+            r#"{"name":"new","events":[],"created_at":0}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("ok.json"),
+            r#"{"name":"ok","events":[],"created_at":0,"description":"changed"}"#,
+        )
+        .unwrap();
+        assert!(manager.poll_changes().is_empty());
+        let summary = manager.poll_changes();
+        assert_eq!(summary.added, ["new"]);
+        assert_eq!(summary.updated, ["ok"]);
+        assert_eq!(manager.get_macro_group("new"), "work");
+        assert_eq!(manager.get_macros(&["ok".to_string()])[0].description, "changed");
+
+        // 文件里的名称被改掉时, 旧名称消失
+        fs::write(dir.path().join("ok.json"), r#"{"name":"renamed","events":[],"created_at":0}"#)
+            .unwrap();
+        manager.poll_changes();
+        let summary = manager.poll_changes();
+        assert_eq!(summary.added, ["renamed"]);
+        assert_eq!(summary.removed, ["ok"]);
+
+        // 删除立即生效
+        fs::remove_file(dir.path().join("work").join("new.json")).unwrap();
+        assert_eq!(manager.poll_changes().removed, ["new"]);
+        assert_eq!(manager.get_macro_names(), ["renamed"]);
+    }
+
+    #[test]
+    fn poll_changes_ignores_own_writes_and_keeps_broken_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        manager.save_macro("a", vec![delay(10)], ConflictPolicy::Error).unwrap();
+        manager.edit_macro("a", |m| m.description = "edited".to_string()).unwrap();
+        manager.rename_macro("a", "b", ConflictPolicy::Error).unwrap();
+        manager.create_group("g").unwrap();
+        manager.move_macro("b", "g").unwrap();
+        manager.save_macro("c", Vec::new(), ConflictPolicy::Error).unwrap();
+        manager.delete_macro("c").unwrap();
+        assert!(manager.poll_changes().is_empty());
+        assert!(manager.poll_changes().is_empty());
+
+        // 外部写坏的文件不影响内存中的旧版本, 修好后失败记录消失
+        let path = dir.path().join("g").join("b.json");
+        fs::write(&path, r#"{"name":"b","#).unwrap();
+        manager.poll_changes();
+        assert!(manager.poll_changes().is_empty());
+        assert_eq!(manager.get_macros(&["b".to_string()])[0].description, "edited");
+        assert!(manager.get_load_report().failures[0].path.ends_with("b.json"));
+
+        fs::write(&path, r#"{"name":"b","events":[],"created_at":0}"#).unwrap();
+        manager.poll_changes();
+        assert_eq!(manager.poll_changes().updated, ["b"]);
+        assert!(!manager.get_load_report().has_failures());
+    }
+
+    #[test]
+    fn events_are_loaded_on_demand() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        manager.save_macro("a", vec![delay(10), delay(20)], ConflictPolicy::Error).unwrap();
+        manager.save_macro("b", vec![delay(30)], ConflictPolicy::Error).unwrap();
+        manager.save_macro("c", vec![delay(40)], ConflictPolicy::Error).unwrap();
+
+        // 列表里只有元数据和统计
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        let header = manager.get_macro_header("a").unwrap();
+        assert!(header.events.is_empty());
+        assert_eq!(header.stats.event_count, 2);
+        assert!(manager.get_all_macros().iter().all(|m| m.events.is_empty()));
+        assert!(!manager.is_body_cached("a"));
+
+        manager.set_body_cache_size(2);
+        assert_eq!(manager.get_macro("a").unwrap().events.len(), 2);
+        assert_eq!(manager.get_macros(&["b".to_string(), "c".to_string()]).len(), 2);
+        assert!(!manager.is_body_cached("a"));
+        assert!(manager.is_body_cached("b") && manager.is_body_cached("c"));
+        assert!(matches!(manager.get_macro("missing"), Err(MacroError::NotFound(_))));
+
+        // 修改和删除时读取完整内容, 历史和回收站里的事件不会丢
+        manager.edit_macro("a", |m| m.description = "edited".to_string()).unwrap();
+        let revision = &manager.list_revisions("a").unwrap()[0];
+        assert_eq!(revision.stats.event_count, 2);
+        manager.set_body_cache_size(0);
+        manager.delete_macro("a").unwrap();
+        let entry = &manager.list_trash()[0];
+        let restored = manager.restore_from_trash(&entry.id, ConflictPolicy::Error).unwrap();
+        assert_eq!(manager.get_macro(&restored).unwrap().events.len(), 2);
+    }
+
+    // 需要迁移的旧文件也只在内存中保留元数据
+    #[test]
+    fn old_files_load_as_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("old.json"),
+            r#"{"name":"old","events":[{"event_type":{"Delay":{"duration_ms":5}},"timestamp":0}]}"#,
+        )
+        .unwrap();
+        let manager = MacroManager::open(dir.path().to_string_lossy()).unwrap();
+        let header = manager.get_macro_header("old").unwrap();
+        assert!(header.events.is_empty());
+        assert_eq!(header.stats.delays, 1);
+        assert_eq!(manager.get_macro("old").unwrap().events.len(), 1);
+    }
+
+    fn delay(duration_ms: u64) -> MacroEvent {
+        MacroEvent {
+            event_type: MacroEventType::Delay { duration_ms },
+            timestamp: 0,
+        }
+    }
+}
@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use mousepilot::{
+        event::{Button, MacroEvent, MacroEventType},
+        macro_manager::{SavedMacro, ScreenInfo},
+        script::ScriptFormat,
+    };
+    use std::{fs, path::PathBuf};
+
+    fn event(event_type: MacroEventType, timestamp: u128) -> MacroEvent {
+        MacroEvent {
+            event_type,
+            timestamp,
+        }
+    }
+
+    /// 覆盖所有事件类型, 包括需要转义的键和不支持的键
+    fn sample() -> SavedMacro {
+        let key = |key: &str| key.to_string();
+        let events = vec![
+            event(MacroEventType::MouseMove { x: 100, y: 200 }, 0),
+            event(
+                MacroEventType::MouseClick {
+                    button: Button::Left,
+                    pressed: true,
+                },
+                50,
+            ),
+            event(
+                MacroEventType::MouseClick {
+                    button: Button::Left,
+                    pressed: false,
+                },
+                120,
+            ),
+            event(
+                MacroEventType::MouseClick {
+                    button: Button::Right,
+                    pressed: true,
+                },
+                400,
+            ),
+            event(
+                MacroEventType::MouseClick {
+                    button: Button::Right,
+                    pressed: false,
+                },
+                450,
+            ),
+            event(
+                MacroEventType::MouseClick {
+                    button: Button::Middle,
+                    pressed: true,
+                },
+                500,
+            ),
+            event(
+                MacroEventType::MouseClick {
+                    button: Button::Middle,
+                    pressed: false,
+                },
+                520,
+            ),
+            event(MacroEventType::Delay { duration_ms: 2000 }, 520),
+            event(MacroEventType::KeyPress { key: key("LShift") }, 600),
+            event(MacroEventType::KeyPress { key: key("A") }, 610),
+            event(MacroEventType::KeyRelease { key: key("A") }, 660),
+            event(MacroEventType::KeyRelease { key: key("LShift") }, 670),
+            event(MacroEventType::KeyPress { key: key("Grave") }, 700),
+            event(MacroEventType::KeyRelease { key: key("Grave") }, 720),
+            event(
+                MacroEventType::KeyPress {
+                    key: key("BackSlash"),
+                },
+                800,
+            ),
+            event(
+                MacroEventType::KeyRelease {
+                    key: key("BackSlash"),
+                },
+                820,
+            ),
+            event(MacroEventType::KeyPress { key: key("Enter") }, 1500),
+            event(MacroEventType::KeyRelease { key: key("Enter") }, 1540),
+            event(
+                MacroEventType::KeyPress {
+                    key: key("MediaPlay"),
+                },
+                1600,
+            ),
+            event(
+                MacroEventType::KeyRelease {
+                    key: key("MediaPlay"),
+                },
+                1620,
+            ),
+        ];
+        SavedMacro::new("golden demo", events).with_screen(Some(ScreenInfo {
+            width: 1920,
+            height: 1080,
+            scale: 1.0,
+        }))
+    }
+
+    /// 与 `tests/golden/` 下的文件比较, 设置 `UPDATE_GOLDEN=1` 时重新生成
+    fn check_golden(format: ScriptFormat) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("sample.{}", format.extension()));
+        let actual = format.export(&sample());
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&path, &actual).unwrap();
+        }
+        let expected = fs::read_to_string(&path).unwrap();
+        assert_eq!(actual, expected, "{} differs from {}", format.name(), path.display());
+    }
+
+    #[test]
+    fn xdotool_matches_golden() {
+        check_golden(ScriptFormat::Xdotool);
+    }
+
+    #[test]
+    fn autohotkey_matches_golden() {
+        check_golden(ScriptFormat::AutoHotkey);
+    }
+
+    #[test]
+    fn pyautogui_matches_golden() {
+        check_golden(ScriptFormat::Pyautogui);
+    }
+
+    /// 宏文件中的键名和名称可能来自导入的包, 不能在注释之外生成可执行的行
+    #[test]
+    fn untrusted_text_stays_in_comments() {
+        let mut saved_macro = sample();
+        saved_macro.name = "evil\rname".to_string();
+        saved_macro.events = vec![event(
+            MacroEventType::KeyPress {
+                key: "x\nrm -rf ~".to_string(),
+            },
+            0,
+        )];
+        for format in ScriptFormat::ALL {
+            let script = format.export(&saved_macro);
+            assert!(script.contains("evil\\rname"), "{}", format.name());
+            assert!(script.contains("unsupported key: "), "{}", format.name());
+            assert!(script.contains("x\\nrm -rf ~"), "{}", format.name());
+            assert!(!script.lines().any(|line| line.starts_with("rm ")), "{}", format.name());
+            assert!(!script.contains('\r'), "{}", format.name());
+        }
+    }
+}
@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod tests {
+    use mousepilot::{
+        settings::{LibraryConfig, Settings},
+        storage::{self, StorageFormat},
+    };
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_library_spec() {
+        let config = LibraryConfig::parse("shared=/repo/macros?ro").unwrap();
+        assert_eq!(config.name, "shared");
+        assert_eq!(config.path, "/repo/macros");
+        assert!(config.read_only);
+
+        // 省略名称时使用目录名
+        let config = LibraryConfig::parse("/home/me/team-macros").unwrap();
+        assert_eq!(config.name, "team-macros");
+        assert!(!config.read_only);
+
+        assert!(LibraryConfig::parse("a:b=/x").is_err());
+        assert!(LibraryConfig::parse("name=").is_err());
+    }
+
+    #[test]
+    fn command_line_overrides() {
+        let settings = Settings::default()
+            .with_args(args(&[
+                "--macros-dir",
+                "/tmp/macros",
+                "--library",
+                "team=/team",
+                "--library=team=/team2?ro",
+                "--storage-format",
+                "compact",
+                "--ipc-socket",
+                "/tmp/mp.sock",
+            ]))
+            .unwrap();
+        assert_eq!(settings.macros_dir.as_deref(), Some("/tmp/macros"));
+        assert_eq!(settings.libraries.len(), 1);
+        assert_eq!(settings.libraries[0].path, "/team2");
+        assert!(settings.libraries[0].read_only);
+        assert_eq!(settings.storage_format, StorageFormat::Compact);
+        assert_eq!(settings.ipc_socket(), std::path::Path::new("/tmp/mp.sock"));
+
+        assert!(Settings::default().with_args(args(&["--library"])).is_err());
+        assert!(Settings::default().with_args(args(&["--unknown"])).is_err());
+        assert!(Settings::default().with_args(args(&["--storage-format", "zip"])).is_err());
+
+        // 打开窗口时忽略启动器附加的参数
+        let settings = Settings::default()
+            .with_gui_args(args(&["-psn_0_12345", "--unknown", "--macros-dir=/tmp/m"]))
+            .unwrap();
+        assert_eq!(settings.macros_dir.as_deref(), Some("/tmp/m"));
+        assert!(Settings::default().with_gui_args(args(&["--storage-format", "zip"])).is_err());
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        let mut settings = Settings::default();
+        settings.add_library(LibraryConfig::parse("team=/team").unwrap());
+        settings.save_to(&path).unwrap();
+        assert_eq!(Settings::load_from(&path).unwrap(), settings);
+
+        // 缺少的字段使用默认值
+        std::fs::write(&path, "{}").unwrap();
+        assert_eq!(Settings::load_from(&path).unwrap(), Settings::default());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn settings_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        // 旧版本写入的文件权限较宽
+        std::fs::write(&path, "{}").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let mut settings = Settings::default();
+        settings.http.token = "secret".to_string();
+        settings.save_to(&path).unwrap();
+        let mode = |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode(&path) & 0o777, 0o600);
+        assert_eq!(mode(&storage::backup_path(&path)) & 0o777, 0o600);
+    }
+}
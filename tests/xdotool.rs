@@ -0,0 +1,149 @@
+#[cfg(test)]
+mod tests {
+    use mousepilot::{
+        event::{Button, MacroEvent, MacroEventType},
+        macro_manager::{MacroError, SavedMacro},
+        xdotool,
+    };
+
+    fn event(event_type: MacroEventType, timestamp: u128) -> MacroEvent {
+        MacroEvent {
+            event_type,
+            timestamp,
+        }
+    }
+
+    fn sample() -> SavedMacro {
+        let key = |key: &str| key.to_string();
+        SavedMacro::new(
+            "demo",
+            vec![
+                event(MacroEventType::MouseMove { x: 10, y: 20 }, 0),
+                event(
+                    MacroEventType::MouseClick {
+                        button: Button::Left,
+                        pressed: true,
+                    },
+                    120,
+                ),
+                event(
+                    MacroEventType::MouseClick {
+                        button: Button::Left,
+                        pressed: false,
+                    },
+                    180,
+                ),
+                event(MacroEventType::Delay { duration_ms: 1500 }, 180),
+                event(
+                    MacroEventType::KeyPress {
+                        key: key("LControl"),
+                    },
+                    200,
+                ),
+                event(MacroEventType::KeyPress { key: key("A") }, 210),
+                event(MacroEventType::KeyRelease { key: key("A") }, 260),
+                event(
+                    MacroEventType::KeyRelease {
+                        key: key("LControl"),
+                    },
+                    270,
+                ),
+                event(
+                    MacroEventType::KeyPress {
+                        key: key("Unknown"),
+                    },
+                    270,
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn export_writes_runnable_script() {
+        let script = xdotool::export(&sample());
+        let body: Vec<&str> = script.lines().skip_while(|l| !l.is_empty()).skip(1).collect();
+        assert!(script.starts_with("#!/usr/bin/env bash\n# Exported from mousepilot: demo\n"));
+        assert_eq!(
+            body,
+            [
+                "xdotool mousemove 10 20",
+                "sleep 0.120",
+                "xdotool mousedown 1",
+                "sleep 0.060",
+                "xdotool mouseup 1",
+                "sleep 1.500",
+                "sleep 0.020",
+                "xdotool keydown Control_L",
+                "sleep 0.010",
+                "xdotool keydown a",
+                "sleep 0.050",
+                "xdotool keyup a",
+                "sleep 0.010",
+                "xdotool keyup Control_L",
+                "# unsupported key: keydown Unknown",
+            ]
+        );
+    }
+
+    #[test]
+    fn export_escapes_untrusted_text_in_comments() {
+        let mut saved_macro = sample();
+        saved_macro.name = "evil\rname".to_string();
+        saved_macro.events = vec![event(
+            MacroEventType::KeyPress {
+                key: "x\nrm -rf ~".to_string(),
+            },
+            0,
+        )];
+        let script = xdotool::export(&saved_macro);
+        assert!(script.contains("# Exported from mousepilot: evil\\rname\n"));
+        assert!(script.contains("# unsupported key: keydown x\\nrm -rf ~\n"));
+        assert!(!script.lines().any(|line| line.starts_with("rm ")));
+        assert!(!script.contains('\r'));
+    }
+
+    #[test]
+    fn import_round_trips_exported_script() {
+        let original = sample();
+        let imported = xdotool::import("demo", &xdotool::export(&original)).unwrap();
+        // 延时事件变成时间间隔, 总时长不变
+        assert_eq!(imported.stats.duration_ms, original.stats.duration_ms);
+        assert_eq!(imported.stats.mouse_clicks, 1);
+        assert_eq!(imported.stats.key_events, 4);
+        assert_eq!(imported.events[1].timestamp, 120);
+        assert_eq!(imported.events[3].timestamp, 1700);
+    }
+
+    #[test]
+    fn import_supports_simple_shorthand() {
+        let script = "xdotool mousemove --sync 5 6\nxdotool click 3\nsleep 0.25\nxdotool key ctrl+V Return\n";
+        let imported = xdotool::import("paste", script).unwrap();
+        let keys: Vec<String> = imported
+            .events
+            .iter()
+            .filter_map(|e| match &e.event_type {
+                MacroEventType::KeyPress { key } => Some(format!("+{key}")),
+                MacroEventType::KeyRelease { key } => Some(format!("-{key}")),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(keys, ["+LControl", "+V", "-V", "-LControl", "+Enter", "-Enter"]);
+        assert_eq!(imported.stats.mouse_clicks, 1);
+        assert_eq!(imported.events.last().unwrap().timestamp, 250);
+
+        let result = xdotool::import("bad", "xdotool mousemove 1 2\nxdotool type hello\n");
+        assert!(matches!(result, Err(MacroError::Format(e)) if e.starts_with("line 2:")));
+        assert!(xdotool::import("bad", "xdotool key NoSuchKey").is_err());
+    }
+
+    #[test]
+    fn import_rejects_huge_sleeps() {
+        let result = xdotool::import("bad", "sleep 1e300\n");
+        assert!(matches!(result, Err(MacroError::Format(e)) if e.starts_with("line 1:")));
+        let result = xdotool::import("bad", "sleep 0.5\nsleep 86401\n");
+        assert!(matches!(result, Err(MacroError::Format(e)) if e.starts_with("line 2:")));
+
+        let imported = xdotool::import("long", "sleep 86400\nxdotool click 1\n").unwrap();
+        assert_eq!(imported.events.last().unwrap().timestamp, 86_400_000);
+    }
+}